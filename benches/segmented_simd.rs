@@ -0,0 +1,103 @@
+// Compares segmented correlation position by position, in scalar batches and in SIMD batches.
+// run with cargo bench --features dev --bench segmented_simd
+
+use rayon::prelude::*;
+use rustautogui::core::template_match::{segmented_ncc, segmented_simd, IntegralImage};
+use rustautogui::data::PreparedData;
+use rustautogui::imgtools;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 20;
+const PRECISION: f32 = 0.9;
+
+/// median duration of function over ITERATIONS runs, after one warm up run
+fn median_time(mut f: impl FnMut() -> usize) -> (Duration, usize) {
+    let found = f();
+    let mut times: Vec<Duration> = (0..ITERATIONS)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .collect();
+    times.sort();
+    (times[times.len() / 2], found)
+}
+
+fn main() {
+    let pairs = [
+        ("Darts_main.png", "Darts_template1.png"),
+        ("Darts_main.png", "Darts_template2.png"),
+        ("Darts_main.png", "Darts_template3.png"),
+        ("Socket_main.png", "Socket_template1.png"),
+        ("Socket_main.png", "Socket_template2.png"),
+        ("Socket_main.png", "Socket_template3.png"),
+        ("Split_main.png", "Split_template1.png"),
+        ("Split_main.png", "Split_template2.png"),
+        ("Split_main.png", "Split_template4.png"),
+        ("Split_main.png", "Split_template5.png"),
+    ];
+    segmented_simd::set_simd_enabled(true);
+    println!("SIMD implementation: {}", segmented_simd::simd_backend());
+    println!(
+        "{:<22} {:>14} {:>14} {:>14} {:>8}",
+        "template", "per position", "scalar batch", "simd batch", "speedup"
+    );
+    let folder = Path::new("tests/testing_images/algorithm_tests");
+    for (image_name, template_name) in pairs {
+        let (image_path, template_path) = (folder.join(image_name), folder.join(template_name));
+        if !image_path.exists() || !template_path.exists() {
+            println!("{template_name:<22} skipped, image not found");
+            continue;
+        }
+        let image = imgtools::load_image_bw(image_path.to_str().unwrap()).unwrap();
+        let template = imgtools::load_image_bw(template_path.to_str().unwrap()).unwrap();
+        let template_data = match segmented_ncc::prepare_template_picture(&template, &false, None) {
+            PreparedData::Segmented(data) => data,
+            _ => unreachable!(),
+        };
+        let integral_image = IntegralImage::new(&image);
+        let region = (0, 0, image.width(), image.height());
+
+        // search as it was done before batching, every position on its own
+        let (per_position, found_per_position) = median_time(|| {
+            let min_fast = PRECISION * template_data.expected_corr_fast - 0.0001;
+            let min_slow = PRECISION * template_data.expected_corr_slow - 0.0001;
+            let positions_x = image.width() - template_data.template_width + 1;
+            let positions_y = image.height() - template_data.template_height + 1;
+            (0..positions_x * positions_y)
+                .into_par_iter()
+                .filter(|i| {
+                    let (x, y) = (i % positions_x, i / positions_x);
+                    segmented_ncc::correlation_at(&integral_image, &template_data, x, y, min_fast)
+                        >= min_slow
+                })
+                .count()
+        });
+        let batched = |simd_enabled| {
+            segmented_simd::set_simd_enabled(simd_enabled);
+            median_time(|| {
+                segmented_ncc::fast_ncc_template_match_in_region(
+                    &integral_image,
+                    region,
+                    PRECISION,
+                    &template_data,
+                )
+                .len()
+            })
+        };
+        let (scalar, found_scalar) = batched(false);
+        let (simd, found_simd) = batched(true);
+        assert_eq!(found_per_position, found_scalar);
+        assert_eq!(found_per_position, found_simd);
+        println!(
+            "{:<22} {:>14?} {:>14?} {:>14?} {:>7.2}x",
+            template_name,
+            per_position,
+            scalar,
+            simd,
+            per_position.as_secs_f64() / simd.as_secs_f64()
+        );
+    }
+}
@@ -6,5 +6,9 @@ fn main() {
     if env::var("CARGO_CFG_TARGET_OS").unwrap() == "linux" {
         println!("cargo:rustc-link-lib=X11");
         println!("cargo:rustc-link-lib=Xtst");
+        // composite extension is used for capturing single window contents
+        if env::var("CARGO_FEATURE_LITE").is_err() {
+            println!("cargo:rustc-link-lib=Xcomposite");
+        }
     }
 }
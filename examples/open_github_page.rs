@@ -75,7 +75,12 @@ fn main() {
 
         // loop till image of star is found or timeout of 15 seconds is hit
         rustautogui
-            .loop_find_stored_image_on_screen_and_move_mouse(0.95, 1.0, 15, "stars")
+            .loop_find_stored_image_on_screen_and_move_mouse(
+                0.95,
+                1.0,
+                time::Duration::from_secs(15),
+                "stars",
+            )
             .unwrap();
 
         thread::sleep(time::Duration::from_millis(500));
@@ -87,7 +92,7 @@ fn main() {
 
         // loop till image found with timeout of 15 seconds
         rustautogui
-            .loop_find_image_on_screen_and_move_mouse(0.95, 1.0, 15)
+            .loop_find_image_on_screen_and_move_mouse(0.95, 1.0, time::Duration::from_secs(15))
             .unwrap();
 
         thread::sleep(time::Duration::from_millis(500));
@@ -0,0 +1,199 @@
+/*
+Async wrapper around RustAutoGui for tokio based applications.
+Screen capture and template matching run on tokio blocking thread pool, while waiting between
+checks and mouse movement steps is done with tokio timers, so executor threads are never blocked.
+Dropping a returned future cancels the operation after the currently running step.
+*/
+
+#[cfg(not(feature = "lite"))]
+use crate::core::polling::poll_async;
+#[cfg(not(feature = "lite"))]
+use crate::ImageProcessingError;
+use crate::{AutoGuiError, MouseClick, PollingPolicy, RustAutoGui};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// time between mouse position updates when moving mouse
+const MOUSE_MOVE_STEP: Duration = Duration::from_millis(10);
+
+/// Cloneable async handle to RustAutoGui. All clones share the same instance
+#[derive(Clone)]
+pub struct AsyncRustAutoGui {
+    gui: Arc<Mutex<RustAutoGui>>,
+}
+
+impl AsyncRustAutoGui {
+    pub fn new(debug: bool) -> Result<Self, AutoGuiError> {
+        Ok(Self::from(RustAutoGui::new(debug)?))
+    }
+
+    /// Runs closure with exclusive access to RustAutoGui on tokio blocking thread pool.
+    /// Used for any sync function that does not have async variant, for instance loading templates
+    pub async fn run<T, F>(&self, f: F) -> Result<T, AutoGuiError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut RustAutoGui) -> Result<T, AutoGuiError> + Send + 'static,
+    {
+        let gui = Arc::clone(&self.gui);
+        tokio::task::spawn_blocking(move || {
+            let mut gui = gui
+                .lock()
+                .map_err(|_| AutoGuiError::OSFailure("RustAutoGui lock poisoned".to_string()))?;
+            f(&mut gui)
+        })
+        .await
+        .map_err(|e| AutoGuiError::OSFailure(format!("Blocking task failed: {}", e)))?
+    }
+
+    #[cfg(not(feature = "lite"))]
+    /// async variant of find_stored_image_on_screen
+    pub async fn find_stored_image_on_screen(
+        &self,
+        precision: f32,
+        alias: &str,
+    ) -> Result<Option<Vec<(u32, u32, f32)>>, AutoGuiError> {
+        let alias = alias.to_string();
+        self.run(move |gui| gui.find_stored_image_on_screen(precision, &alias))
+            .await
+    }
+
+    #[cfg(not(feature = "lite"))]
+    /// Async variant of loop_find_stored_image_on_screen. Waits until image stored under provided alias
+    /// is found and returns found values, or error if timeout passed or attempts ran out.
+    /// Checks are spaced by polling policy set with set_polling_policy. Timeout of 0 waits indefinitely
+    pub async fn wait_for_image(
+        &self,
+        precision: f32,
+        timeout: Duration,
+        alias: &str,
+    ) -> Result<Option<Vec<(u32, u32, f32)>>, AutoGuiError> {
+        let policy = self.run(|gui| Ok(gui.get_polling_policy())).await?;
+        self.wait_for_image_with_policy(precision, timeout, policy, alias)
+            .await
+    }
+
+    #[cfg(not(feature = "lite"))]
+    /// same as wait_for_image, with polling policy used only for this call
+    pub async fn wait_for_image_with_policy(
+        &self,
+        precision: f32,
+        timeout: Duration,
+        policy: PollingPolicy,
+        alias: &str,
+    ) -> Result<Option<Vec<(u32, u32, f32)>>, AutoGuiError> {
+        let alias = alias.to_string();
+        let found = self
+            .poll(&policy, timeout, || {
+                let alias = alias.clone();
+                self.run(move |gui| gui.find_stored_image_on_screen(precision, &alias))
+            })
+            .await?
+            .ok_or(ImageProcessingError::new(
+                "loop find image timed out or ran out of attempts. Could not find image",
+            ))?;
+        Ok(Some(found))
+    }
+
+    #[cfg(not(feature = "lite"))]
+    /// async variant of wait_until_image_vanishes.
+    /// Returns Ok(true) if image vanished and Ok(false) if timeout passed while image was still visible
+    pub async fn wait_until_image_vanishes(
+        &self,
+        precision: f32,
+        timeout: Duration,
+        alias: &str,
+    ) -> Result<bool, AutoGuiError> {
+        let policy = self.run(|gui| Ok(gui.get_polling_policy())).await?;
+        let alias = alias.to_string();
+        let vanished = self
+            .poll(&policy, timeout, || {
+                let alias = alias.clone();
+                self.run(move |gui| {
+                    Ok(gui
+                        .find_stored_image_on_screen(precision, &alias)?
+                        .is_none()
+                        .then_some(()))
+                })
+            })
+            .await?;
+        Ok(vanished.is_some())
+    }
+
+    /// Moves mouse to x,y pixel coordinate over moving_time seconds. Mouse position is updated in steps,
+    /// awaiting a timer between them, so dropping the future stops the mouse where it currently is
+    pub async fn move_mouse_to_pos(
+        &self,
+        x: u32,
+        y: u32,
+        moving_time: f32,
+    ) -> Result<(), AutoGuiError> {
+        if moving_time <= 0.0 {
+            return self.run(move |gui| gui.move_mouse_to_pos(x, y, 0.0)).await;
+        }
+        // target is checked before the first step, so out of screen target does not move the mouse
+        let (start_x, start_y) = self
+            .run(move |gui| {
+                gui.check_mouse_target(x, y)?;
+                gui.get_mouse_position()
+            })
+            .await?;
+        let distance_x = x as f32 - start_x as f32;
+        let distance_y = y as f32 - start_y as f32;
+        let start = Instant::now();
+        loop {
+            let time_passed_percentage = start.elapsed().as_secs_f32() / moving_time;
+            if time_passed_percentage >= 1.0 {
+                // final position is logged in dry run and followed by pause
+                return self.run(move |gui| gui.move_mouse_to_pos(x, y, 0.0)).await;
+            }
+            let new_x = (start_x as f32 + time_passed_percentage * distance_x) as u32;
+            let new_y = (start_y as f32 + time_passed_percentage * distance_y) as u32;
+            self.run(move |gui| gui.move_mouse_step(new_x, new_y))
+                .await?;
+            tokio::time::sleep(MOUSE_MOVE_STEP).await;
+        }
+    }
+
+    /// async variant of click
+    pub async fn click(&self, button: MouseClick) -> Result<(), AutoGuiError> {
+        self.run(move |gui| gui.click(button)).await
+    }
+
+    /// async variant of keyboard_input
+    pub async fn keyboard_input(&self, input: &str) -> Result<(), AutoGuiError> {
+        let input = input.to_string();
+        self.run(move |gui| gui.keyboard_input(&input)).await
+    }
+
+    /// async variant of keyboard_command
+    pub async fn keyboard_command(&self, input: &str) -> Result<(), AutoGuiError> {
+        let input = input.to_string();
+        self.run(move |gui| gui.keyboard_command(&input)).await
+    }
+
+    #[cfg(not(feature = "lite"))]
+    /// Async counterpart of RustAutoGui::poll. Awaits attempt until it returns Some,
+    /// sleeping on tokio timer between attempts as defined by policy.
+    /// Returns Ok(None) when timeout passes or attempts run out
+    async fn poll<T, F, Fut>(
+        &self,
+        policy: &PollingPolicy,
+        timeout: Duration,
+        attempt: F,
+    ) -> Result<Option<T>, AutoGuiError>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<Option<T>, AutoGuiError>>,
+    {
+        let suppress_warnings = self.run(|gui| Ok(gui.suppress_warnings)).await?;
+        poll_async(policy, timeout, suppress_warnings, attempt).await
+    }
+}
+
+impl From<RustAutoGui> for AsyncRustAutoGui {
+    fn from(gui: RustAutoGui) -> Self {
+        Self {
+            gui: Arc::new(Mutex::new(gui)),
+        }
+    }
+}
@@ -0,0 +1,358 @@
+/*
+Debug artifacts for template matching. In debug mode every search saves the searched region with
+found locations drawn over it, or with the best match below threshold when nothing was found.
+With debug heatmap turned on, it instead saves best candidates, above and below threshold, and a
+correlation heatmap of whole region, which requires correlation at every position and so runs
+the search again. Used for diagnosing searches that did not find the image.
+*/
+
+use crate::core::template_match::{fft_ncc, segmented_ncc, IntegralImage};
+use crate::data::PreparedData;
+use crate::{imgtools, AutoGuiError};
+use image::{ImageBuffer, Luma, Rgb, RgbImage};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// number of best candidates drawn on annotated image
+const MAX_CANDIDATES: usize = 10;
+const FOUND_COLOR: Rgb<u8> = Rgb([0, 220, 0]);
+const NOT_FOUND_COLOR: Rgb<u8> = Rgb([230, 0, 0]);
+const TEXT_BACKGROUND: Rgb<u8> = Rgb([0, 0, 0]);
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+
+/// correlation value for every position template can be placed at in searched image
+pub struct CorrelationMap {
+    pub width: u32,
+    pub height: u32,
+    pub values: Vec<f32>,
+}
+
+impl CorrelationMap {
+    /// Computes correlation at every position with the same algorithm prepared data was made for.
+    /// Positions the algorithm discards as negative correlation are left at 0.
+    /// Returns None if template is larger than image or no data is prepared.
+    /// Features match mode has no correlation map, so it returns None as well
+    pub fn compute(
+        image: &ImageBuffer<Luma<u8>, Vec<u8>>,
+        prepared_data: &PreparedData,
+    ) -> Option<Self> {
+        let (template_width, template_height) = template_size(prepared_data)?;
+        let (image_width, image_height) = image.dimensions();
+        if (image_width < template_width) | (image_height < template_height) {
+            return None;
+        }
+        let width = image_width - template_width + 1;
+        let height = image_height - template_height + 1;
+        // precision 0 keeps every non negative correlation
+        let positions: Vec<(u32, u32, f32)> = match prepared_data {
+            PreparedData::Segmented(data) => segmented_ncc::fast_ncc_template_match_in_region(
+                &IntegralImage::new(image),
+                (0, 0, image_width, image_height),
+                0.0,
+                data,
+            ),
+            PreparedData::FFT(data) => fft_ncc::fft_ncc(image, 0.0, data)
+                .into_iter()
+                .map(|(x, y, corr)| (x, y, corr as f32))
+                .collect(),
+            PreparedData::Edge(data) => fft_ncc::fft_ncc(&imgtools::edge_map(image), 0.0, data)
+                .into_iter()
+                .map(|(x, y, corr)| (x, y, corr as f32))
+                .collect(),
+            PreparedData::Features(_) | PreparedData::None => return None,
+            #[cfg(feature = "opencl")]
+            PreparedData::Dense(_) => return None,
+        };
+        let mut values = vec![0.0; (width * height) as usize];
+        for (x, y, corr) in positions {
+            values[(y * width + x) as usize] = corr.clamp(0.0, 1.0);
+        }
+        Some(Self {
+            width,
+            height,
+            values,
+        })
+    }
+
+    /// Returns best local maxima, sorted from highest correlation. Candidates closer than
+    /// half of template size to a better candidate are skipped
+    pub fn best_candidates(&self, template_size: (u32, u32), count: usize) -> Vec<(u32, u32, f32)> {
+        let mut order: Vec<usize> = (0..self.values.len()).collect();
+        order.sort_unstable_by(|a, b| self.values[*b].total_cmp(&self.values[*a]));
+        let min_distance_x = (template_size.0 / 2).max(1) as i64;
+        let min_distance_y = (template_size.1 / 2).max(1) as i64;
+        let mut candidates: Vec<(u32, u32, f32)> = Vec::new();
+        for index in order {
+            if candidates.len() >= count {
+                break;
+            }
+            let x = index as u32 % self.width;
+            let y = index as u32 / self.width;
+            let too_close = candidates.iter().any(|(cx, cy, _)| {
+                ((*cx as i64 - x as i64).abs() < min_distance_x)
+                    & ((*cy as i64 - y as i64).abs() < min_distance_y)
+            });
+            if !too_close {
+                candidates.push((x, y, self.values[index]));
+            }
+        }
+        candidates
+    }
+
+    /// heatmap image, where blue is no correlation and red is full correlation
+    pub fn heatmap(&self) -> RgbImage {
+        ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            heat_color(self.values[(y * self.width + x) as usize])
+        })
+    }
+}
+
+/// Best match in searched image no matter how low its correlation is, found with best match
+/// search of the algorithm prepared data was made for. Unlike correlation map, it does not keep
+/// correlation of every position. None for modes without best match search
+pub fn best_candidate(
+    image: &ImageBuffer<Luma<u8>, Vec<u8>>,
+    prepared_data: &PreparedData,
+) -> Option<(u32, u32, f32)> {
+    match prepared_data {
+        PreparedData::Segmented(data) => segmented_ncc::fast_ncc_best_match_in_region(
+            &IntegralImage::new(image),
+            (0, 0, image.width(), image.height()),
+            data,
+        ),
+        PreparedData::FFT(data) => {
+            fft_ncc::fft_ncc_best_match(image, data).map(|(x, y, corr)| (x, y, corr as f32))
+        }
+        PreparedData::Edge(data) => fft_ncc::fft_ncc_best_match(&imgtools::edge_map(image), data)
+            .map(|(x, y, corr)| (x, y, corr as f32)),
+        _ => None,
+    }
+}
+
+/// Saves searched region annotated with locations the search already found, so correlation is
+/// not computed again. When nothing was found, best match below threshold is searched and drawn
+/// instead, to show where template came closest to matching. Locations are relative to searched region.
+/// Returns path of saved image, or None if prepared data has no template size to draw
+pub fn save_found_artifacts(
+    directory: &Path,
+    alias: &str,
+    image: &ImageBuffer<Luma<u8>, Vec<u8>>,
+    prepared_data: &PreparedData,
+    found: &[(u32, u32, f32)],
+) -> Result<Option<PathBuf>, AutoGuiError> {
+    let Some(template_size) = template_size(prepared_data) else {
+        return Ok(None);
+    };
+    let (candidates, threshold) = if found.is_empty() {
+        // best candidate did not pass the threshold, since nothing was found
+        let best: Vec<(u32, u32, f32)> = best_candidate(image, prepared_data).into_iter().collect();
+        (best, f32::INFINITY)
+    } else {
+        let mut found = found.to_vec();
+        found.sort_unstable_by(|a, b| b.2.total_cmp(&a.2));
+        found.truncate(MAX_CANDIDATES);
+        // every found location passed the threshold
+        (found, f32::MIN)
+    };
+    let annotated = annotate(image, alias, template_size, &candidates, threshold);
+
+    let file_prefix = file_prefix(directory, alias)?;
+    let annotated_path = directory.join(format!("{}_matches.png", file_prefix));
+    annotated.save(&annotated_path)?;
+    Ok(Some(annotated_path))
+}
+
+/// Saves searched region annotated with best candidates and correlation heatmap into directory.
+/// Unlike save_found_artifacts, it computes correlation at every position, which runs the whole search again.
+/// Files are prefixed with milliseconds timestamp and alias. Returns paths of saved images,
+/// or None if there is no prepared data to compute correlation with
+pub fn save_match_artifacts(
+    directory: &Path,
+    alias: &str,
+    image: &ImageBuffer<Luma<u8>, Vec<u8>>,
+    prepared_data: &PreparedData,
+    precision: f32,
+) -> Result<Option<(PathBuf, PathBuf)>, AutoGuiError> {
+    let Some(template_size) = template_size(prepared_data) else {
+        return Ok(None);
+    };
+    let Some(correlation_map) = CorrelationMap::compute(image, prepared_data) else {
+        return Ok(None);
+    };
+    // correlation a candidate needs to be reported as found
+    let threshold = match prepared_data {
+        PreparedData::Segmented(data) => precision * data.expected_corr_slow,
+        _ => precision,
+    };
+    let candidates = correlation_map.best_candidates(template_size, MAX_CANDIDATES);
+    let annotated = annotate(image, alias, template_size, &candidates, threshold);
+
+    let file_prefix = file_prefix(directory, alias)?;
+    let annotated_path = directory.join(format!("{}_matches.png", file_prefix));
+    let heatmap_path = directory.join(format!("{}_heatmap.png", file_prefix));
+    annotated.save(&annotated_path)?;
+    correlation_map.heatmap().save(&heatmap_path)?;
+    Ok(Some((annotated_path, heatmap_path)))
+}
+
+/// grayscale image converted to rgb with candidates drawn over it, sorted from the best one.
+/// Candidates at or above threshold are drawn as found
+fn annotate(
+    image: &ImageBuffer<Luma<u8>, Vec<u8>>,
+    alias: &str,
+    template_size: (u32, u32),
+    candidates: &[(u32, u32, f32)],
+    threshold: f32,
+) -> RgbImage {
+    let mut annotated: RgbImage = ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+        let value = image.get_pixel(x, y)[0];
+        Rgb([value, value, value])
+    });
+    // worst candidates drawn first, so best ones end up on top
+    for (x, y, corr) in candidates.iter().rev() {
+        let color = if *corr >= threshold {
+            FOUND_COLOR
+        } else {
+            NOT_FOUND_COLOR
+        };
+        draw_rectangle(
+            &mut annotated,
+            (*x, *y, template_size.0, template_size.1),
+            color,
+        );
+        let label = format!("{} {:.3}", alias, corr);
+        // label above the box, or inside it when box touches top of the image
+        let label_y = if *y > GLYPH_HEIGHT + 2 {
+            y - GLYPH_HEIGHT - 2
+        } else {
+            y + 2
+        };
+        draw_text(&mut annotated, &label, x + 1, label_y, color);
+    }
+    annotated
+}
+
+/// creates directory and returns file name prefix made of milliseconds timestamp and alias
+fn file_prefix(directory: &Path, alias: &str) -> Result<String, AutoGuiError> {
+    fs::create_dir_all(directory)?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_millis())
+        .unwrap_or(0);
+    let file_alias: String = alias
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() | (c == '-') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    Ok(format!("{}_{}", timestamp, file_alias))
+}
+
+fn template_size(prepared_data: &PreparedData) -> Option<(u32, u32)> {
+    match prepared_data {
+        PreparedData::Segmented(data) => Some((data.template_width, data.template_height)),
+        PreparedData::FFT(data) | PreparedData::Edge(data) => {
+            Some((data.template_width, data.template_height))
+        }
+        #[cfg(feature = "opencl")]
+        PreparedData::Dense(data) => Some((data.template_width, data.template_height)),
+        PreparedData::Features(_) | PreparedData::None => None,
+    }
+}
+
+fn heat_color(value: f32) -> Rgb<u8> {
+    let value = value.clamp(0.0, 1.0);
+    let channel =
+        |center: f32| ((1.5 - (4.0 * value - center).abs()).clamp(0.0, 1.0) * 255.0) as u8;
+    Rgb([channel(3.0), channel(2.0), channel(1.0)])
+}
+
+fn draw_rectangle(image: &mut RgbImage, rect: (u32, u32, u32, u32), color: Rgb<u8>) {
+    let (x, y, width, height) = rect;
+    let x_end = (x + width).min(image.width()).saturating_sub(1);
+    let y_end = (y + height).min(image.height()).saturating_sub(1);
+    for px in x..=x_end {
+        image.put_pixel(px, y, color);
+        image.put_pixel(px, y_end, color);
+    }
+    for py in y..=y_end {
+        image.put_pixel(x, py, color);
+        image.put_pixel(x_end, py, color);
+    }
+}
+
+/// draws text with built in 5x7 pixel font on black background, cut at image borders
+fn draw_text(image: &mut RgbImage, text: &str, x: u32, y: u32, color: Rgb<u8>) {
+    let (width, height) = image.dimensions();
+    for (i, c) in text.chars().enumerate() {
+        let glyph_x = x + i as u32 * (GLYPH_WIDTH + 1);
+        let rows = glyph(c);
+        for dy in 0..=GLYPH_HEIGHT {
+            for dx in 0..=GLYPH_WIDTH {
+                let (px, py) = (glyph_x + dx, y + dy);
+                if (px >= width) | (py >= height) {
+                    continue;
+                }
+                let lit = (dy < GLYPH_HEIGHT) & (dx < GLYPH_WIDTH)
+                    && (rows[dy as usize] >> (GLYPH_WIDTH - 1 - dx)) & 1 == 1;
+                image.put_pixel(px, py, if lit { color } else { TEXT_BACKGROUND });
+            }
+        }
+    }
+}
+
+/// rows of 5x7 glyph, highest of 5 bits is leftmost pixel. Letters are drawn as uppercase
+fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        ' ' => [0x00; 7],
+        _ => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    }
+}
@@ -0,0 +1,76 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Screen corner which triggers the fail safe when mouse is moved into it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScreenCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Emergency stop for mouse and keyboard input. Once triggered it stays triggered until reset.
+/// Triggered by moving mouse into one of selected corners, by pressing hotkey
+/// or by setting shared switch from any thread
+#[derive(Debug, Clone)]
+pub struct FailSafe {
+    pub corners: Vec<ScreenCorner>,
+    // size of square in each corner that triggers fail safe, in pixels
+    pub corner_size: u32,
+    pub hotkey: Option<String>,
+    triggered: Arc<AtomicBool>,
+}
+
+impl FailSafe {
+    /// disabled fail safe, without corners and hotkey. Can still be triggered by switch
+    pub fn new() -> Self {
+        Self {
+            corners: Vec::new(),
+            corner_size: 1,
+            hotkey: None,
+            triggered: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.triggered.load(Ordering::SeqCst)
+    }
+
+    pub fn trigger(&self) {
+        self.triggered.store(true, Ordering::SeqCst);
+    }
+
+    pub fn reset(&self) {
+        self.triggered.store(false, Ordering::SeqCst);
+    }
+
+    /// shared flag, setting it to true triggers the fail safe
+    pub fn switch(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.triggered)
+    }
+
+    /// checks if position lies inside one of selected corners of screen with provided size
+    pub fn is_in_corner(&self, position: (i32, i32), screen_size: (i32, i32)) -> bool {
+        let size = self.corner_size.max(1) as i32;
+        let (x, y) = position;
+        let left = x < size;
+        let right = x >= screen_size.0 - size;
+        let top = y < size;
+        let bottom = y >= screen_size.1 - size;
+        self.corners.iter().any(|corner| match corner {
+            ScreenCorner::TopLeft => left & top,
+            ScreenCorner::TopRight => right & top,
+            ScreenCorner::BottomLeft => left & bottom,
+            ScreenCorner::BottomRight => right & bottom,
+        })
+    }
+}
+
+impl Default for FailSafe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
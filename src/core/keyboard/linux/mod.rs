@@ -1,18 +1,19 @@
 use super::get_keymap_key;
+use crate::core::x11_display::X11Display;
 use crate::errors::AutoGuiError;
 use std::{collections::HashMap, ffi::CString, process::Command, thread, time::Duration};
-use x11::xlib::{CurrentTime, XFlush, XKeysymToKeycode, XStringToKeysym, _XDisplay};
+use x11::xlib::{CurrentTime, XFlush, XKeysymToKeycode, XQueryKeymap, XStringToKeysym};
 use x11::xtest::XTestFakeKeyEvent;
 /// main struct for interacting with keyboard. Keymap is generated upon intialization.
 /// screen is stored from Screen struct, where pointer for same screen object is used across the code
 #[derive(Debug)]
 pub struct Keyboard {
     pub keymap: HashMap<String, (String, bool)>,
-    screen: *mut _XDisplay,
+    screen: X11Display,
 }
 impl Keyboard {
     /// create new keyboard instance. Display object is needed as argument
-    pub fn new(screen: *mut _XDisplay) -> Self {
+    pub fn new(screen: X11Display) -> Self {
         // for future development
         let is_us_layout: bool = Self::is_us_layout();
 
@@ -39,15 +40,26 @@ impl Keyboard {
         Ok(())
     }
 
+    /// checks if key is currently held down on physical keyboard
+    pub fn is_key_pressed(&self, key: &str) -> Result<bool, AutoGuiError> {
+        unsafe {
+            let (keycode, _) = self.get_keycode(key)?;
+            // bit vector of 256 keys, one bit per keycode
+            let mut keys = [0; 32];
+            XQueryKeymap(self.screen.as_ptr(), keys.as_mut_ptr());
+            Ok(keys[(keycode / 8) as usize] as u8 & (1 << (keycode % 8)) != 0)
+        }
+    }
+
     /// Function that presses key down. When sending key, press key down and release key is executed
     unsafe fn press_key(&self, keycode: u32) {
-        XTestFakeKeyEvent(self.screen, keycode, 1, CurrentTime);
-        XFlush(self.screen);
+        XTestFakeKeyEvent(self.screen.as_ptr(), keycode, 1, CurrentTime);
+        XFlush(self.screen.as_ptr());
     }
     /// Function that releases key up. When sending key, press key down and release key is executed
     unsafe fn release_key(&self, keycode: u32) {
-        XTestFakeKeyEvent(self.screen, keycode, 0, CurrentTime);
-        XFlush(self.screen);
+        XTestFakeKeyEvent(self.screen.as_ptr(), keycode, 0, CurrentTime);
+        XFlush(self.screen.as_ptr());
     }
 
     /// send a key by press down and release up
@@ -118,7 +130,7 @@ impl Keyboard {
 
             let keysym = XStringToKeysym(key_cstring);
             if !keysym_to_keycode2.contains_key(&keysym) {
-                let keycode = XKeysymToKeycode(self.screen, keysym) as u32;
+                let keycode = XKeysymToKeycode(self.screen.as_ptr(), keysym) as u32;
                 keysym_to_keycode2.insert(keysym, keycode);
             }
             let keycode = keysym_to_keycode2[&keysym];
@@ -145,7 +157,7 @@ impl Keyboard {
             ));
         }
         if !keysym_to_keycode.contains_key(&keysym) {
-            let keycode = XKeysymToKeycode(self.screen, keysym) as u32;
+            let keycode = XKeysymToKeycode(self.screen.as_ptr(), keysym) as u32;
             keysym_to_keycode.insert(keysym, keycode);
         }
         let keycode = keysym_to_keycode[&keysym];
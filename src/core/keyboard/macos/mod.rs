@@ -10,6 +10,11 @@ use crate::errors::AutoGuiError;
 
 use super::get_keymap_key;
 
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    fn CGEventSourceKeyState(state_id: CGEventSourceStateID, key: CGKeyCode) -> bool;
+}
+
 pub struct Keyboard {
     pub keymap: HashMap<String, (u16, bool)>,
 }
@@ -66,6 +71,12 @@ impl Keyboard {
         Ok(())
     }
 
+    /// checks if key is currently held down on physical keyboard
+    pub fn is_key_pressed(&self, key: &str) -> Result<bool, AutoGuiError> {
+        let value = get_keymap_key(self, key)?;
+        Ok(unsafe { CGEventSourceKeyState(CGEventSourceStateID::HIDSystemState, value.0) })
+    }
+
     fn send_shifted_key(&self, keycode: CGKeyCode) -> Result<(), AutoGuiError> {
         self.press_key(KeyCode::SHIFT)?;
         self.send_key(keycode)?;
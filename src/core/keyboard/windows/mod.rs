@@ -2,7 +2,7 @@ use crate::core::keyboard::get_keymap_key;
 use crate::errors::AutoGuiError;
 use std::{collections::HashMap, mem::size_of, thread::sleep, time::Duration};
 use winapi::um::wingdi::SRCAND;
-use winapi::um::winuser::{MapVirtualKeyW, MAPVK_VK_TO_VSC};
+use winapi::um::winuser::{GetAsyncKeyState, MapVirtualKeyW, MAPVK_VK_TO_VSC};
 use winapi::um::winuser::{
     SendInput, INPUT, INPUT_KEYBOARD, KEYEVENTF_KEYUP, KEYEVENTF_SCANCODE, VK_CONTROL, VK_MENU,
     VK_SHIFT,
@@ -90,6 +90,14 @@ impl Keyboard {
         Ok(())
     }
 
+    /// checks if key is currently held down on physical keyboard
+    pub fn is_key_pressed(&self, key: &str) -> Result<bool, AutoGuiError> {
+        let (value, _) = get_keymap_key(self, key)?;
+        // most significant bit is set while key is down
+        let state = unsafe { GetAsyncKeyState(*value as i32) };
+        Ok(state as u16 & 0x8000 != 0)
+    }
+
     /// executes press down of a key, then press up.
     pub fn send_key(scan_code: &u16) {
         unsafe {
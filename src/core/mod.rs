@@ -1,5 +1,11 @@
+#[cfg(not(feature = "lite"))]
+pub mod debug_artifacts;
+pub mod failsafe;
 pub mod keyboard;
 pub mod mouse;
+pub mod polling;
 pub mod screen;
 #[cfg(not(feature = "lite"))]
 pub mod template_match;
+#[cfg(target_os = "linux")]
+pub mod x11_display;
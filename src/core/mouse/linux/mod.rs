@@ -1,3 +1,4 @@
+use crate::core::x11_display::X11Display;
 use crate::errors::AutoGuiError;
 
 use super::{MouseClick, MouseScroll};
@@ -5,18 +6,18 @@ use std::time::Instant;
 use std::{ptr, thread, time::Duration};
 use x11::xlib::{
     CurrentTime, RevertToParent, Window, XDefaultRootWindow, XFlush, XQueryPointer, XSetInputFocus,
-    XTranslateCoordinates, XWarpPointer, _XDisplay,
+    XTranslateCoordinates, XWarpPointer,
 };
 use x11::xtest::{XTestFakeButtonEvent, XTestQueryExtension};
 
 #[derive(Debug)]
 pub struct Mouse {
-    screen: *mut _XDisplay,
+    screen: X11Display,
     root_window: u64,
 }
 
 impl Mouse {
-    pub fn new(screen: *mut _XDisplay, root_window: u64) -> Self {
+    pub fn new(screen: X11Display, root_window: u64) -> Self {
         Self {
             screen,
             root_window,
@@ -24,12 +25,20 @@ impl Mouse {
     }
 
     /// moves mouse to x, y pixel coordinate on screen
-    pub fn move_mouse_to_pos(&self, x: i32, y: i32, moving_time: f32) -> Result<(), AutoGuiError> {
+    /// moves mouse to x, y pixel coordinate. step_check is called before every movement step
+    /// and aborts the movement when it returns error
+    pub fn move_mouse_to_pos(
+        &self,
+        x: i32,
+        y: i32,
+        moving_time: f32,
+        step_check: &dyn Fn() -> Result<(), AutoGuiError>,
+    ) -> Result<(), AutoGuiError> {
         // if no moving time, then instant move is executed
         unsafe {
             if moving_time <= 0.0 {
-                XWarpPointer(self.screen, 0, self.root_window, 0, 0, 0, 0, x, y);
-                XFlush(self.screen);
+                XWarpPointer(self.screen.as_ptr(), 0, self.root_window, 0, 0, 0, 0, x, y);
+                XFlush(self.screen.as_ptr());
                 return Ok(());
             }
         }
@@ -40,6 +49,7 @@ impl Mouse {
         let distance_x = x - start_location.0;
         let distance_y = y - start_location.1;
         loop {
+            step_check()?;
             let duration = start.elapsed().as_secs_f32();
 
             let time_passed_percentage = duration / moving_time;
@@ -50,12 +60,12 @@ impl Mouse {
             let new_y = start_location.1 as f32 + (time_passed_percentage * distance_y as f32);
             unsafe {
                 if time_passed_percentage >= 1.0 {
-                    XWarpPointer(self.screen, 0, self.root_window, 0, 0, 0, 0, x, y);
-                    XFlush(self.screen);
+                    XWarpPointer(self.screen.as_ptr(), 0, self.root_window, 0, 0, 0, 0, x, y);
+                    XFlush(self.screen.as_ptr());
                     break;
                 } else {
                     XWarpPointer(
-                        self.screen,
+                        self.screen.as_ptr(),
                         0,
                         self.root_window,
                         0,
@@ -65,19 +75,25 @@ impl Mouse {
                         new_x as i32,
                         new_y as i32,
                     );
-                    XFlush(self.screen);
+                    XFlush(self.screen.as_ptr());
                 }
             }
         }
         Ok(())
     }
 
-    pub fn drag_mouse(&self, x: i32, y: i32, moving_time: f32) -> Result<(), AutoGuiError> {
+    pub fn drag_mouse(
+        &self,
+        x: i32,
+        y: i32,
+        moving_time: f32,
+        step_check: &dyn Fn() -> Result<(), AutoGuiError>,
+    ) -> Result<(), AutoGuiError> {
         let mut event_base = 0;
         let mut error_base = 0;
         unsafe {
             if XTestQueryExtension(
-                self.screen,
+                self.screen.as_ptr(),
                 &mut event_base,
                 &mut error_base,
                 &mut event_base,
@@ -92,17 +108,18 @@ impl Mouse {
                 self.set_focus_to_window(window);
             }
             // Press the mouse button
-            XTestFakeButtonEvent(self.screen, 1, 1, CurrentTime);
-            XFlush(self.screen);
+            XTestFakeButtonEvent(self.screen.as_ptr(), 1, 1, CurrentTime);
+            XFlush(self.screen.as_ptr());
         }
         thread::sleep(Duration::from_millis(50));
-        self.move_mouse_to_pos(x, y, moving_time)?;
+        // button is released even if movement was aborted
+        let moved = self.move_mouse_to_pos(x, y, moving_time, step_check);
         unsafe {
             // Release the mouse button
-            XTestFakeButtonEvent(self.screen, 1, 0, CurrentTime);
-            XFlush(self.screen);
+            XTestFakeButtonEvent(self.screen.as_ptr(), 1, 0, CurrentTime);
+            XFlush(self.screen.as_ptr());
         }
-        Ok(())
+        moved
     }
 
     /// returns x, y pixel coordinate of mouse position
@@ -117,7 +134,7 @@ impl Mouse {
             let mut mask_return = 0;
 
             let status = XQueryPointer(
-                self.screen,
+                self.screen.as_ptr(),
                 self.root_window,
                 &mut root_return,
                 &mut child_return,
@@ -150,7 +167,7 @@ impl Mouse {
         let mut error_base = 0;
         unsafe {
             if XTestQueryExtension(
-                self.screen,
+                self.screen.as_ptr(),
                 &mut event_base,
                 &mut error_base,
                 &mut event_base,
@@ -165,12 +182,12 @@ impl Mouse {
                 self.set_focus_to_window(window);
             }
             // Press the mouse button
-            XTestFakeButtonEvent(self.screen, button, 1, CurrentTime);
-            XFlush(self.screen);
+            XTestFakeButtonEvent(self.screen.as_ptr(), button, 1, CurrentTime);
+            XFlush(self.screen.as_ptr());
 
             // Release the mouse button
-            XTestFakeButtonEvent(self.screen, button, 0, CurrentTime);
-            XFlush(self.screen);
+            XTestFakeButtonEvent(self.screen.as_ptr(), button, 0, CurrentTime);
+            XFlush(self.screen.as_ptr());
         }
         Ok(())
     }
@@ -185,7 +202,7 @@ impl Mouse {
         let mut error_base = 0;
         unsafe {
             if XTestQueryExtension(
-                self.screen,
+                self.screen.as_ptr(),
                 &mut event_base,
                 &mut error_base,
                 &mut event_base,
@@ -200,8 +217,8 @@ impl Mouse {
                 self.set_focus_to_window(window);
             }
             // Press the mouse button
-            XTestFakeButtonEvent(self.screen, button, 1, CurrentTime);
-            XFlush(self.screen);
+            XTestFakeButtonEvent(self.screen.as_ptr(), button, 1, CurrentTime);
+            XFlush(self.screen.as_ptr());
         }
         Ok(())
     }
@@ -216,7 +233,7 @@ impl Mouse {
         let mut error_base = 0;
         unsafe {
             if XTestQueryExtension(
-                self.screen,
+                self.screen.as_ptr(),
                 &mut event_base,
                 &mut error_base,
                 &mut event_base,
@@ -231,8 +248,8 @@ impl Mouse {
                 self.set_focus_to_window(window);
             }
             // Press the mouse button
-            XTestFakeButtonEvent(self.screen, button, 0, CurrentTime);
-            XFlush(self.screen);
+            XTestFakeButtonEvent(self.screen.as_ptr(), button, 0, CurrentTime);
+            XFlush(self.screen.as_ptr());
         }
         Ok(())
     }
@@ -248,7 +265,7 @@ impl Mouse {
         let mut error_base = 0;
         unsafe {
             if XTestQueryExtension(
-                self.screen,
+                self.screen.as_ptr(),
                 &mut event_base,
                 &mut error_base,
                 &mut event_base,
@@ -263,12 +280,12 @@ impl Mouse {
             // }
             // Press the mouse button
             for _ in 0..intensity {
-                XTestFakeButtonEvent(self.screen, button, 1, CurrentTime);
-                XFlush(self.screen);
+                XTestFakeButtonEvent(self.screen.as_ptr(), button, 1, CurrentTime);
+                XFlush(self.screen.as_ptr());
 
                 // Release the mouse button
-                XTestFakeButtonEvent(self.screen, button, 0, CurrentTime);
-                XFlush(self.screen);
+                XTestFakeButtonEvent(self.screen.as_ptr(), button, 0, CurrentTime);
+                XFlush(self.screen.as_ptr());
             }
         }
     }
@@ -283,9 +300,9 @@ impl Mouse {
         unsafe {
             let (pos_x, pos_y) = self.get_mouse_position()?;
             if XTranslateCoordinates(
-                self.screen,
-                XDefaultRootWindow(self.screen),
-                XDefaultRootWindow(self.screen),
+                self.screen.as_ptr(),
+                XDefaultRootWindow(self.screen.as_ptr()),
+                XDefaultRootWindow(self.screen.as_ptr()),
                 pos_x,
                 pos_y,
                 &mut win_x,
@@ -304,8 +321,8 @@ impl Mouse {
     /// change focused window. Used when clicking a window
     fn set_focus_to_window(&self, window: Window) {
         unsafe {
-            XSetInputFocus(self.screen, window, RevertToParent, CurrentTime);
-            XFlush(self.screen);
+            XSetInputFocus(self.screen.as_ptr(), window, RevertToParent, CurrentTime);
+            XFlush(self.screen.as_ptr());
             thread::sleep(Duration::from_millis(50));
         }
     }
@@ -17,9 +17,14 @@ impl Mouse {
     pub fn new() -> Self {
         Self {}
     }
-    /// moves mouse to x, y pixel coordinate on screen
-
-    pub fn move_mouse_to_pos(x: i32, y: i32, moving_time: f32) -> Result<(), AutoGuiError> {
+    /// moves mouse to x, y pixel coordinate on screen. step_check is called before every
+    /// movement step and aborts the movement when it returns error
+    pub fn move_mouse_to_pos(
+        x: i32,
+        y: i32,
+        moving_time: f32,
+        step_check: &dyn Fn() -> Result<(), AutoGuiError>,
+    ) -> Result<(), AutoGuiError> {
         if moving_time <= 0.0 {
             Mouse::move_mouse(x, y)
         } else {
@@ -28,6 +33,7 @@ impl Mouse {
             let distance_y = y - start_location.1;
             let start = Instant::now();
             loop {
+                step_check()?;
                 let duration = start.elapsed().as_secs_f32();
 
                 let time_passed_percentage = duration / moving_time;
@@ -47,7 +53,12 @@ impl Mouse {
         }
     }
 
-    pub fn drag_mouse(x: i32, y: i32, moving_time: f32) -> Result<(), AutoGuiError> {
+    pub fn drag_mouse(
+        x: i32,
+        y: i32,
+        moving_time: f32,
+        step_check: &dyn Fn() -> Result<(), AutoGuiError>,
+    ) -> Result<(), AutoGuiError> {
         let (cg_button, down, up) = (
             CGMouseButton::Left,
             CGEventType::LeftMouseDown,
@@ -82,7 +93,13 @@ impl Mouse {
         let dx = (x - mouse_pos.0) as f64 / steps as f64;
         let dy = (y - mouse_pos.1) as f64 / steps as f64;
 
+        // button is released even if movement was aborted
+        let mut moved = Ok(());
         for i in 1..=steps as i32 {
+            if let Err(err) = step_check() {
+                moved = Err(err);
+                break;
+            }
             let new_x = mouse_pos.0 as f64 + dx * i as f64;
             let new_y = mouse_pos.1 as f64 + dy * i as f64;
 
@@ -120,7 +137,7 @@ impl Mouse {
 
         sleep(Duration::from_millis(20));
 
-        Ok(())
+        moved
     }
 
     // separate private function called by move to pos
@@ -16,12 +16,14 @@ pub use macos::Mouse;
 #[cfg(target_os = "linux")]
 pub use linux::Mouse;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum MouseClick {
     LEFT,
     RIGHT,
     MIDDLE,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum MouseScroll {
     UP,
     DOWN,
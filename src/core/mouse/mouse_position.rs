@@ -1,8 +1,8 @@
 #[cfg(target_os = "linux")]
 use super::Mouse;
-use crate::errors::AutoGuiError;
 #[cfg(target_os = "linux")]
-use std::ptr;
+use crate::core::x11_display::X11Display;
+use crate::errors::AutoGuiError;
 #[cfg(target_os = "linux")]
 use x11::xlib::*;
 
@@ -24,41 +24,16 @@ fn main() {
 }
     thats all
 */
-#[cfg(target_os = "linux")]
-struct DisplayWrapper {
-    display: *mut x11::xlib::Display,
-}
-//created so display gets dropped when code finishes
-#[cfg(target_os = "linux")]
-impl DisplayWrapper {
-    fn new() -> Self {
-        unsafe {
-            let display = XOpenDisplay(ptr::null());
-            if display.is_null() {
-                panic!("Unable to open X display");
-            }
-            DisplayWrapper { display }
-        }
-    }
-}
-#[cfg(target_os = "linux")]
-impl Drop for DisplayWrapper {
-    fn drop(&mut self) {
-        unsafe {
-            XCloseDisplay(self.display);
-        }
-    }
-}
-
 pub fn print_mouse_position() -> Result<(), AutoGuiError> {
     #[cfg(target_os = "linux")]
     {
-        let display_wrapper = DisplayWrapper::new();
+        // display connection gets closed when mouse is dropped
+        let display = X11Display::open()?;
 
         unsafe {
-            let screen = XDefaultScreen(display_wrapper.display);
-            let root = XRootWindow(display_wrapper.display, screen);
-            let mouse = Mouse::new(display_wrapper.display, root);
+            let screen = XDefaultScreen(display.as_ptr());
+            let root = XRootWindow(display.as_ptr(), screen);
+            let mouse = Mouse::new(display, root);
             loop {
                 let (x, y) = mouse.get_mouse_position()?;
                 println!("{x}, {y}");
@@ -1,4 +1,5 @@
 use crate::core::mouse::{MouseClick, MouseScroll};
+use crate::errors::AutoGuiError;
 use std::mem::{size_of, zeroed};
 use std::{thread, time, time::Instant};
 use winapi::shared::windef::POINT;
@@ -15,13 +16,19 @@ impl Mouse {
         Mouse {}
     }
 
-    /// moves mouse to x, y pixel coordinate on screen
-    pub fn move_mouse_to_pos(x: i32, y: i32, moving_time: f32) {
+    /// moves mouse to x, y pixel coordinate on screen. step_check is called before every
+    /// movement step and aborts the movement when it returns error
+    pub fn move_mouse_to_pos(
+        x: i32,
+        y: i32,
+        moving_time: f32,
+        step_check: &dyn Fn() -> Result<(), AutoGuiError>,
+    ) -> Result<(), AutoGuiError> {
         // if no moving time, then instant move is executed
         unsafe {
             if moving_time <= 0.0 {
                 SetCursorPos(x, y);
-                return;
+                return Ok(());
             }
         };
         // if moving time is included, loop is executed that moves step by step
@@ -31,6 +38,7 @@ impl Mouse {
         let distance_y = y - start_location.1;
 
         loop {
+            step_check()?;
             let duration = start.elapsed().as_secs_f32();
 
             let time_passed_percentage = duration / moving_time;
@@ -51,9 +59,15 @@ impl Mouse {
                 }
             }
         }
+        Ok(())
     }
 
-    pub fn drag_mouse(x: i32, y: i32, moving_time: f32) {
+    pub fn drag_mouse(
+        x: i32,
+        y: i32,
+        moving_time: f32,
+        step_check: &dyn Fn() -> Result<(), AutoGuiError>,
+    ) -> Result<(), AutoGuiError> {
         let (down, up) = (MOUSEEVENTF_LEFTDOWN, MOUSEEVENTF_LEFTUP);
         unsafe {
             // set up the first input event (mouse down)
@@ -63,7 +77,8 @@ impl Mouse {
             SendInput(1, &mut input_down, size_of::<INPUT>() as i32);
             // wait a bit after click down, before moving
             thread::sleep(time::Duration::from_millis(80));
-            Mouse::move_mouse_to_pos(x, y, moving_time);
+            // button is released even if movement was aborted
+            let moved = Mouse::move_mouse_to_pos(x, y, moving_time, step_check);
             thread::sleep(time::Duration::from_millis(50));
             // set up the second input event (mouse up)
             let mut input_up: INPUT = zeroed();
@@ -71,6 +86,7 @@ impl Mouse {
             input_up.u.mi_mut().dwFlags = up;
             // send the input events
             SendInput(2, &mut input_up, size_of::<INPUT>() as i32);
+            moved
         }
     }
 
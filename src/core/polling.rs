@@ -0,0 +1,149 @@
+use crate::AutoGuiError;
+use std::time::{Duration, Instant};
+
+/// sleep between checks of functions that wait for a condition
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Defines how often loop functions check for the condition they wait on.
+/// Interval between checks is multiplied by backoff_factor after every unsuccessful check,
+/// up to max_interval. max_attempts of 0 means unlimited attempts, limited only by timeout
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PollingPolicy {
+    pub interval: Duration,
+    pub backoff_factor: f32,
+    pub max_interval: Duration,
+    pub max_attempts: u32,
+}
+
+impl PollingPolicy {
+    /// checks are done every interval, without backoff
+    pub fn fixed(interval: Duration) -> Self {
+        Self {
+            interval,
+            backoff_factor: 1.0,
+            max_interval: interval,
+            max_attempts: 0,
+        }
+    }
+
+    /// starts with initial interval, multiplying it by factor after each check until max_interval is reached
+    pub fn backoff(initial: Duration, factor: f32, max_interval: Duration) -> Self {
+        Self {
+            interval: initial,
+            backoff_factor: factor.max(1.0),
+            max_interval: max_interval.max(initial),
+            max_attempts: 0,
+        }
+    }
+
+    /// limits number of checks. 0 means unlimited
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    fn next_interval(&self, current: Duration) -> Duration {
+        // huge or infinite factor overflows Duration, which is capped to max_interval as well
+        Duration::try_from_secs_f64(current.as_secs_f64() * self.backoff_factor.max(1.0) as f64)
+            .map_or(self.max_interval, |next| next.min(self.max_interval))
+    }
+}
+
+impl Default for PollingPolicy {
+    fn default() -> Self {
+        Self::fixed(DEFAULT_POLL_INTERVAL)
+    }
+}
+
+/// Bookkeeping of a single polling loop: attempts made, current interval and time left until timeout.
+/// Shared by sync and async loops, which only differ in how they sleep
+pub struct PollingState {
+    policy: PollingPolicy,
+    timeout: Duration,
+    timeout_start: Instant,
+    interval: Duration,
+    attempts: u32,
+}
+
+impl PollingState {
+    pub fn new(policy: &PollingPolicy, timeout: Duration, suppress_warnings: bool) -> Self {
+        if timeout.is_zero() & (policy.max_attempts == 0) & (!suppress_warnings) {
+            eprintln!(
+                "Warning: setting a timeout to 0 without max attempts initiates an infinite loop"
+            )
+        }
+        Self {
+            policy: *policy,
+            timeout,
+            timeout_start: Instant::now(),
+            interval: policy.interval,
+            attempts: 0,
+        }
+    }
+
+    /// Registers unsuccessful attempt and returns how long to sleep before the next one,
+    /// or None when timeout passed or attempts ran out
+    pub fn next_sleep(&mut self) -> Option<Duration> {
+        self.attempts += 1;
+        if (self.policy.max_attempts > 0) & (self.attempts >= self.policy.max_attempts) {
+            return None;
+        }
+        let mut sleep_time = self.interval;
+        if !self.timeout.is_zero() {
+            let elapsed = self.timeout_start.elapsed();
+            if elapsed >= self.timeout {
+                return None;
+            }
+            // do not oversleep the timeout
+            sleep_time = sleep_time.min(self.timeout - elapsed);
+        }
+        self.interval = self.policy.next_interval(self.interval);
+        Some(sleep_time)
+    }
+}
+
+/// Runs attempt until it returns Some, sleeping on current thread between attempts as defined by policy.
+/// Returns Ok(None) when timeout passes or attempts run out, and stops on the first error
+pub fn poll_blocking<T>(
+    policy: &PollingPolicy,
+    timeout: Duration,
+    suppress_warnings: bool,
+    mut attempt: impl FnMut() -> Result<Option<T>, AutoGuiError>,
+) -> Result<Option<T>, AutoGuiError> {
+    let mut state = PollingState::new(policy, timeout, suppress_warnings);
+    loop {
+        if let Some(result) = attempt()? {
+            return Ok(Some(result));
+        }
+        match state.next_sleep() {
+            Some(sleep_time) => std::thread::sleep(sleep_time),
+            None => return Ok(None),
+        }
+    }
+}
+
+#[cfg(all(feature = "async", not(feature = "lite")))]
+/// Async counterpart of poll_blocking. Awaits attempt until it returns Some,
+/// sleeping on tokio timer between attempts, so executor thread is not blocked.
+/// Dropping the returned future stops polling
+pub async fn poll_async<T, F, Fut>(
+    policy: &PollingPolicy,
+    timeout: Duration,
+    suppress_warnings: bool,
+    mut attempt: F,
+) -> Result<Option<T>, AutoGuiError>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<Option<T>, AutoGuiError>>,
+{
+    let mut state = PollingState::new(policy, timeout, suppress_warnings);
+    loop {
+        if let Some(result) = attempt().await? {
+            return Ok(Some(result));
+        }
+        match state.next_sleep() {
+            Some(sleep_time) => tokio::time::sleep(sleep_time).await,
+            None => return Ok(None),
+        }
+    }
+}
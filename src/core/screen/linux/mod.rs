@@ -1,6 +1,7 @@
 #[cfg(not(feature = "lite"))]
 extern crate image;
 extern crate x11;
+use crate::core::x11_display::X11Display;
 #[cfg(not(feature = "lite"))]
 use crate::errors::ImageProcessingError;
 use crate::{errors::AutoGuiError, imgtools};
@@ -9,20 +10,89 @@ use core::error;
 use image::{GrayImage, ImageBuffer, Luma, Rgba};
 #[cfg(not(feature = "lite"))]
 use rayon::prelude::*;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
 use std::ptr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Mutex;
 use x11::xlib::{
-    XCloseDisplay, XDefaultScreen, XDestroyImage, XDisplayHeight, XDisplayWidth, XGetImage,
-    XOpenDisplay, XRootWindow, ZPixmap, _XDisplay,
+    _XDisplay, IsViewable, Pixmap, Window, XDefaultScreen, XDestroyImage, XDisplayHeight,
+    XDisplayWidth, XErrorEvent, XFetchName, XFree, XFreePixmap, XGetErrorText, XGetImage,
+    XGetWindowAttributes, XQueryTree, XRootWindow, XSetErrorHandler, XSync, XTranslateCoordinates,
+    XWindowAttributes, ZPixmap,
 };
 
 #[cfg(not(feature = "lite"))]
 const ALLPLANES: u64 = 0xFFFFFFFFFFFFFFFF;
 
+// Xcomposite bindings, not included in x11 crate. Linked in build.rs
+#[cfg(not(feature = "lite"))]
+const COMPOSITE_REDIRECT_AUTOMATIC: c_int = 0;
+#[cfg(not(feature = "lite"))]
+extern "C" {
+    fn XCompositeQueryExtension(
+        display: *mut _XDisplay,
+        event_base: *mut c_int,
+        error_base: *mut c_int,
+    ) -> c_int;
+    fn XCompositeRedirectWindow(display: *mut _XDisplay, window: Window, update: c_int);
+    fn XCompositeUnredirectWindow(display: *mut _XDisplay, window: Window, update: c_int);
+    fn XCompositeNameWindowPixmap(display: *mut _XDisplay, window: Window) -> Pixmap;
+}
+
+// error handler is global for the process, so only one trap is active at a time
+static X_ERROR_TRAP: Mutex<()> = Mutex::new(());
+// code of the first error raised while trap is active, 0 when there was none
+static X_ERROR_CODE: AtomicU8 = AtomicU8::new(0);
+
+unsafe extern "C" fn record_x_error(_display: *mut _XDisplay, event: *mut XErrorEvent) -> c_int {
+    let _ =
+        X_ERROR_CODE.compare_exchange(0, (*event).error_code, Ordering::SeqCst, Ordering::SeqCst);
+    0
+}
+
+/// Runs X requests with error handler which records errors, instead of the default one which exits
+/// the process, for instance on BadWindow when window was closed. Requests are synced before the previous
+/// handler is restored, so all their errors are caught. Returns result of requests and description
+/// of the first X error they raised
+fn trap_x_errors<T>(display: *mut _XDisplay, requests: impl FnOnce() -> T) -> (T, Option<String>) {
+    let _trap = X_ERROR_TRAP
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    unsafe {
+        // errors of earlier requests are still reported to previous handler
+        XSync(display, 0);
+        X_ERROR_CODE.store(0, Ordering::SeqCst);
+        let previous_handler = XSetErrorHandler(Some(record_x_error));
+        let result = requests();
+        XSync(display, 0);
+        XSetErrorHandler(previous_handler);
+        let error = match X_ERROR_CODE.swap(0, Ordering::SeqCst) {
+            0 => None,
+            code => {
+                let mut text = [0 as c_char; 256];
+                XGetErrorText(
+                    display,
+                    code as c_int,
+                    text.as_mut_ptr(),
+                    text.len() as c_int,
+                );
+                Some(format!(
+                    "X error {}: {}",
+                    code,
+                    CStr::from_ptr(text.as_ptr()).to_string_lossy()
+                ))
+            }
+        };
+        (result, error)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Screen {
     pub screen_width: i32,
     pub screen_height: i32,
-    pub display: *mut _XDisplay,
+    pub display: X11Display,
     pub root_window: u64,
     #[cfg(not(feature = "lite"))]
     pub screen_data: ScreenImgData,
@@ -36,35 +106,31 @@ pub struct ScreenImgData {
 }
 
 impl Screen {
-    pub fn new() -> Self {
+    pub fn new() -> Result<Self, AutoGuiError> {
+        // open the display. This display handle will be shared with
+        // mouse and keyboard structs aswell
+        let display = X11Display::open()?;
         unsafe {
-            // open the display (usually ":0"). This display pointer will be passed
-            // to mouse and keyboard structs aswell
-            let display: *mut _XDisplay = XOpenDisplay(ptr::null());
-            if display.is_null() {
-                panic!("Error grabbing display. Unable to open X display. Possible x11 issue, check if it is activated and that you're not running wayland");
-            }
-
             // get root window
-            let screen = XDefaultScreen(display);
-            let root = XRootWindow(display, screen);
+            let screen = XDefaultScreen(display.as_ptr());
+            let root = XRootWindow(display.as_ptr(), screen);
 
-            let screen_width = XDisplayWidth(display, screen);
-            let screen_height = XDisplayHeight(display, screen);
+            let screen_width = XDisplayWidth(display.as_ptr(), screen);
+            let screen_height = XDisplayHeight(display.as_ptr(), screen);
             #[cfg(not(feature = "lite"))]
             let img_data = ScreenImgData {
                 pixel_data: vec![0u8; (screen_width * screen_height * 4) as usize],
                 screen_region_width: 0,
                 screen_region_height: 0,
             };
-            Screen {
+            Ok(Screen {
                 screen_width: screen_width,
                 screen_height: screen_height,
                 display: display,
                 root_window: root,
                 #[cfg(not(feature = "lite"))]
                 screen_data: img_data,
-            }
+            })
         }
     }
 
@@ -85,12 +151,6 @@ impl Screen {
         dimensions
     }
 
-    pub fn destroy(&self) {
-        unsafe {
-            XCloseDisplay(self.display);
-        }
-    }
-
     #[allow(dead_code)]
     /// executes convert_bitmap_to_rgba, meaning it converts Vector of values to RGBA and crops the image
     /// as inputted region area. Not used anywhere at the moment
@@ -126,18 +186,307 @@ impl Screen {
         Ok(cropped_image)
     }
     #[cfg(not(feature = "lite"))]
+    /// captures screen once and returns both RGBA and grayscale Imagebuffers cropped as inputted region area.
+    /// Used when same capture is searched and inspected multiple times
+    #[allow(clippy::type_complexity)]
+    pub fn grab_screen_images(
+        &mut self,
+        region: &(u32, u32, u32, u32),
+    ) -> Result<
+        (
+            ImageBuffer<Rgba<u8>, Vec<u8>>,
+            ImageBuffer<Luma<u8>, Vec<u8>>,
+        ),
+        AutoGuiError,
+    > {
+        let (x, y, width, height) = *region;
+        self.screen_data.screen_region_width = width;
+        self.screen_data.screen_region_height = height;
+        self.capture_screen()?;
+        let image = self.convert_bitmap_to_rgba()?;
+        let gray_image = self.convert_bitmap_to_grayscale()?;
+        Ok((
+            imgtools::cut_screen_region(x, y, width, height, &image),
+            imgtools::cut_screen_region(x, y, width, height, &gray_image),
+        ))
+    }
+    #[cfg(not(feature = "lite"))]
     /// captures and saves screenshot of monitors
     pub fn grab_screenshot(&mut self, image_path: &str) -> Result<(), AutoGuiError> {
         self.capture_screen()?;
         let image = self.convert_bitmap_to_rgba()?;
         Ok(image.save(image_path)?)
     }
+    #[cfg(not(feature = "lite"))]
+    /// captures contents of a single window, even if it is covered by other windows.
+    /// Uses Composite extension to redirect window into offscreen pixmap which is then read.
+    /// Window has to be mapped, meaning minimized windows cannot be captured
+    pub fn grab_window_image(
+        &mut self,
+        window: u64,
+    ) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>, AutoGuiError> {
+        let (image, error) = trap_x_errors(self.display.as_ptr(), || {
+            unsafe {
+                let mut event_base = 0;
+                let mut error_base = 0;
+                if XCompositeQueryExtension(self.display.as_ptr(), &mut event_base, &mut error_base)
+                    == 0
+                {
+                    return Err(AutoGuiError::OSFailure(
+                        "Xcomposite extension is not available".to_string(),
+                    ));
+                }
+                let (width, height) = self.window_dimension_untrapped(window)?;
+
+                // redirect window to offscreen storage. If compositor already redirected it
+                // this only increases the reference count
+                XCompositeRedirectWindow(
+                    self.display.as_ptr(),
+                    window,
+                    COMPOSITE_REDIRECT_AUTOMATIC,
+                );
+                XSync(self.display.as_ptr(), 0);
+                let pixmap = XCompositeNameWindowPixmap(self.display.as_ptr(), window);
+                let ximage = XGetImage(
+                    self.display.as_ptr(),
+                    pixmap,
+                    0,
+                    0,
+                    width,
+                    height,
+                    ALLPLANES,
+                    ZPixmap,
+                );
+                if ximage.is_null() {
+                    XFreePixmap(self.display.as_ptr(), pixmap);
+                    XCompositeUnredirectWindow(
+                        self.display.as_ptr(),
+                        window,
+                        COMPOSITE_REDIRECT_AUTOMATIC,
+                    );
+                    return Err(AutoGuiError::OSFailure(
+                        "Error grabbing window image. Unable to get X image from window pixmap"
+                            .to_string(),
+                    ));
+                }
+
+                // rows can be padded, so bytes_per_line is used instead of width
+                let bytes_per_line = (*ximage).bytes_per_line as usize;
+                let bytes_per_pixel = ((*ximage).bits_per_pixel / 8) as usize;
+                let data = (*ximage).data as *mut u8;
+                let slice = std::slice::from_raw_parts(data, bytes_per_line * height as usize);
+                let mut pixel_data: Vec<u8> = Vec::with_capacity((width * height * 4) as usize);
+                for y in 0..height as usize {
+                    for x in 0..width as usize {
+                        let index = y * bytes_per_line + x * bytes_per_pixel;
+                        pixel_data.push(slice[index + 2]); // R
+                        pixel_data.push(slice[index + 1]); // G
+                        pixel_data.push(slice[index]); // B
+                        pixel_data.push(255); // A
+                    }
+                }
+                XDestroyImage(ximage);
+                XFreePixmap(self.display.as_ptr(), pixmap);
+                XCompositeUnredirectWindow(
+                    self.display.as_ptr(),
+                    window,
+                    COMPOSITE_REDIRECT_AUTOMATIC,
+                );
+
+                ImageBuffer::from_raw(width, height, pixel_data).ok_or(
+                    ImageProcessingError::new("Failed conversion of window image to RGBa").into(),
+                )
+            }
+        });
+        match error {
+            Some(error) => Err(AutoGuiError::OSFailure(format!(
+                "Error grabbing window {}. {}",
+                window, error
+            ))),
+            None => image,
+        }
+    }
+
+    #[cfg(not(feature = "lite"))]
+    /// captures window contents and returns grayscale Imagebuffer cropped for the selected region.
+    /// Region is relative to the window top left corner and is cut if it exceeds the window size
+    pub fn grab_window_image_grayscale(
+        &mut self,
+        window: u64,
+        region: &(u32, u32, u32, u32),
+    ) -> Result<ImageBuffer<Luma<u8>, Vec<u8>>, AutoGuiError> {
+        Ok(self.grab_window_images(window, region)?.1)
+    }
+
+    #[cfg(not(feature = "lite"))]
+    /// captures window contents once and returns both RGBA and grayscale Imagebuffers cropped for the
+    /// selected region. Region is relative to the window top left corner and is cut if it exceeds the window size
+    #[allow(clippy::type_complexity)]
+    pub fn grab_window_images(
+        &mut self,
+        window: u64,
+        region: &(u32, u32, u32, u32),
+    ) -> Result<
+        (
+            ImageBuffer<Rgba<u8>, Vec<u8>>,
+            ImageBuffer<Luma<u8>, Vec<u8>>,
+        ),
+        AutoGuiError,
+    > {
+        let (x, y, width, height) = *region;
+        let image = self.grab_window_image(window)?;
+        if (x >= image.width()) | (y >= image.height()) {
+            return Err(AutoGuiError::OutOfBoundsError(
+                "Region starts outside of window".to_string(),
+            ));
+        }
+        // region is cut down to window size, since regions are prepared for whole screen by default
+        let width = width.min(image.width() - x);
+        let height = height.min(image.height() - y);
+        self.screen_data.screen_region_width = width;
+        self.screen_data.screen_region_height = height;
+        let image = imgtools::cut_screen_region(x, y, width, height, &image);
+        let gray_image: ImageBuffer<Luma<u8>, Vec<u8>> =
+            image::DynamicImage::ImageRgba8(image.clone()).to_luma8();
+        Ok((image, gray_image))
+    }
+
+    /// returns width and height of the window. Errors if window does not exist or is not viewable
+    pub fn window_dimension(&self, window: u64) -> Result<(u32, u32), AutoGuiError> {
+        let (dimension, error) = trap_x_errors(self.display.as_ptr(), || {
+            self.window_dimension_untrapped(window)
+        });
+        match error {
+            Some(error) => Err(AutoGuiError::OSFailure(format!(
+                "Unable to get attributes for window {}. {}",
+                window, error
+            ))),
+            None => dimension,
+        }
+    }
+
+    #[cfg(not(feature = "lite"))]
+    /// Translates position relative to window top left corner into screen position.
+    /// Errors if window does not exist or position is outside of the screen
+    pub fn window_to_screen_position(
+        &self,
+        window: u64,
+        x: u32,
+        y: u32,
+    ) -> Result<(u32, u32), AutoGuiError> {
+        let mut screen_x = 0;
+        let mut screen_y = 0;
+        let mut child = 0;
+        let (translated, error) = trap_x_errors(self.display.as_ptr(), || unsafe {
+            XTranslateCoordinates(
+                self.display.as_ptr(),
+                window,
+                self.root_window,
+                x as c_int,
+                y as c_int,
+                &mut screen_x,
+                &mut screen_y,
+                &mut child,
+            )
+        });
+        if let Some(error) = error {
+            return Err(AutoGuiError::OSFailure(format!(
+                "Unable to translate position in window {}. {}",
+                window, error
+            )));
+        }
+        if (translated == 0)
+            | (screen_x < 0)
+            | (screen_y < 0)
+            | (screen_x >= self.screen_width)
+            | (screen_y >= self.screen_height)
+        {
+            return Err(AutoGuiError::OutOfBoundsError(format!(
+                "Position ({}, {}) of window {} is outside of the screen",
+                x, y, window
+            )));
+        }
+        Ok((screen_x as u32, screen_y as u32))
+    }
+
+    /// window_dimension without X error trap, for calls made inside of already active trap
+    fn window_dimension_untrapped(&self, window: u64) -> Result<(u32, u32), AutoGuiError> {
+        unsafe {
+            let mut attributes: XWindowAttributes = std::mem::zeroed();
+            if XGetWindowAttributes(self.display.as_ptr(), window, &mut attributes) == 0 {
+                return Err(AutoGuiError::OSFailure(format!(
+                    "Unable to get attributes for window {}",
+                    window
+                )));
+            }
+            if attributes.map_state != IsViewable {
+                return Err(AutoGuiError::OSFailure(format!(
+                    "Window {} is not viewable. Minimized or unmapped windows cannot be captured",
+                    window
+                )));
+            }
+            Ok((attributes.width as u32, attributes.height as u32))
+        }
+    }
+
+    /// searches window tree for first window whose title contains provided name
+    pub fn find_window_by_name(&self, name: &str) -> Option<u64> {
+        // windows closed during the search raise BadWindow, their subtrees are then skipped
+        trap_x_errors(self.display.as_ptr(), || {
+            self.search_window_tree(self.root_window, name)
+        })
+        .0
+    }
+
+    fn search_window_tree(&self, window: u64, name: &str) -> Option<u64> {
+        unsafe {
+            let mut window_name: *mut std::os::raw::c_char = ptr::null_mut();
+            if XFetchName(self.display.as_ptr(), window, &mut window_name) != 0
+                && !window_name.is_null()
+            {
+                let found = CStr::from_ptr(window_name).to_string_lossy().contains(name);
+                XFree(window_name as *mut _);
+                if found {
+                    return Some(window);
+                }
+            }
+
+            let mut root_return = 0;
+            let mut parent_return = 0;
+            let mut children: *mut u64 = ptr::null_mut();
+            let mut children_count = 0;
+            if XQueryTree(
+                self.display.as_ptr(),
+                window,
+                &mut root_return,
+                &mut parent_return,
+                &mut children,
+                &mut children_count,
+            ) == 0
+            {
+                return None;
+            }
+            let mut result = None;
+            if !children.is_null() {
+                let children_slice = std::slice::from_raw_parts(children, children_count as usize);
+                for child in children_slice {
+                    result = self.search_window_tree(*child, name);
+                    if result.is_some() {
+                        break;
+                    }
+                }
+                XFree(children as *mut _);
+            }
+            result
+        }
+    }
+
     #[cfg(not(feature = "lite"))]
     /// first order capture screen function. it captures screen image and stores it as vector in self.pixel_data
     fn capture_screen(&mut self) -> Result<(), AutoGuiError> {
         unsafe {
             let ximage = XGetImage(
-                self.display,
+                self.display.as_ptr(),
                 self.root_window,
                 0,
                 0,
@@ -112,6 +112,38 @@ impl Screen {
         Ok(cropped_image)
     }
     #[cfg(not(feature = "lite"))]
+    /// captures screen once and returns both RGBA and grayscale Imagebuffers cropped as inputted region area.
+    /// Grayscale image is in logical resolution, while RGBA image keeps physical resolution,
+    /// so on retina displays its region is multiplied by scaling factor
+    #[allow(clippy::type_complexity)]
+    pub fn grab_screen_images(
+        &mut self,
+        region: &(u32, u32, u32, u32),
+    ) -> Result<
+        (
+            ImageBuffer<Rgba<u8>, Vec<u8>>,
+            ImageBuffer<Luma<u8>, Vec<u8>>,
+        ),
+        AutoGuiError,
+    > {
+        let (x, y, width, height) = *region;
+        self.screen_data.screen_region_width = width;
+        self.screen_data.screen_region_height = height;
+        self.capture_screen()?;
+        let image = self.convert_bitmap_to_rgba()?;
+        let gray_image = self.convert_bitmap_to_grayscale()?;
+        let scale_x = self.screen_data.scaling_factor_x;
+        let scale_y = self.screen_data.scaling_factor_y;
+        let rgba_x = ((x as f32 * scale_x) as u32).min(image.width());
+        let rgba_y = ((y as f32 * scale_y) as u32).min(image.height());
+        let rgba_width = ((width as f32 * scale_x) as u32).min(image.width() - rgba_x);
+        let rgba_height = ((height as f32 * scale_y) as u32).min(image.height() - rgba_y);
+        Ok((
+            imgtools::cut_screen_region(rgba_x, rgba_y, rgba_width, rgba_height, &image),
+            imgtools::cut_screen_region(x, y, width, height, &gray_image),
+        ))
+    }
+    #[cfg(not(feature = "lite"))]
     /// captures and saves screenshot of monitors
     pub fn grab_screenshot(&mut self, image_path: &str) -> Result<(), AutoGuiError> {
         self.capture_screen()?;
@@ -33,6 +33,12 @@ pub struct ScreenImgData {
     h_memory_dc: *mut winapi::shared::windef::HDC__,
     h_bitmap: *mut winapi::shared::windef::HBITMAP__,
 }
+// GDI handles belong to the process, not to the thread that created them. Capturing requires
+// &mut self, so handles are never used from two threads at the same time
+#[cfg(not(feature = "lite"))]
+unsafe impl Send for ScreenImgData {}
+#[cfg(not(feature = "lite"))]
+unsafe impl Sync for ScreenImgData {}
 
 impl Screen {
     ///Creates struct that holds information about screen
@@ -114,6 +120,31 @@ impl Screen {
         Ok(cropped_image)
     }
     #[cfg(not(feature = "lite"))]
+    /// captures screen once and returns both RGBA and grayscale Imagebuffers cropped as inputted region area.
+    /// Used when same capture is searched and inspected multiple times
+    #[allow(clippy::type_complexity)]
+    pub fn grab_screen_images(
+        &mut self,
+        region: &(u32, u32, u32, u32),
+    ) -> Result<
+        (
+            ImageBuffer<Rgba<u8>, Vec<u8>>,
+            ImageBuffer<Luma<u8>, Vec<u8>>,
+        ),
+        AutoGuiError,
+    > {
+        let (x, y, width, height) = *region;
+        self.screen_data.screen_region_width = width;
+        self.screen_data.screen_region_height = height;
+        self.capture_screen();
+        let image = self.convert_bitmap_to_rgba()?;
+        let gray_image = self.convert_bitmap_to_grayscale()?;
+        Ok((
+            imgtools::cut_screen_region(x, y, width, height, &image),
+            imgtools::cut_screen_region(x, y, width, height, &gray_image),
+        ))
+    }
+    #[cfg(not(feature = "lite"))]
     /// grabs screen image and saves file at provided
     pub fn grab_screenshot(&mut self, image_path: &str) -> Result<(), AutoGuiError> {
         self.capture_screen();
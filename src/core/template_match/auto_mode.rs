@@ -0,0 +1,186 @@
+/*
+Selection of match mode for MatchMode::Auto. Cost of segmented search grows with number of positions
+in region times number of fast segments of template, while cost of FFT search grows with padded region
+size and its logarithm, no matter how complex the template is. Both costs are estimated from template
+segments and region size. When one mode is estimated to be several times cheaper, it is picked right away,
+otherwise both modes are run once on generated image of region size and the faster one is picked.
+*/
+
+use crate::core::template_match::segmented_simd::LANES;
+use crate::core::template_match::{fft_ncc, segmented_ncc};
+use crate::data::{PreparedData, SegmentedData};
+use crate::MatchMode;
+use image::{ImageBuffer, Luma};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::convert::Infallible;
+use std::time::{Duration, Instant};
+
+/// region size assumed when it is not known at preparation, which is full HD screen
+const DEFAULT_REGION_SIZE: (u32, u32) = (1920, 1080);
+/// estimated cost of one mode needs to be this many times lower to be picked without benchmark
+const DECISIVE_RATIO: f32 = 4.0;
+/// precision used in benchmark searches
+const BENCHMARK_PRECISION: f32 = 0.9;
+/// timed runs of every benchmarked search, after one warm up run
+const BENCHMARK_RUNS: u32 = 3;
+
+/// Match mode picked by MatchMode::Auto, with values the choice was based on.
+/// Segment counts are 0 when template could not be segmented. Estimated ratio is estimated cost of
+/// segmented search divided by estimated cost of FFT search. Timings hold shortest duration of several
+/// searches of every benchmarked mode, and are empty when estimate alone decided
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutoModeChoice {
+    pub match_mode: MatchMode,
+    pub segments_fast: usize,
+    pub segments_slow: usize,
+    pub region_size: (u32, u32),
+    pub estimated_ratio: f32,
+    pub timings: Vec<(MatchMode, Duration)>,
+}
+
+impl AutoModeChoice {
+    /// adds timing of another benchmarked mode, which is picked if it is faster than all others
+    pub(crate) fn add_timing(&mut self, match_mode: MatchMode, duration: Duration) {
+        if self.timings.iter().all(|(_, timing)| duration < *timing) {
+            self.match_mode = match_mode.clone();
+        }
+        self.timings.push((match_mode, duration));
+    }
+}
+
+/// Picks Segmented or FFT match mode for template searched in region of provided size. Without region
+/// size, full HD region is assumed and choice is made by estimate only. Benchmark is forced when other
+/// modes are going to be compared with CPU ones as well
+pub fn select_match_mode(
+    template: &ImageBuffer<Luma<u8>, Vec<u8>>,
+    region_size: Option<(u32, u32)>,
+    user_threshold: Option<f32>,
+    force_benchmark: bool,
+) -> AutoModeChoice {
+    let (template_width, template_height) = template.dimensions();
+    let size = region_size.unwrap_or(DEFAULT_REGION_SIZE);
+    let size = (size.0.max(template_width), size.1.max(template_height));
+    let segmented = segmentable(template, user_threshold);
+    let Some(segmented) = segmented else {
+        // too complex templates are only searched with FFT on CPU
+        let mut choice = AutoModeChoice {
+            match_mode: MatchMode::FFT,
+            segments_fast: 0,
+            segments_slow: 0,
+            region_size: size,
+            estimated_ratio: f32::INFINITY,
+            timings: Vec::new(),
+        };
+        if region_size.is_some() & force_benchmark {
+            let image = benchmark_image(template, size.0, size.1);
+            choice.add_timing(MatchMode::FFT, time_fft(template, &image));
+        }
+        return choice;
+    };
+    let estimated_ratio =
+        estimated_segmented_cost(&segmented, size) / estimated_fft_cost(&segmented, size);
+    let mut choice = AutoModeChoice {
+        match_mode: if estimated_ratio <= 1.0 {
+            MatchMode::Segmented
+        } else {
+            MatchMode::FFT
+        },
+        segments_fast: segmented.template_segments_fast.len(),
+        segments_slow: segmented.template_segments_slow.len(),
+        region_size: size,
+        estimated_ratio,
+        timings: Vec::new(),
+    };
+    let decisive = !(1.0 / DECISIVE_RATIO..=DECISIVE_RATIO).contains(&estimated_ratio);
+    if region_size.is_none() | (decisive & !force_benchmark) {
+        return choice;
+    }
+
+    let image = benchmark_image(template, size.0, size.1);
+    choice.timings.clear();
+    choice.add_timing(
+        MatchMode::Segmented,
+        time_search(|| {
+            segmented_ncc::fast_ncc_template_match(&image, BENCHMARK_PRECISION, &segmented, &false)
+        }),
+    );
+    choice.add_timing(MatchMode::FFT, time_fft(template, &image));
+    choice
+}
+
+fn time_fft(
+    template: &ImageBuffer<Luma<u8>, Vec<u8>>,
+    image: &ImageBuffer<Luma<u8>, Vec<u8>>,
+) -> Duration {
+    let fft_data = fft_ncc::prepare_template_picture(template, image.width(), image.height());
+    time_search(|| fft_ncc::fft_ncc(image, BENCHMARK_PRECISION, &fft_data))
+}
+
+/// segmented data of template, or None when template is too complex to be segmented
+fn segmentable(
+    template: &ImageBuffer<Luma<u8>, Vec<u8>>,
+    user_threshold: Option<f32>,
+) -> Option<SegmentedData> {
+    match segmented_ncc::prepare_template_picture(template, &false, user_threshold) {
+        PreparedData::Segmented(segmented)
+            if (segmented.template_segments_fast.len() > 1)
+                & (segmented.template_segments_slow.len() > 1) =>
+        {
+            Some(segmented)
+        }
+        _ => None,
+    }
+}
+
+fn positions(segmented: &SegmentedData, (width, height): (u32, u32)) -> f32 {
+    ((width - segmented.template_width + 1) * (height - segmented.template_height + 1)) as f32
+}
+
+/// every position sums fast segments from 4 integral image values, LANES positions at once,
+/// and computes mean and deviation of image under template
+fn estimated_segmented_cost(segmented: &SegmentedData, size: (u32, u32)) -> f32 {
+    let per_position = (segmented.template_segments_fast.len() * 4) as f32 / LANES as f32 + 8.0;
+    positions(segmented, size) * per_position
+}
+
+/// forward and inverse real fft of padded region, and normalization of every position
+fn estimated_fft_cost(segmented: &SegmentedData, size: (u32, u32)) -> f32 {
+    let length = (size.0 as f32 + 1.0) * (size.1 as f32 + 1.0);
+    2.5 * length * length.log2() + positions(segmented, size) * 8.0
+}
+
+/// Generated image of region size used for benchmark. Pixels are pseudo random, so correlation of
+/// most positions is low like on real screen, with template placed in the middle to be found once
+pub fn benchmark_image(
+    template: &ImageBuffer<Luma<u8>, Vec<u8>>,
+    width: u32,
+    height: u32,
+) -> ImageBuffer<Luma<u8>, Vec<u8>> {
+    // fixed seed so same template always gets the same benchmark
+    let mut rng = StdRng::seed_from_u64(0x9E37_79B9);
+    let mut image = ImageBuffer::from_fn(width, height, |_, _| Luma([rng.random::<u8>()]));
+    let (x, y) = (
+        (width - template.width()) / 2,
+        (height - template.height()) / 2,
+    );
+    image::imageops::replace(&mut image, template, x as i64, y as i64);
+    image
+}
+
+/// Shortest duration of several runs of search. First run is not timed, since it pays for
+/// cold caches and lazily initialized data like FFT plans
+pub fn time_search<R>(mut search: impl FnMut() -> R) -> Duration {
+    try_time_search(|| Ok::<R, Infallible>(search())).unwrap_or_else(|never| match never {})
+}
+
+/// same as time_search, for search that can fail. First error stops the benchmark
+pub fn try_time_search<R, E>(mut search: impl FnMut() -> Result<R, E>) -> Result<Duration, E> {
+    std::hint::black_box(search()?);
+    let mut shortest = Duration::MAX;
+    for _ in 0..BENCHMARK_RUNS {
+        let start = Instant::now();
+        std::hint::black_box(search()?);
+        shortest = shortest.min(start.elapsed());
+    }
+    Ok(shortest)
+}
@@ -0,0 +1,602 @@
+/*
+Feature point matching, in style of ORB. Corners are detected with FAST on image pyramid,
+described with binary descriptors of intensity comparisons rotated by corner orientation,
+and matched by Hamming distance. Homography between template and searched image is estimated
+with RANSAC, so template is found when it is scaled, rotated or partially covered.
+*/
+
+use crate::data::{FeaturesData, KeyPoint};
+use crate::ImageProcessingError;
+use image::{
+    imageops::{resize, FilterType},
+    ImageBuffer, Luma,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rayon::prelude::*;
+use std::sync::OnceLock;
+
+const PYRAMID_LEVELS: u32 = 5;
+const PYRAMID_SCALE: f32 = 1.25;
+const FAST_THRESHOLD: i32 = 20;
+// radius of patch used for orientation and descriptor
+const PATCH_RADIUS: i32 = 12;
+// descriptor samples are smoothed with 5x5 box, so 2 more pixels are needed around patch
+const BORDER: i32 = PATCH_RADIUS + 3;
+const MAX_TEMPLATE_KEYPOINTS: usize = 500;
+const MAX_IMAGE_KEYPOINTS: usize = 10000;
+const MAX_HAMMING_DISTANCE: u32 = 64;
+// best match needs to be clearly better than second best
+const RATIO_TEST: f32 = 0.8;
+const RANSAC_ITERATIONS: u32 = 2000;
+const REPROJECTION_THRESHOLD: f64 = 4.0;
+const MIN_INLIERS: usize = 8;
+
+// template point and matching point in searched image
+type PointPair = ((f64, f64), (f64, f64));
+
+// pixels on circle of radius 3 around FAST candidate, in order around circle
+const FAST_CIRCLE: [(i32, i32); 16] = [
+    (0, -3),
+    (1, -3),
+    (2, -2),
+    (3, -1),
+    (3, 0),
+    (3, 1),
+    (2, 2),
+    (1, 3),
+    (0, 3),
+    (-1, 3),
+    (-2, 2),
+    (-3, 1),
+    (-3, 0),
+    (-3, -1),
+    (-2, -2),
+    (-1, -3),
+];
+
+/// Template found with feature matching. Corners are template corners projected onto searched
+/// image, in order top left, top right, bottom right, bottom left of the template
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureMatch {
+    pub corners: [(f32, f32); 4],
+    /// middle of the matched quadrilateral
+    pub center: (u32, u32),
+    /// share of matched feature points consistent with found position, from 0 to 1
+    pub score: f32,
+    /// number of matched feature points consistent with found position
+    pub inliers: u32,
+}
+
+impl FeatureMatch {
+    /// same match moved by offset, used to turn region positions into screen positions
+    pub(crate) fn translated(&self, x: u32, y: u32) -> Self {
+        Self {
+            corners: self
+                .corners
+                .map(|(corner_x, corner_y)| (corner_x + x as f32, corner_y + y as f32)),
+            center: (self.center.0 + x, self.center.1 + y),
+            score: self.score,
+            inliers: self.inliers,
+        }
+    }
+}
+
+/// detects and describes template feature points
+pub fn prepare_template_picture(
+    template: &ImageBuffer<Luma<u8>, Vec<u8>>,
+) -> Result<FeaturesData, ImageProcessingError> {
+    let (keypoints, descriptors) = detect_and_describe(template, MAX_TEMPLATE_KEYPOINTS);
+    if keypoints.len() < MIN_INLIERS {
+        return Err(ImageProcessingError::new(
+            "Template has too few distinct corners for Features match mode. Use larger or more detailed template, or another match mode",
+        ));
+    }
+    Ok(FeaturesData {
+        keypoints,
+        descriptors,
+        template_width: template.width(),
+        template_height: template.height(),
+    })
+}
+
+/// Searches for template in image. Returns best found position, which still needs
+/// to be compared with precision, or None if no consistent position was found
+pub fn find_match(
+    image: &ImageBuffer<Luma<u8>, Vec<u8>>,
+    template_data: &FeaturesData,
+) -> Option<FeatureMatch> {
+    let (keypoints, descriptors) = detect_and_describe(image, MAX_IMAGE_KEYPOINTS);
+    if keypoints.len() < MIN_INLIERS {
+        return None;
+    }
+    // pairs of template and image point for every descriptor that passed ratio test
+    let matches: Vec<PointPair> = template_data
+        .descriptors
+        .par_iter()
+        .zip(template_data.keypoints.par_iter())
+        .filter_map(|(template_descriptor, template_keypoint)| {
+            let mut best = (u32::MAX, 0);
+            let mut second = u32::MAX;
+            for (i, descriptor) in descriptors.iter().enumerate() {
+                let distance = hamming(template_descriptor, descriptor);
+                if distance < best.0 {
+                    second = best.0;
+                    best = (distance, i);
+                } else if distance < second {
+                    second = distance;
+                }
+            }
+            if (best.0 > MAX_HAMMING_DISTANCE) | (best.0 as f32 >= RATIO_TEST * second as f32) {
+                return None;
+            }
+            let image_keypoint = keypoints[best.1];
+            Some((
+                (template_keypoint.x as f64, template_keypoint.y as f64),
+                (image_keypoint.x as f64, image_keypoint.y as f64),
+            ))
+        })
+        .collect();
+    if matches.len() < MIN_INLIERS {
+        return None;
+    }
+
+    let (homography, inliers) = ransac(&matches)?;
+    if inliers < MIN_INLIERS {
+        return None;
+    }
+    let (width, height) = (
+        template_data.template_width as f64,
+        template_data.template_height as f64,
+    );
+    let corners = [(0.0, 0.0), (width, 0.0), (width, height), (0.0, height)]
+        .map(|corner| project(&homography, corner));
+    let corners = [corners[0]?, corners[1]?, corners[2]?, corners[3]?];
+    if !is_plausible_quad(&corners, width * height) {
+        return None;
+    }
+    let (center_x, center_y) = project(&homography, (width / 2.0, height / 2.0))?;
+    Some(FeatureMatch {
+        corners: corners.map(|(x, y)| (x as f32, y as f32)),
+        center: (
+            center_x.max(0.0).round() as u32,
+            center_y.max(0.0).round() as u32,
+        ),
+        score: inliers as f32 / matches.len() as f32,
+        inliers: inliers as u32,
+    })
+}
+
+/// FAST corners with descriptors from all pyramid levels, positions scaled to full image size
+fn detect_and_describe(
+    image: &ImageBuffer<Luma<u8>, Vec<u8>>,
+    max_keypoints: usize,
+) -> (Vec<KeyPoint>, Vec<[u64; 4]>) {
+    let (width, height) = image.dimensions();
+    let mut levels: Vec<ImageBuffer<Luma<u8>, Vec<u8>>> = vec![image.clone()];
+    for level in 1..PYRAMID_LEVELS {
+        let scale = PYRAMID_SCALE.powi(level as i32);
+        let level_width = (width as f32 / scale).round() as u32;
+        let level_height = (height as f32 / scale).round() as u32;
+        if (level_width as i32 <= 2 * BORDER) | (level_height as i32 <= 2 * BORDER) {
+            break;
+        }
+        levels.push(resize(
+            image,
+            level_width,
+            level_height,
+            FilterType::Triangle,
+        ));
+    }
+    let total_area: u64 = levels
+        .iter()
+        .map(|level| level.width() as u64 * level.height() as u64)
+        .sum();
+
+    let mut keypoints = Vec::new();
+    let mut descriptors = Vec::new();
+    for level in &levels {
+        // keypoints are shared between levels according to their size
+        let level_area = level.width() as u64 * level.height() as u64;
+        let level_max = (max_keypoints as u64 * level_area / total_area.max(1)) as usize;
+        let scale_x = width as f32 / level.width() as f32;
+        let scale_y = height as f32 / level.height() as f32;
+        let integral = integral_image(level);
+        for (x, y) in detect_corners(level, level_max) {
+            let angle = orientation(level, x, y);
+            descriptors.push(describe(&integral, level.width(), x, y, angle));
+            keypoints.push(KeyPoint {
+                x: x as f32 * scale_x,
+                y: y as f32 * scale_y,
+            });
+        }
+    }
+    (keypoints, descriptors)
+}
+
+/// FAST-9 corners after non maximum suppression, strongest first
+fn detect_corners(image: &ImageBuffer<Luma<u8>, Vec<u8>>, max_corners: usize) -> Vec<(i32, i32)> {
+    let (width, height) = (image.width() as i32, image.height() as i32);
+    if (width <= 2 * BORDER) | (height <= 2 * BORDER) {
+        return Vec::new();
+    }
+    let raw = image.as_raw();
+    let scores: Vec<u32> = (0..height)
+        .into_par_iter()
+        .flat_map_iter(|y| {
+            (0..width).map(move |x| {
+                if (x < BORDER) | (y < BORDER) | (x >= width - BORDER) | (y >= height - BORDER) {
+                    0
+                } else {
+                    fast_score(raw, width, x, y)
+                }
+            })
+        })
+        .collect();
+    let mut corners: Vec<(i32, i32, u32)> = (BORDER..height - BORDER)
+        .into_par_iter()
+        .flat_map_iter(|y| {
+            let scores = &scores;
+            (BORDER..width - BORDER).filter_map(move |x| {
+                let score = scores[(y * width + x) as usize];
+                if score == 0 {
+                    return None;
+                }
+                // ties are won by the pixel later in the row order
+                for dy in -1..=1 {
+                    for dx in -1..=1 {
+                        let neighbour = scores[((y + dy) * width + x + dx) as usize];
+                        let earlier = (dy < 0) | ((dy == 0) & (dx < 0));
+                        if (neighbour > score) | ((neighbour == score) & !earlier & (dx | dy != 0))
+                        {
+                            return None;
+                        }
+                    }
+                }
+                Some((x, y, score))
+            })
+        })
+        .collect();
+    corners.sort_by_key(|corner| std::cmp::Reverse(corner.2));
+    corners
+        .into_iter()
+        .take(max_corners)
+        .map(|(x, y, _)| (x, y))
+        .collect()
+}
+
+/// Score of FAST corner, or 0 if there are no 9 contiguous circle pixels
+/// all brighter or all darker than the center by threshold
+fn fast_score(raw: &[u8], width: i32, x: i32, y: i32) -> u32 {
+    let center = raw[(y * width + x) as usize] as i32;
+    let mut brighter: u32 = 0;
+    let mut darker: u32 = 0;
+    let mut differences = [0i32; 16];
+    for (i, (dx, dy)) in FAST_CIRCLE.iter().enumerate() {
+        let difference = raw[((y + dy) * width + x + dx) as usize] as i32 - center;
+        differences[i] = difference;
+        if difference > FAST_THRESHOLD {
+            brighter |= 1 << i;
+        } else if difference < -FAST_THRESHOLD {
+            darker |= 1 << i;
+        }
+    }
+    let has_arc = |mask: u32| {
+        // circle is doubled so arcs crossing the start are found as well
+        let doubled = mask | (mask << 16);
+        let mut run = doubled;
+        for shift in 1..9 {
+            run &= doubled >> shift;
+        }
+        run != 0
+    };
+    let score = |sign: i32| {
+        differences
+            .iter()
+            .map(|difference| (sign * difference - FAST_THRESHOLD).max(0) as u32)
+            .sum::<u32>()
+    };
+    match (has_arc(brighter), has_arc(darker)) {
+        (true, _) => score(1),
+        (false, true) => score(-1),
+        (false, false) => 0,
+    }
+}
+
+/// orientation of corner from intensity centroid of circular patch around it
+fn orientation(image: &ImageBuffer<Luma<u8>, Vec<u8>>, x: i32, y: i32) -> f32 {
+    let mut moment_x: i64 = 0;
+    let mut moment_y: i64 = 0;
+    for dy in -PATCH_RADIUS..=PATCH_RADIUS {
+        for dx in -PATCH_RADIUS..=PATCH_RADIUS {
+            if dx * dx + dy * dy > PATCH_RADIUS * PATCH_RADIUS {
+                continue;
+            }
+            let value = image.get_pixel((x + dx) as u32, (y + dy) as u32)[0] as i64;
+            moment_x += dx as i64 * value;
+            moment_y += dy as i64 * value;
+        }
+    }
+    (moment_y as f32).atan2(moment_x as f32)
+}
+
+/// pairs of compared points, shared by all descriptors. Generated once with fixed seed
+fn descriptor_pattern() -> &'static [(f32, f32, f32, f32)] {
+    static PATTERN: OnceLock<Vec<(f32, f32, f32, f32)>> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        let mut rng = StdRng::seed_from_u64(0x5DEECE66D);
+        let mut random = || rng.random::<f32>();
+        // points are spread around the middle and kept inside the patch for every rotation
+        let radius = (PATCH_RADIUS - 1) as f32;
+        let mut point = || loop {
+            let x = (random() + random() + random() - 1.5) / 1.5 * radius;
+            let y = (random() + random() + random() - 1.5) / 1.5 * radius;
+            if x * x + y * y <= radius * radius {
+                return (x, y);
+            }
+        };
+        (0..256)
+            .map(|_| {
+                let (x1, y1) = point();
+                let (x2, y2) = point();
+                (x1, y1, x2, y2)
+            })
+            .collect()
+    })
+}
+
+/// 256 bit descriptor, where every bit compares smoothed intensity at two points of rotated pattern
+fn describe(integral: &[u32], width: u32, x: i32, y: i32, angle: f32) -> [u64; 4] {
+    let (sin, cos) = angle.sin_cos();
+    let sample = |px: f32, py: f32| {
+        let rotated_x = x + (px * cos - py * sin).round() as i32;
+        let rotated_y = y + (px * sin + py * cos).round() as i32;
+        box_sum(integral, width, rotated_x, rotated_y)
+    };
+    let mut descriptor = [0u64; 4];
+    for (i, (x1, y1, x2, y2)) in descriptor_pattern().iter().enumerate() {
+        if sample(*x1, *y1) < sample(*x2, *y2) {
+            descriptor[i / 64] |= 1 << (i % 64);
+        }
+    }
+    descriptor
+}
+
+/// integral image with extra zero row and column, so box sums need no border checks
+fn integral_image(image: &ImageBuffer<Luma<u8>, Vec<u8>>) -> Vec<u32> {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let mut integral = vec![0u32; (width + 1) * (height + 1)];
+    for y in 0..height {
+        let mut row_sum = 0u32;
+        for x in 0..width {
+            row_sum += image.as_raw()[y * width + x] as u32;
+            integral[(y + 1) * (width + 1) + x + 1] = integral[y * (width + 1) + x + 1] + row_sum;
+        }
+    }
+    integral
+}
+
+/// sum of 5x5 box around the point
+fn box_sum(integral: &[u32], width: u32, x: i32, y: i32) -> u32 {
+    let stride = width as usize + 1;
+    let (x1, y1) = ((x - 2) as usize, (y - 2) as usize);
+    let (x2, y2) = ((x + 3) as usize, (y + 3) as usize);
+    integral[y2 * stride + x2] + integral[y1 * stride + x1]
+        - integral[y1 * stride + x2]
+        - integral[y2 * stride + x1]
+}
+
+fn hamming(a: &[u64; 4], b: &[u64; 4]) -> u32 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(a, b)| (a ^ b).count_ones())
+        .sum()
+}
+
+/// Homography with most matches consistent with it, refined on all of them,
+/// and number of consistent matches
+fn ransac(matches: &[PointPair]) -> Option<([f64; 9], usize)> {
+    // fixed seed so same screen gives same result
+    let mut rng = StdRng::seed_from_u64(0x2545F4914F6CDD1D);
+    let mut best: Option<([f64; 9], usize)> = None;
+    let mut iterations = RANSAC_ITERATIONS;
+    let mut iteration = 0;
+    while iteration < iterations {
+        iteration += 1;
+        let mut sample = [0usize; 4];
+        for i in 0..4 {
+            sample[i] = loop {
+                let index = rng.random_range(0..matches.len());
+                if !sample[..i].contains(&index) {
+                    break index;
+                }
+            };
+        }
+        let sample_matches: Vec<PointPair> = sample.iter().map(|i| matches[*i]).collect();
+        let Some(homography) = fit_homography(&sample_matches) else {
+            continue;
+        };
+        let inliers = count_inliers(&homography, matches);
+        if best.is_none_or(|(_, best_inliers)| inliers > best_inliers) {
+            best = Some((homography, inliers));
+            // fewer iterations are needed to pick 4 inliers when most of matches are inliers
+            let inlier_ratio = inliers as f64 / matches.len() as f64;
+            let needed = (1.0 - 0.995f64).ln() / (1.0 - inlier_ratio.powi(4)).ln();
+            if needed.is_finite() {
+                iterations = iterations.min(needed.ceil().max(1.0) as u32);
+            }
+        }
+    }
+    let (homography, _) = best?;
+    let inlier_matches: Vec<PointPair> = matches
+        .iter()
+        .filter(|(from, to)| reprojection_error(&homography, *from, *to) < REPROJECTION_THRESHOLD)
+        .copied()
+        .collect();
+    let refined = fit_homography(&inlier_matches).unwrap_or(homography);
+    let refined_inliers = count_inliers(&refined, matches);
+    let (homography, inliers) = best?;
+    if refined_inliers >= inliers {
+        Some((refined, refined_inliers))
+    } else {
+        Some((homography, inliers))
+    }
+}
+
+fn count_inliers(homography: &[f64; 9], matches: &[PointPair]) -> usize {
+    matches
+        .iter()
+        .filter(|(from, to)| reprojection_error(homography, *from, *to) < REPROJECTION_THRESHOLD)
+        .count()
+}
+
+fn reprojection_error(homography: &[f64; 9], from: (f64, f64), to: (f64, f64)) -> f64 {
+    match project(homography, from) {
+        Some((x, y)) => ((x - to.0).powi(2) + (y - to.1).powi(2)).sqrt(),
+        None => f64::INFINITY,
+    }
+}
+
+fn project(homography: &[f64; 9], (x, y): (f64, f64)) -> Option<(f64, f64)> {
+    let w = homography[6] * x + homography[7] * y + homography[8];
+    if w.abs() < 1e-9 {
+        return None;
+    }
+    Some((
+        (homography[0] * x + homography[1] * y + homography[2]) / w,
+        (homography[3] * x + homography[4] * y + homography[5]) / w,
+    ))
+}
+
+/// Least squares homography from at least 4 point pairs. Points are normalized around their
+/// centroid first, which keeps the equations well conditioned
+fn fit_homography(matches: &[PointPair]) -> Option<[f64; 9]> {
+    if matches.len() < 4 {
+        return None;
+    }
+    let normalization = |points: &mut dyn Iterator<Item = (f64, f64)>| {
+        let points: Vec<(f64, f64)> = points.collect();
+        let count = points.len() as f64;
+        let center_x = points.iter().map(|p| p.0).sum::<f64>() / count;
+        let center_y = points.iter().map(|p| p.1).sum::<f64>() / count;
+        let mean_distance = points
+            .iter()
+            .map(|p| ((p.0 - center_x).powi(2) + (p.1 - center_y).powi(2)).sqrt())
+            .sum::<f64>()
+            / count;
+        if mean_distance < 1e-9 {
+            return None;
+        }
+        Some((center_x, center_y, std::f64::consts::SQRT_2 / mean_distance))
+    };
+    let (from_x, from_y, from_scale) = normalization(&mut matches.iter().map(|m| m.0))?;
+    let (to_x, to_y, to_scale) = normalization(&mut matches.iter().map(|m| m.1))?;
+
+    // normal equations of the linear system with last homography element fixed to 1
+    let mut ata = [[0.0f64; 8]; 8];
+    let mut atb = [0.0f64; 8];
+    for (from, to) in matches {
+        let x = (from.0 - from_x) * from_scale;
+        let y = (from.1 - from_y) * from_scale;
+        let u = (to.0 - to_x) * to_scale;
+        let v = (to.1 - to_y) * to_scale;
+        let rows = [
+            ([x, y, 1.0, 0.0, 0.0, 0.0, -x * u, -y * u], u),
+            ([0.0, 0.0, 0.0, x, y, 1.0, -x * v, -y * v], v),
+        ];
+        for (row, value) in rows {
+            for i in 0..8 {
+                for j in 0..8 {
+                    ata[i][j] += row[i] * row[j];
+                }
+                atb[i] += row[i] * value;
+            }
+        }
+    }
+    let h = solve(ata, atb)?;
+    let normalized = [h[0], h[1], h[2], h[3], h[4], h[5], h[6], h[7], 1.0];
+
+    // undo normalization: inverse(to normalization) * homography * from normalization
+    let from_matrix = [
+        from_scale,
+        0.0,
+        -from_scale * from_x,
+        0.0,
+        from_scale,
+        -from_scale * from_y,
+        0.0,
+        0.0,
+        1.0,
+    ];
+    let to_inverse = [
+        1.0 / to_scale,
+        0.0,
+        to_x,
+        0.0,
+        1.0 / to_scale,
+        to_y,
+        0.0,
+        0.0,
+        1.0,
+    ];
+    let homography = multiply(&to_inverse, &multiply(&normalized, &from_matrix));
+    if homography[8].abs() < 1e-12 {
+        return None;
+    }
+    Some(homography.map(|value| value / homography[8]))
+}
+
+fn multiply(a: &[f64; 9], b: &[f64; 9]) -> [f64; 9] {
+    let mut result = [0.0; 9];
+    for row in 0..3 {
+        for column in 0..3 {
+            result[row * 3 + column] = (0..3).map(|k| a[row * 3 + k] * b[k * 3 + column]).sum();
+        }
+    }
+    result
+}
+
+/// gaussian elimination with partial pivoting, None for singular system
+fn solve(mut a: [[f64; 8]; 8], mut b: [f64; 8]) -> Option<[f64; 8]> {
+    for column in 0..8 {
+        let pivot =
+            (column..8).max_by(|i, j| a[*i][column].abs().total_cmp(&a[*j][column].abs()))?;
+        if a[pivot][column].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(column, pivot);
+        b.swap(column, pivot);
+        let (pivot_rows, rows) = a.split_at_mut(column + 1);
+        let pivot_row = &pivot_rows[column];
+        for (offset, row) in rows.iter_mut().enumerate() {
+            let factor = row[column] / pivot_row[column];
+            for (value, pivot_value) in row[column..].iter_mut().zip(pivot_row[column..].iter()) {
+                *value -= factor * pivot_value;
+            }
+            b[column + 1 + offset] -= factor * b[column];
+        }
+    }
+    let mut x = [0.0; 8];
+    for row in (0..8).rev() {
+        let sum: f64 = (row + 1..8).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    Some(x)
+}
+
+/// Projected template needs to stay convex, not mirrored, and within scale range of pyramid
+fn is_plausible_quad(corners: &[(f64, f64); 4], template_area: f64) -> bool {
+    let mut area = 0.0;
+    for i in 0..4 {
+        let (x1, y1) = corners[i];
+        let (x2, y2) = corners[(i + 1) % 4];
+        let (x3, y3) = corners[(i + 2) % 4];
+        // corners go clockwise on screen, where y axis points down
+        if (x2 - x1) * (y3 - y2) - (y2 - y1) * (x3 - x2) <= 0.0 {
+            return false;
+        }
+        area += x1 * y2 - x2 * y1;
+    }
+    let max_scale = PYRAMID_SCALE.powi(PYRAMID_LEVELS as i32) as f64;
+    let area_ratio = area / 2.0 / template_area;
+    (area_ratio > 1.0 / (max_scale * max_scale)) & (area_ratio < max_scale * max_scale)
+}
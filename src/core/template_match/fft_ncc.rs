@@ -4,133 +4,293 @@
  * http://scribblethink.org/Work/nvisionInterface/vi95_lewis.pdf
  */
 
-use crate::{data::FFTData, imgtools};
-use core::cmp::max;
+use crate::data::{FFTData, FFTPlans, FFTScratch};
 use image::{ImageBuffer, Luma};
 use rayon::prelude::*;
-use rustfft::{num_complex::Complex, Fft, FftPlanner};
+use realfft::RealFftPlanner;
+use rustfft::num_complex::Complex;
+use std::sync::{Mutex, OnceLock};
 
-use super::{compute_integral_images, sum_region};
+use super::{with_scratch, IntegralImage};
+
+/// Zero mean image padded to prepared size and converted to frequency domain, along with its
+/// integral images needed for denominator. Spectrum does not depend on template, so it can be
+/// computed once and shared by all templates prepared for the same padded size
+#[derive(Default)]
+pub struct ImageSpectrum {
+    image_width: u32,
+    image_height: u32,
+    padded_width: u32,
+    padded_height: u32,
+    padded_image: Vec<f32>,
+    spectrum: Vec<Complex<f32>>,
+    integral_image: IntegralImage,
+}
+
+impl ImageSpectrum {
+    /// Computes spectrum of image for templates prepared like provided one.
+    /// None if image does not fit into padded size of prepared data
+    pub fn new(image: &ImageBuffer<Luma<u8>, Vec<u8>>, prepared_data: &FFTData) -> Option<Self> {
+        let mut spectrum = Self::default();
+        let mut fft_scratch = Vec::new();
+        spectrum
+            .compute(image, prepared_data, &mut fft_scratch)
+            .then_some(spectrum)
+    }
+
+    /// whether spectrum can be used with template prepared like provided one
+    pub fn fits(&self, prepared_data: &FFTData) -> bool {
+        (self.padded_width == prepared_data.padded_width)
+            & (self.padded_height == prepared_data.padded_height)
+            & (self.image_width >= prepared_data.template_width)
+            & (self.image_height >= prepared_data.template_height)
+    }
+
+    /// Computes spectrum into already allocated buffers. False if image does not fit into padded size
+    fn compute(
+        &mut self,
+        image: &ImageBuffer<Luma<u8>, Vec<u8>>,
+        prepared_data: &FFTData,
+        fft_scratch: &mut Vec<Complex<f32>>,
+    ) -> bool {
+        let (image_width, image_height) = image.dimensions();
+        if (image_width > prepared_data.padded_width)
+            | (image_height > prepared_data.padded_height)
+            | (image_width == 0)
+            | (image_height == 0)
+        {
+            return false;
+        }
+        self.image_width = image_width;
+        self.image_height = image_height;
+        self.padded_width = prepared_data.padded_width;
+        self.padded_height = prepared_data.padded_height;
+
+        // compute needed integral images for denominator calculation
+        self.integral_image.compute(image);
+        // whole image can be too large for exact integral image sum, so pixels are summed directly
+        let sum_image: u64 = image.as_raw().par_iter().map(|pixel| *pixel as u64).sum();
+        let image_average_total = sum_image as f32 / (image_height * image_width) as f32;
+
+        // zero mean image, meaning image pixel values - image average, padded with zeros
+        // and placed in top left corner, same as template
+        let padded_width = self.padded_width as usize;
+        let padded_image = &mut self.padded_image;
+        padded_image.clear();
+        padded_image.resize(padded_width * self.padded_height as usize, 0.0);
+        padded_image
+            .par_chunks_mut(padded_width)
+            .zip(image.as_raw().par_chunks(image_width as usize))
+            .for_each(|(padded_row, image_row)| {
+                for (padded_value, pixel) in padded_row.iter_mut().zip(image_row) {
+                    *padded_value = *pixel as f32 - image_average_total;
+                }
+            });
+
+        // real input gives symmetric spectrum, so only its first half is computed and stored
+        let fft = &prepared_data.plans.forward;
+        self.spectrum.clear();
+        self.spectrum
+            .resize(fft.complex_len(), Complex::new(0.0, 0.0));
+        fft_scratch.resize(fft.get_scratch_len(), Complex::new(0.0, 0.0));
+        fft.process_with_scratch(padded_image, &mut self.spectrum, fft_scratch)
+            .expect("buffer lengths match the fft plan");
+        true
+    }
+}
 
 pub fn fft_ncc(
     image: &ImageBuffer<Luma<u8>, Vec<u8>>,
     precision: f32,
     prepared_data: &FFTData,
 ) -> Vec<(u32, u32, f64)> {
-    // retreive all precalculated template data, most importantly template with already fft and conjugation calculated
-    // sum squared deviations will be needed for denominator
+    with_scratch(&prepared_data.scratch, |scratch| {
+        let FFTScratch {
+            spectrum,
+            product,
+            correlation,
+            fft_scratch,
+        } = scratch;
+        if !spectrum.compute(image, prepared_data, fft_scratch) {
+            return Vec::new();
+        }
+        found_above_precision(
+            correlations(spectrum, prepared_data, product, correlation, fft_scratch),
+            precision,
+        )
+    })
+}
 
-    let mut planner = FftPlanner::<f32>::new();
-    let fft: std::sync::Arc<dyn Fft<f32>> =
-        planner.plan_fft_forward((prepared_data.padded_size * prepared_data.padded_size) as usize);
-    let (image_width, image_height) = image.dimensions();
-    let image_vec: Vec<Vec<u8>> = imgtools::imagebuffer_to_vec(image);
+/// Same as fft_ncc, but searches image whose spectrum is already computed.
+/// Used when multiple templates are searched on the same image, so image is transformed only once
+pub fn fft_ncc_with_spectrum(
+    spectrum: &ImageSpectrum,
+    precision: f32,
+    prepared_data: &FFTData,
+) -> Vec<(u32, u32, f64)> {
+    with_scratch(&prepared_data.scratch, |scratch| {
+        found_above_precision(
+            correlations(
+                spectrum,
+                prepared_data,
+                &mut scratch.product,
+                &mut scratch.correlation,
+                &mut scratch.fft_scratch,
+            ),
+            precision,
+        )
+    })
+}
 
-    if (image_width < prepared_data.template_width)
-        || (image_height < prepared_data.template_height)
-    {
-        return Vec::new();
-    }
+/// Returns position and correlation of the best match, no matter how low its correlation is.
+/// None if template is larger than image
+pub fn fft_ncc_best_match(
+    image: &ImageBuffer<Luma<u8>, Vec<u8>>,
+    prepared_data: &FFTData,
+) -> Option<(u32, u32, f64)> {
+    with_scratch(&prepared_data.scratch, |scratch| {
+        let FFTScratch {
+            spectrum,
+            product,
+            correlation,
+            fft_scratch,
+        } = scratch;
+        if !spectrum.compute(image, prepared_data, fft_scratch) {
+            return None;
+        }
+        best_correlation(correlations(
+            spectrum,
+            prepared_data,
+            product,
+            correlation,
+            fft_scratch,
+        ))
+    })
+}
 
-    // compute needed integral images for denominator calculation
-    let (image_integral, squared_image_integral) = compute_integral_images(&image_vec);
+/// Same as fft_ncc_best_match, on image whose spectrum is already computed
+pub fn fft_ncc_best_match_with_spectrum(
+    spectrum: &ImageSpectrum,
+    prepared_data: &FFTData,
+) -> Option<(u32, u32, f64)> {
+    with_scratch(&prepared_data.scratch, |scratch| {
+        best_correlation(correlations(
+            spectrum,
+            prepared_data,
+            &mut scratch.product,
+            &mut scratch.correlation,
+            &mut scratch.fft_scratch,
+        ))
+    })
+}
+
+fn found_above_precision(
+    correlations: Option<impl ParallelIterator<Item = (u32, u32, f64)>>,
+    precision: f32,
+) -> Vec<(u32, u32, f64)> {
+    // correlations are filtered by precision
+    let mut found_points: Vec<(u32, u32, f64)> = match correlations {
+        Some(correlations) => correlations
+            .filter(|&(_, _, corr)| corr > precision as f64)
+            .collect(),
+        None => Vec::new(),
+    };
+    found_points.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap());
+    found_points
+}
 
-    //// calculating zero mean image
-    let sum_image: u64 = sum_region(&image_integral, 0, 0, image_width, image_height);
+fn best_correlation(
+    correlations: Option<impl ParallelIterator<Item = (u32, u32, f64)>>,
+) -> Option<(u32, u32, f64)> {
+    // flat image regions give NaN correlation
+    correlations?
+        .filter(|&(_, _, corr)| !corr.is_nan())
+        .max_by(|a, b| a.2.total_cmp(&b.2))
+}
 
-    // calculating zero mean image , meaning image pixel values - image zero value
-    let image_average_total = sum_image as f32 / (image_height * image_width) as f32; //@audit check image_height*image_width != 0
-    let mut zero_mean_image: Vec<Vec<f32>> =
-        vec![vec![0.0; image_width as usize]; image_height as usize];
-    for y in 0..image_height {
-        for x in 0..image_width {
-            let image_pixel_value = image.get_pixel(x, y)[0] as f32;
-            zero_mean_image[y as usize][x as usize] = image_pixel_value - image_average_total;
-        }
+/// Correlation at every template position, calculated in parallel when iterated.
+/// Product of spectra and its inverse transformation are computed into scratch buffers.
+/// None if template is larger than image or spectrum was computed for other padded size
+fn correlations<'a>(
+    spectrum: &'a ImageSpectrum,
+    prepared_data: &'a FFTData,
+    product: &'a mut Vec<Complex<f32>>,
+    correlation: &'a mut Vec<f32>,
+    fft_scratch: &mut Vec<Complex<f32>>,
+) -> Option<impl ParallelIterator<Item = (u32, u32, f64)> + 'a> {
+    if !spectrum.fits(prepared_data) {
+        return None;
     }
-
-    // padding to least squares and placing image in top left corner, same as template
-    let mut image_padded: Vec<Complex<f32>> = vec![
-        Complex::new(0.0, 0.0);
-        (prepared_data.padded_size * prepared_data.padded_size)
-            as usize
-    ];
-    for dy in 0..image_height {
-        for dx in 0..image_width {
-            let image_pixel_value = zero_mean_image[dy as usize][dx as usize];
-            image_padded[dy as usize * prepared_data.padded_size as usize + dx as usize] =
-                Complex::new(image_pixel_value, 0.0);
-        }
+    // calculate F(image) * F(template).conjugate and do inverse fft.
+    // Spectrum stays untouched so other templates can use it
+    let ifft = &prepared_data.plans.inverse;
+    product.clear();
+    product.extend(
+        spectrum
+            .spectrum
+            .iter()
+            .zip(prepared_data.template_conj_freq.iter())
+            .map(|(image_value, template_value)| image_value * template_value),
+    );
+    // first and, for even lengths, last value of real signal spectrum have no imaginary part,
+    // rounding errors are removed so inverse transformation accepts them
+    product[0].im = 0.0;
+    if ifft.len() % 2 == 0 {
+        product.last_mut().unwrap().im = 0.0;
     }
+    correlation.clear();
+    correlation.resize(ifft.len(), 0.0);
+    fft_scratch.resize(ifft.get_scratch_len(), Complex::new(0.0, 0.0));
+    ifft.process_with_scratch(product, correlation, fft_scratch)
+        .expect("buffer lengths match the fft plan");
 
-    // conver image into frequency domain
-    let ifft: std::sync::Arc<dyn Fft<f32>> =
-        planner.plan_fft_inverse((prepared_data.padded_size * prepared_data.padded_size) as usize);
-    fft.process(&mut image_padded);
-
-    // calculate F(image) * F(template).conjugate
-    let product_freq: Vec<Complex<f32>> = image_padded
-        .iter()
-        .zip(prepared_data.template_conj_freq.iter())
-        .map(|(&img_val, &tmpl_val)| img_val * tmpl_val)
-        .collect();
-    // do inverse fft
-    let mut fft_result: Vec<Complex<f32>> = product_freq.clone();
-    ifft.process(&mut fft_result);
-
-    // flatten for multithreading
-    let coords: Vec<(u32, u32)> = (0..=(image_height - prepared_data.template_height)) //@audit could underflow if image_height = 0
-        .flat_map(|y| (0..=(image_width - prepared_data.template_width)).map(move |x| (x, y))) //@audit could underflow if image_width = 0
-        .collect();
-    // multithreading pixel by pixel template sliding, where correlations are filtered by precision
+    let integral_image: &'a IntegralImage = &spectrum.integral_image;
+    let fft_result: &'a [f32] = correlation;
+    let positions_x = spectrum.image_width - prepared_data.template_width + 1;
+    let positions_y = spectrum.image_height - prepared_data.template_height + 1;
+    // multithreading pixel by pixel template sliding
     // sending all needed data to calculate nominator and denominator at each of pixel positions
-    let mut found_points: Vec<(u32, u32, f64)> = coords
-        .par_iter()
-        .map(|&(x, y)| {
-            let corr = fft_correlation_calculation(
-                &image_integral,
-                &squared_image_integral,
-                prepared_data.template_width,
-                prepared_data.template_height,
-                prepared_data.template_sum_squared_deviations,
-                x,
-                y,
-                prepared_data.padded_size,
-                &fft_result,
-            );
-
-            (x, y, corr)
-        })
-        .filter(|&(_, _, corr)| corr > precision as f64)
-        .collect();
-    found_points.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap());
+    Some(
+        (0..positions_x * positions_y)
+            .into_par_iter()
+            .map(move |i| {
+                let (x, y) = (i % positions_x, i / positions_x);
+                let corr = fft_correlation_calculation(
+                    integral_image,
+                    prepared_data.template_width,
+                    prepared_data.template_height,
+                    prepared_data.template_sum_squared_deviations,
+                    x,
+                    y,
+                    prepared_data.padded_width,
+                    prepared_data.padded_height,
+                    fft_result,
+                );
 
-    found_points
+                (x, y, corr)
+            }),
+    )
 }
 
 #[allow(dead_code)]
+#[allow(clippy::too_many_arguments)]
 fn fft_correlation_calculation(
-    image_integral: &[Vec<u64>],
-    squared_image_integral: &[Vec<u64>],
+    integral_image: &IntegralImage,
     template_width: u32,
     template_height: u32,
     template_sum_squared_deviations: f32,
     x: u32, // big image x value
     y: u32, // big image y value,
-    padded_size: u32,
-    fft_result: &[Complex<f32>],
+    padded_width: u32,
+    padded_height: u32,
+    fft_result: &[f32],
 ) -> f64 {
     /// Function for calculation of correlation at each pixel position
     ////////// denominator calculation
-    let sum_image: u64 = sum_region(image_integral, x, y, template_width, template_height);
+    let sum_image: u64 = integral_image.sum_region(x, y, template_width, template_height);
 
-    let sum_squared_image: u64 = sum_region(
-        squared_image_integral,
-        x,
-        y,
-        template_width,
-        template_height,
-    );
+    let sum_squared_image: u64 =
+        integral_image.squared_sum_region(x, y, template_width, template_height);
     let image_sum_squared_deviations = sum_squared_image as f64
         - (sum_image as f64).powi(2) / (template_height * template_width) as f64; //@audit check template_height*template_width!=0
     let denominator =
@@ -140,8 +300,8 @@ fn fft_correlation_calculation(
 
     // fft result is calculated invert of whole image and template that were padded and zero valued
     // each pixel position shows value for that template position
-    let numerator_value =
-        fft_result[(y * padded_size) as usize + x as usize].re / (padded_size * padded_size) as f32; //@audit guess the padded_size is always non zero but could be checked
+    let numerator_value = fft_result[(y * padded_width) as usize + x as usize]
+        / (padded_width * padded_height) as f32;
     let mut corr = numerator_value as f64 / denominator;
 
     if corr > 2.0 {
@@ -158,57 +318,82 @@ pub fn prepare_template_picture(
     /// precalculate all the neccessary data so its not slowing down main process
     /// returning template in frequency domain, with calculated conjugate
     let (template_width, template_height) = template.dimensions();
-    let padded_width = image_width.next_power_of_two();
-    let padded_height = image_height.next_power_of_two();
-    let padded_size = max(padded_width, padded_height);
-
-    let mut sum_template = 0.0;
-    // calculate needed sums
-    for y in 0..template_height {
-        for x in 0..template_width {
-            let template_value = template.get_pixel(x, y)[0] as f32;
-            sum_template += template_value;
-        }
-    }
+    /*
+    Padded image rows are laid one after another and transformed as one long signal, where template
+    at position (x, y) is shifted by y * padded_width + x. Template never crosses right edge of the
+    image at valid positions, so rows only need to be as wide as the image, without doubling the size.
+    Each side is only enlarged to next size made of factors 2, 3 and 5, for which fft is fast
+    */
+    let padded_width = fft_friendly_size(image_width.max(template_width));
+    let padded_height = fft_friendly_size(image_height.max(template_height));
+
+    let template_pixels = template.as_raw();
+    let sum_template: f32 = template_pixels.iter().map(|pixel| *pixel as f32).sum();
     let mean_template_value = sum_template / (template_height * template_width) as f32;
-    // create zero mean template
-    let mut zero_mean_template: Vec<Vec<f32>> =
-        vec![vec![0.0; template_width as usize]; template_height as usize];
-    let mut template_sum_squared_deviations: f32 = 0.0;
-    for y in 0..template_height {
-        for x in 0..template_width {
-            let template_value = template.get_pixel(x, y)[0] as f32;
-            let squared_deviation = (template_value - mean_template_value).powf(2.0);
-            template_sum_squared_deviations += squared_deviation;
-
-            // set zero mean value on new template
-            zero_mean_template[y as usize][x as usize] = template_value - mean_template_value;
-        }
-    }
+    let template_sum_squared_deviations: f32 = template_pixels
+        .iter()
+        .map(|pixel| (*pixel as f32 - mean_template_value).powf(2.0))
+        .sum();
     // pad the zero mean template
-    let mut template_padded: Vec<Complex<f32>> =
-        vec![Complex::new(0.0, 0.0); (padded_size * padded_size) as usize];
-    for dy in 0..template_height {
-        for dx in 0..template_width {
-            let template_pixel_value = zero_mean_template[dy as usize][dx as usize];
-            template_padded[dy as usize * padded_size as usize + dx as usize] =
-                Complex::new(template_pixel_value, 0.0);
+    let mut template_padded: Vec<f32> = vec![0.0; (padded_width * padded_height) as usize];
+    for (padded_row, template_row) in template_padded
+        .chunks_mut(padded_width as usize)
+        .zip(template_pixels.chunks(template_width as usize))
+    {
+        for (padded_value, pixel) in padded_row.iter_mut().zip(template_row) {
+            *padded_value = *pixel as f32 - mean_template_value;
         }
     }
     // convert template to frequency domain
-    let mut planner = FftPlanner::<f32>::new();
-    let fft: std::sync::Arc<dyn Fft<f32>> =
-        planner.plan_fft_forward((padded_size * padded_size) as usize);
-    fft.process(&mut template_padded);
+    let plans = fft_plans((padded_width * padded_height) as usize);
+    let mut template_freq = plans.forward.make_output_vec();
+    plans
+        .forward
+        .process(&mut template_padded, &mut template_freq)
+        .expect("buffer lengths match the fft plan");
     // calculate template conjugate
     let template_conj_freq: Vec<Complex<f32>> =
-        template_padded.iter().map(|&val| val.conj()).collect();
+        template_freq.iter().map(|&val| val.conj()).collect();
 
     FFTData {
         template_conj_freq,
         template_sum_squared_deviations,
         template_width,
         template_height,
-        padded_size,
+        padded_width,
+        padded_height,
+        plans,
+        scratch: Mutex::default(),
+    }
+}
+
+/// Forward and inverse plans for provided length. Planner is shared by whole process and keeps
+/// created plans, so templates prepared for the same size reuse them instead of planning again
+fn fft_plans(length: usize) -> FFTPlans {
+    static PLANNER: OnceLock<Mutex<RealFftPlanner<f32>>> = OnceLock::new();
+    let mut planner = PLANNER
+        .get_or_init(|| Mutex::new(RealFftPlanner::new()))
+        .lock()
+        .unwrap();
+    FFTPlans {
+        forward: planner.plan_fft_forward(length),
+        inverse: planner.plan_fft_inverse(length),
+    }
+}
+
+/// smallest even size not smaller than provided one, which has no prime factors other than 2, 3 and 5
+fn fft_friendly_size(size: u32) -> u32 {
+    let mut candidate = size.max(2).next_multiple_of(2);
+    loop {
+        let mut remainder = candidate;
+        for factor in [2, 3, 5] {
+            while remainder.is_multiple_of(factor) {
+                remainder /= factor;
+            }
+        }
+        if remainder == 1 {
+            return candidate;
+        }
+        candidate += 2;
     }
 }
@@ -1,166 +1,199 @@
+pub mod auto_mode;
+pub mod features;
 pub mod fft_ncc;
 #[cfg(feature = "opencl")]
 pub mod open_cl;
+#[cfg(feature = "opencl")]
+pub mod opencl_dense;
 pub mod opencl_kernel;
 #[cfg(feature = "opencl")]
 pub mod opencl_v2;
+pub mod pyramid;
+pub mod rotation;
 pub mod segmented_ncc;
+pub mod segmented_simd;
 pub mod slow_ncc;
 
-#[allow(dead_code)]
-fn compute_integral_image(image: &[Vec<u8>]) -> Vec<Vec<u64>> {
-    /*
-    Function that takes an image as input and computes an integral table (sum table).
-    Table is calculated in a way : f(x,y) = sum where f(x1<=x, y1<=y), meaning it always
-    sums all the pixels that are above and left of the value, including the value. Meaning if we take middle of
-    the picture as point, the top left corner will be summed and be represented in that value.
-    This is done a bit faster with an algorithm that says:
-        s(x,y) = f(x,y) + s(x-1,y) + s(x, y-1) - s(x-1, y-1), meaning it takes pixel value and adds
-        already calculated sum value left from it, above from it and subtracts the pixel value that is
-        above and left from it.
-    for x=0 or y=0 we just omit part of the formula, and for x=0 and y=0 we insert just the pixel value.
-    This table is made in order to more easily compute sums of images for further calculations in the algorithm.
-    example:
-    [1 1 1 1]
-    [1 1 1 1]
-    [1 1 1 1]
-    [1 1 1 1]
-    becomes
-    [ 1 2 3  4  ]
-    [ 2 4 6  8  ]
-    [ 3 6 9  12 ]
-    [ 4 8 12 16 ]
-    */
-
-    let height = image.len() as u32;
-    let width = if height > 0 { image[0].len() as u32 } else { 0 };
+use image::{ImageBuffer, Luma};
+use rayon::prelude::*;
+use rustfft::num_traits::{WrappingAdd, WrappingSub};
+use std::sync::Mutex;
 
-    let mut integral_image = vec![vec![0u64; width as usize]; height as usize];
+/// Integral image and squared integral image of searched image, stored in contiguous row major
+/// buffers. Both have an additional row and column of zeros on top and left, so region sums
+/// need no border checks. Buffers are reused when integrals are computed again.
+/// Integral image is kept in 32 bits with wrapping arithmetic, which gives exact sums for regions
+/// of up to 16 million pixels, while taking half the memory and fitting twice as many values
+/// into a SIMD register
+#[derive(Default)]
+pub struct IntegralImage {
+    width: u32,
+    sum: Vec<u32>,
+    squared_sum: Vec<u64>,
+}
 
-    for y in 0..height {
-        for x in 0..width {
-            let pixel_value = image[y as usize][x as usize] as u64;
-            let integral_value = if x == 0 && y == 0 {
-                pixel_value
-            } else if x == 0 {
-                pixel_value + integral_image[(y - 1) as usize][x as usize]
-            } else if y == 0 {
-                pixel_value + integral_image[y as usize][(x - 1) as usize]
-            } else {
-                pixel_value
-                    + integral_image[(y - 1) as usize][x as usize]
-                    + integral_image[y as usize][(x - 1) as usize]
-                    - integral_image[(y - 1) as usize][(x - 1) as usize]
-            };
-            integral_image[y as usize][x as usize] = integral_value;
-        }
+impl IntegralImage {
+    pub fn new(image: &ImageBuffer<Luma<u8>, Vec<u8>>) -> Self {
+        let mut integral_image = Self::default();
+        integral_image.compute(image);
+        integral_image
     }
 
-    integral_image
-}
-
-#[allow(dead_code)]
-fn compute_squared_integral_image(image: &[Vec<u8>]) -> Vec<Vec<u64>> {
-    /*
-    Same as compute_integral_image, except we always take squared value of pixel f(x,y).
-    */
+    /// Computes integral images of image into already allocated buffers, growing them if needed
+    pub fn compute(&mut self, image: &ImageBuffer<Luma<u8>, Vec<u8>>) {
+        /*
+        Integral image is a sum table where f(x,y) = sum of all pixels above and left of the value,
+        including the value. Meaning if we take middle of the picture as point, the top left corner
+        will be summed and represented in that value. Squared integral image sums squared pixel values.
+        example:
+        [1 1 1 1]
+        [1 1 1 1]
+        [1 1 1 1]
+        becomes, with added row and column of zeros
+        [0 0 0 0  0 ]
+        [0 1 2 3  4 ]
+        [0 2 4 6  8 ]
+        [0 3 6 9  12]
+        It is computed in two passes. First, every row is turned into running sums of that row,
+        which is done for all rows in parallel. Second, every row gets the row above added to it,
+        which runs over whole rows and is vectorized by compiler.
+        */
+        let (width, height) = image.dimensions();
+        let stride = width as usize + 1;
+        let length = stride * (height as usize + 1);
+        self.width = width;
+        self.sum.clear();
+        self.sum.resize(length, 0);
+        self.squared_sum.clear();
+        self.squared_sum.resize(length, 0);
+        if (width == 0) | (height == 0) {
+            return;
+        }
 
-    let height = image.len() as u32;
-    let width = if height > 0 { image[0].len() as u32 } else { 0 };
+        self.sum[stride..]
+            .par_chunks_mut(stride)
+            .zip(self.squared_sum[stride..].par_chunks_mut(stride))
+            .zip(image.as_raw().par_chunks(width as usize))
+            .for_each(|((sum_row, squared_sum_row), pixels)| {
+                let mut row_sum = 0u32;
+                let mut row_squared_sum = 0u64;
+                for ((sum, squared_sum), pixel) in sum_row[1..]
+                    .iter_mut()
+                    .zip(squared_sum_row[1..].iter_mut())
+                    .zip(pixels)
+                {
+                    row_sum = row_sum.wrapping_add(*pixel as u32);
+                    row_squared_sum += (*pixel as u64).pow(2);
+                    *sum = row_sum;
+                    *squared_sum = row_squared_sum;
+                }
+            });
+        rayon::join(
+            || add_rows_above(&mut self.sum, stride),
+            || add_rows_above(&mut self.squared_sum, stride),
+        );
+    }
 
-    let mut integral_image = vec![vec![0u64; width as usize]; height as usize];
+    /// sum of pixels in region
+    pub fn sum_region(&self, x: u32, y: u32, width: u32, height: u32) -> u64 {
+        region_sum(&self.sum, self.width, x, y, width, height) as u64
+    }
 
-    for y in 0..height {
-        for x in 0..width {
-            let pixel_value = (image[y as usize][x as usize] as u64).pow(2);
-            let integral_value = if x == 0 && y == 0 {
-                pixel_value
-            } else if x == 0 {
-                pixel_value + integral_image[(y - 1) as usize][x as usize]
-            } else if y == 0 {
-                pixel_value + integral_image[y as usize][(x - 1) as usize]
-            } else {
-                pixel_value
-                    + integral_image[(y - 1) as usize][x as usize]
-                    + integral_image[y as usize][(x - 1) as usize]
-                    - integral_image[(y - 1) as usize][(x - 1) as usize]
-            };
-            integral_image[y as usize][x as usize] = integral_value;
-        }
+    /// sum of squared pixels in region
+    pub fn squared_sum_region(&self, x: u32, y: u32, width: u32, height: u32) -> u64 {
+        region_sum(&self.squared_sum, self.width, x, y, width, height)
     }
 
-    integral_image
+    /// integral image buffer and its row length
+    pub(crate) fn sum_buffer(&self) -> (&[u32], usize) {
+        (&self.sum, self.width as usize + 1)
+    }
 }
 
-/// Compute both normal and squared integral image
-fn compute_integral_images(image: &[Vec<u8>]) -> (Vec<Vec<u64>>, Vec<Vec<u64>>) {
-    let height = image.len() as u32;
-    let width = if height > 0 { image[0].len() as u32 } else { 0 };
-    let mut integral_image = vec![vec![0u64; width as usize]; height as usize];
-    let mut squared_integral_image = vec![vec![0u64; width as usize]; height as usize];
-    for y in 0..height {
-        for x in 0..width {
-            let pixel_value = image[y as usize][x as usize] as u64;
-            let pixel_value_squared = (image[y as usize][x as usize] as u64).pow(2);
-            let (integral_value, squared_integral_value) = if x == 0 && y == 0 {
-                (pixel_value, pixel_value_squared)
-            } else if x == 0 {
-                (
-                    pixel_value + integral_image[(y - 1) as usize][x as usize],
-                    pixel_value_squared + squared_integral_image[(y - 1) as usize][x as usize],
-                )
-            } else if y == 0 {
-                (
-                    pixel_value + integral_image[y as usize][(x - 1) as usize],
-                    pixel_value_squared + squared_integral_image[y as usize][(x - 1) as usize],
-                )
-            } else {
-                (
-                    pixel_value
-                        + integral_image[(y - 1) as usize][x as usize]
-                        + integral_image[y as usize][(x - 1) as usize]
-                        - integral_image[(y - 1) as usize][(x - 1) as usize],
-                    pixel_value_squared
-                        + squared_integral_image[(y - 1) as usize][x as usize]
-                        + squared_integral_image[y as usize][(x - 1) as usize]
-                        - squared_integral_image[(y - 1) as usize][(x - 1) as usize],
-                )
-            };
-            integral_image[y as usize][x as usize] = integral_value;
-            squared_integral_image[y as usize][x as usize] = squared_integral_value;
+fn add_rows_above<T: Copy + WrappingAdd>(buffer: &mut [T], stride: usize) {
+    for y in 2..buffer.len() / stride {
+        let (above, current) = buffer.split_at_mut(y * stride);
+        for (value, above_value) in current[..stride]
+            .iter_mut()
+            .zip(above[(y - 1) * stride..].iter())
+        {
+            *value = value.wrapping_add(above_value);
         }
     }
-
-    (integral_image, squared_integral_image)
 }
 
-fn sum_region(integral_image: &[Vec<u64>], x: u32, y: u32, width: u32, height: u32) -> u64 {
+fn region_sum<T: Copy + WrappingAdd + WrappingSub>(
+    buffer: &[T],
+    image_width: u32,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+) -> T {
     /*
-    Used to calculate sum region of an integral image. Bottom right pixel will have summed up value of everything above and left.
-    In order to get exact sum value of subregion of the image, we take that sum from bottom right,
-    subtract from it the value that is on the top right , in the first row above the image, subtract the value that is on
-    the bottom left, in the first column left of the image, and add up the value that is top left in the
-    first row and colum above and left of the image.
-    [ 1 2   3  4 ]
-    [ 2 4/  6  8/]
-    [ 3 6   9 12 ]
-    [ 4 8/ 12 16/]
+    Used to calculate sum of subregion of the image. Bottom right value has summed up everything above and left.
+    In order to get exact sum value of subregion, we take that sum from bottom right,
+    subtract from it the value on the top right, in the first row above the region, subtract the value
+    on the bottom left, in the first column left of the region, and add up the value that is top left in the
+    first row and column above and left of the region. Row and column of zeros make this work on image borders.
+    [0 0 0  0  0 ]
+    [0 1 2/ 3  4/]
+    [0 2 4  6  8 ]
+    [0 3 6/ 9 12/]
      - ive marked most important numbers with /  that are important to calculate subimage that is like
-     [9  12] (bottom right of the image)
-     [12 16]
-     In order to calculate subimage sum, we take 16 - 8 - 8 + 4, which is 4
+     [6  8]
+     [9 12] (bottom right of the image)
+     In order to calculate subimage sum, we take 12 - 4 - 6 + 2, which is 4
      */
-    let mut sum = integral_image[(y + height - 1) as usize][(x + width - 1) as usize];
-    if x == 0 && y == 0 {
-        // do nothing
-    } else if y == 0 {
-        sum -= integral_image[(y + height - 1) as usize][(x - 1) as usize];
-    } else if x == 0 {
-        sum -= integral_image[(y - 1) as usize][(x + width - 1) as usize]
-    } else {
-        sum += integral_image[(y - 1) as usize][(x - 1) as usize];
-        sum -= integral_image[(y + height - 1) as usize][(x - 1) as usize];
-        sum -= integral_image[(y - 1) as usize][(x + width - 1) as usize];
+    let stride = image_width as usize + 1;
+    let (left, top) = (x as usize, y as usize);
+    let (right, bottom) = ((x + width) as usize, (y + height) as usize);
+    // wrapping arithmetic gives exact result as long as region sum fits into the type
+    buffer[bottom * stride + right]
+        .wrapping_add(&buffer[top * stride + left])
+        .wrapping_sub(&buffer[top * stride + right])
+        .wrapping_sub(&buffer[bottom * stride + left])
+}
+
+/// Runs function with scratch buffers held in prepared data, so they are not allocated on every search.
+/// When another thread is already using them, temporary buffers are used instead of waiting
+pub fn with_scratch<T: Default, R>(scratch: &Mutex<T>, f: impl FnOnce(&mut T) -> R) -> R {
+    match scratch.try_lock() {
+        Ok(mut guard) => f(&mut guard),
+        Err(_) => f(&mut T::default()),
+    }
+}
+
+/// Defines which of the positions above precision a search returns.
+/// All returns every found position sorted by correlation, which is the default.
+/// Best returns only the position with the highest correlation.
+/// First returns single position as soon as any is found, without scoring the rest of the region,
+/// which is fastest when only presence of the image matters. Found position is not necessarily the
+/// best one, nor the top left one. Early exit is done by segmented search on CPU, other match modes
+/// search whole region and return the best position
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FindStrategy {
+    First,
+    Best,
+    #[default]
+    All,
+}
+
+impl FindStrategy {
+    /// keeps positions returned by strategy from positions sorted by correlation
+    pub(crate) fn apply<T>(self, mut found: Vec<T>) -> Vec<T> {
+        if self != FindStrategy::All {
+            found.truncate(1);
+        }
+        found
+    }
+
+    /// count of positions a search needs to read back, None for all of them
+    #[cfg(feature = "opencl")]
+    pub(crate) fn max_results(self) -> Option<usize> {
+        match self {
+            FindStrategy::All => None,
+            _ => Some(1),
+        }
     }
-    sum
 }
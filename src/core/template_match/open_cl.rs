@@ -1,16 +1,20 @@
 use super::opencl_v2;
-use crate::core::template_match::{compute_integral_images, sum_region};
 use crate::data::SegmentedData;
-use crate::data::{GpuMemoryPointers, KernelStorage};
+use crate::data::{GpuMemoryPointers, IntegralKernels, KernelStorage, ResultCompaction};
 use image::{ImageBuffer, Luma};
 use ocl;
 use ocl::{Buffer, Context, Kernel, Program, Queue};
 
+/// how many times threshold is bisected when more positions are found than compaction can hold
+const COMPACTION_STEPS: usize = 12;
+
 pub enum OclVersion {
     V1,
     V2,
 }
 
+/// max_results limits positions read back from device by V1, which with max_results of 1 reads back
+/// only the best one. V2 gathers positions that pass its slow pass on device already
 pub fn gui_opencl_ncc_template_match(
     queue: &Queue,
     program: &Program,
@@ -21,29 +25,28 @@ pub fn gui_opencl_ncc_template_match(
     image: &ImageBuffer<Luma<u8>, Vec<u8>>,
     template_data: &SegmentedData,
     ocl_version: OclVersion,
+    max_results: Option<usize>,
 ) -> ocl::Result<Vec<(u32, u32, f32)>> {
     let (image_width, image_height) = image.dimensions();
 
-    let (image_integral, squared_image_integral) = compute_integral_images_ocl(&image);
+    compute_integral_images_ocl(
+        &gpu_memory_pointers.buffer_image,
+        &kernel_storage.integral_kernels,
+        image,
+    )?;
 
     let slow_expected_corr = precision * (template_data.expected_corr_slow - 0.001);
     match ocl_version {
         OclVersion::V1 => {
             let kernel = &kernel_storage.v1_kernel;
-            let mut gpu_results = gui_opencl_ncc(
+            return gui_opencl_ncc(
                 kernel,
-                &image_integral,
-                &squared_image_integral,
-                image_width,
-                image_height,
-                template_data.template_width,
-                template_data.template_height,
+                &kernel_storage.compaction,
                 gpu_memory_pointers,
                 precision,
-            )?;
-            gpu_results.retain(|&(_, _, value)| value >= slow_expected_corr);
-            gpu_results.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal));
-            return Ok(gpu_results);
+                slow_expected_corr,
+                max_results,
+            );
         }
         OclVersion::V2 => {
             let slow_segment_count = template_data.template_segments_slow.len() as i32;
@@ -57,8 +60,6 @@ pub fn gui_opencl_ncc_template_match(
             };
             return opencl_v2::gui_opencl_ncc_v2(
                 kernel,
-                &image_integral,
-                &squared_image_integral,
                 image_width,
                 image_height,
                 template_data.template_width,
@@ -79,29 +80,15 @@ pub fn gui_opencl_ncc_template_match(
     }
 }
 
+/// Runs V1 kernel and gathers positions with correlation of at least min_corr on device
 pub fn gui_opencl_ncc(
     kernel: &Kernel,
-    image_integral: &[u64],
-    squared_image_integral: &[u64],
-    image_width: u32,
-    image_height: u32,
-    template_width: u32,
-    template_height: u32,
+    compaction: &ResultCompaction,
     gpu_memory_pointers: &GpuMemoryPointers,
     precision: f32,
+    min_corr: f32,
+    max_results: Option<usize>,
 ) -> ocl::Result<Vec<(u32, u32, f32)>> {
-    let result_width = (image_width - template_width + 1) as usize;
-    let result_height = (image_height - template_height + 1) as usize;
-    let output_size = result_width * result_height;
-    gpu_memory_pointers
-        .buffer_image_integral
-        .write(image_integral)
-        .enq()?;
-    gpu_memory_pointers
-        .buffer_image_integral_squared
-        .write(squared_image_integral)
-        .enq()?;
-
     gpu_memory_pointers
         .buffer_precision
         .write(&vec![precision - 0.01])
@@ -110,62 +97,122 @@ pub fn gui_opencl_ncc(
     unsafe {
         kernel.enq()?;
     }
-    let mut results = vec![0.0f32; output_size];
-    gpu_memory_pointers
-        .results_buffer
-        .read(&mut results)
-        .enq()?;
+    compacted_results(compaction, min_corr, max_results)
+}
 
-    let final_results: Vec<(u32, u32, f32)> = results
-        .into_iter()
-        .enumerate()
-        .map(|(idx, corr)| {
-            let x = (idx % result_width) as u32;
-            let y = (idx / result_width) as u32;
-            (x, y, corr)
-        })
+/// Positions of results buffer with correlation of at least min_corr, sorted from highest correlation.
+/// Only found positions are read back from device, instead of whole correlation map. When more positions
+/// are found than compaction holds, threshold is raised by bisection until the best ones fit, so returned
+/// positions are always the best ones. With max_results of 1 only the best position is read back
+pub fn compacted_results(
+    compaction: &ResultCompaction,
+    min_corr: f32,
+    max_results: Option<usize>,
+) -> ocl::Result<Vec<(u32, u32, f32)>> {
+    if max_results == Some(1) {
+        return Ok(best_result(compaction)?
+            .filter(|&(_, _, corr)| corr >= min_corr)
+            .into_iter()
+            .collect());
+    }
+    let (mut found_count, best_corr) = run_compaction(compaction, min_corr)?;
+    if let Some(best_corr) = best_corr.filter(|_| found_count > compaction.capacity) {
+        // lowest threshold between min_corr and best correlation whose positions all fit
+        let (mut low, mut high) = (min_corr, best_corr);
+        for _ in 0..COMPACTION_STEPS {
+            let middle = (low + high) / 2.0;
+            if run_compaction(compaction, middle)?.0 > compaction.capacity {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        // when best correlation itself is found more times than compaction holds, some are dropped
+        found_count = run_compaction(compaction, high)?.0.min(compaction.capacity);
+    }
+    if found_count == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut positions = vec![ocl::prm::Int2::zero(); found_count];
+    let mut corrs = vec![0.0f32; found_count];
+    compaction
+        .buffer_found_positions
+        .read(&mut positions)
+        .enq()?;
+    compaction.buffer_found_corrs.read(&mut corrs).enq()?;
+    let mut found: Vec<(u32, u32, f32)> = positions
+        .iter()
+        .zip(corrs)
+        .map(|(position, corr)| (position[0] as u32, position[1] as u32, corr))
         .collect();
+    found.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal));
+    if let Some(max_results) = max_results {
+        found.truncate(max_results);
+    }
+    Ok(found)
+}
 
-    Ok(final_results)
+/// Best correlation of results buffer and its position, of which only 2 values are read back.
+/// None when no correlation was calculated
+pub fn best_result(compaction: &ResultCompaction) -> ocl::Result<Option<(u32, u32, f32)>> {
+    // nothing passes infinite threshold, only best correlation is computed
+    let Some(best_corr) = run_compaction(compaction, f32::INFINITY)?.1 else {
+        return Ok(None);
+    };
+    compaction.buffer_best_index.write(&vec![i32::MAX]).enq()?;
+    unsafe {
+        compaction.best_position_kernel.enq()?;
+    }
+    let mut best_index = vec![0i32; 1];
+    compaction.buffer_best_index.read(&mut best_index).enq()?;
+    let best_index = best_index[0] as u32;
+    Ok(Some((
+        best_index % compaction.result_width,
+        best_index / compaction.result_width,
+        best_corr,
+    )))
 }
 
-pub fn compute_integral_images_ocl(image: &ImageBuffer<Luma<u8>, Vec<u8>>) -> (Vec<u64>, Vec<u64>) {
-    let (width, height) = image.dimensions();
-    let image = image.as_raw();
-    let mut integral_image = vec![0u64; (width * height) as usize];
-    let mut squared_integral_image = vec![0u64; (width * height) as usize];
-    for y in 0..height {
-        for x in 0..width {
-            let pixel_value = image[(y * width + x) as usize] as u64;
-            let pixel_value_squared = (image[(y * width + x) as usize] as u64).pow(2);
-            let (integral_value, squared_integral_value) = if x == 0 && y == 0 {
-                (pixel_value, pixel_value_squared)
-            } else if x == 0 {
-                (
-                    pixel_value + integral_image[((y - 1) * width + x) as usize],
-                    pixel_value_squared + squared_integral_image[((y - 1) * width + x) as usize],
-                )
-            } else if y == 0 {
-                (
-                    pixel_value + integral_image[(y * width + (x - 1)) as usize],
-                    pixel_value_squared + squared_integral_image[(y * width + (x - 1)) as usize],
-                )
-            } else {
-                (
-                    pixel_value
-                        + integral_image[((y - 1) * width + x) as usize]
-                        + integral_image[(y * width + (x - 1)) as usize]
-                        - integral_image[((y - 1) * width + (x - 1)) as usize],
-                    pixel_value_squared
-                        + squared_integral_image[((y - 1) * width + x) as usize]
-                        + squared_integral_image[(y * width + (x - 1)) as usize]
-                        - squared_integral_image[((y - 1) * width + (x - 1)) as usize],
-                )
-            };
-            integral_image[(y * width + x) as usize] = integral_value;
-            squared_integral_image[(y * width + x) as usize] = squared_integral_value;
-        }
+/// Runs compaction kernel with threshold, returning count of positions above it, including those that
+/// did not fit, and best correlation
+fn run_compaction(
+    compaction: &ResultCompaction,
+    min_corr: f32,
+) -> ocl::Result<(usize, Option<f32>)> {
+    compaction.buffer_min_corr.write(&vec![min_corr]).enq()?;
+    compaction.buffer_found_count.write(&vec![0i32]).enq()?;
+    compaction.buffer_best_key.write(&vec![i32::MIN]).enq()?;
+    unsafe {
+        compaction.compact_kernel.enq()?;
     }
+    let mut found_count = vec![0i32; 1];
+    let mut best_key = vec![0i32; 1];
+    compaction.buffer_found_count.read(&mut found_count).enq()?;
+    compaction.buffer_best_key.read(&mut best_key).enq()?;
+    // key is left at minimum when results hold no number
+    let best_corr = (best_key[0] != i32::MIN).then(|| {
+        let bits = if best_key[0] >= 0 {
+            best_key[0]
+        } else {
+            best_key[0] ^ 0x7FFF_FFFF
+        };
+        f32::from_bits(bits as u32)
+    });
+    Ok((found_count[0] as usize, best_corr))
+}
 
-    (integral_image, squared_integral_image)
+/// Uploads grayscale image and computes its integral image and squared integral image on device,
+/// into buffers the integral kernels were created with. Only 1 byte per pixel is transferred
+pub fn compute_integral_images_ocl(
+    buffer_image: &Buffer<u8>,
+    integral_kernels: &IntegralKernels,
+    image: &ImageBuffer<Luma<u8>, Vec<u8>>,
+) -> ocl::Result<()> {
+    buffer_image.write(image.as_raw()).enq()?;
+    unsafe {
+        integral_kernels.rows_kernel.enq()?;
+        integral_kernels.columns_kernel.enq()?;
+    }
+    Ok(())
 }
@@ -0,0 +1,53 @@
+/*
+Dense normalized cross correlation on OpenCL device. Unlike segmented OpenCL versions, every template pixel
+is correlated at every position, so speed depends only on template and region size and not on how well the
+template can be segmented. This makes it the better fit for highly textured templates, which produce
+thousands of segments. Kernel is tiled, with image patches and template blocks loaded into local memory,
+described in dense_ncc_match kernel.
+*/
+
+use super::open_cl::{best_result, compacted_results, compute_integral_images_ocl};
+use crate::data::DenseKernelStorage;
+use image::{ImageBuffer, Luma};
+use ocl;
+
+/// Searches image of prepared region size and returns positions above precision, sorted from highest correlation.
+/// Only positions above precision are read back from device, at most max_results of them
+pub fn gui_opencl_dense_ncc(
+    storage: &DenseKernelStorage,
+    precision: f32,
+    image: &ImageBuffer<Luma<u8>, Vec<u8>>,
+    max_results: Option<usize>,
+) -> ocl::Result<Vec<(u32, u32, f32)>> {
+    run_dense_ncc(storage, image)?;
+    compacted_results(&storage.compaction, precision, max_results)
+}
+
+/// Best correlation in image of prepared region size and its position, no matter how low it is.
+/// None when correlation could not be calculated at any position, for instance on a flat image
+pub fn gui_opencl_dense_best_match(
+    storage: &DenseKernelStorage,
+    image: &ImageBuffer<Luma<u8>, Vec<u8>>,
+) -> ocl::Result<Option<(u32, u32, f32)>> {
+    run_dense_ncc(storage, image)?;
+    // -1 marks positions where correlation could not be calculated
+    Ok(best_result(&storage.compaction)?.filter(|&(_, _, corr)| corr > -1.0))
+}
+
+/// fills results buffer on device with correlation of every position
+fn run_dense_ncc(
+    storage: &DenseKernelStorage,
+    image: &ImageBuffer<Luma<u8>, Vec<u8>>,
+) -> ocl::Result<()> {
+    let (image_width, image_height) = image.dimensions();
+    if (image_width != storage.image_width) | (image_height != storage.image_height) {
+        return Err(ocl::Error::from(
+            "Searched image size differs from size dense OpenCL search was prepared for",
+        ));
+    }
+    compute_integral_images_ocl(&storage.buffer_image, &storage.integral_kernels, image)?;
+    unsafe {
+        storage.kernel.enq()?;
+    }
+    Ok(())
+}
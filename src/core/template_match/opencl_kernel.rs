@@ -1,5 +1,78 @@
 #[cfg(feature = "opencl")]
 pub const OCL_KERNEL: &str = r#"
+// Integral images are computed on device in two passes. First, running sums of every row are computed by
+// one workgroup per row. Every thread sums its chunk of the row, chunk sums are scanned in local memory,
+// and every thread then writes running sums of its chunk, starting from the sum of all chunks before it.
+// Second, every thread walks down one column adding the value above, where neighbouring threads
+// access neighbouring memory
+__kernel void integral_rows(
+    __global const uchar* image,
+    __global ulong* integral,
+    __global ulong* integral_sq,
+    const int image_width,
+    __local ulong* chunk_sums,
+    __local ulong* chunk_sq_sums
+) {
+    const int row_start = get_group_id(0) * image_width;
+    const int local_id = get_local_id(0);
+    const int threads = get_local_size(0);
+    const int chunk = (image_width + threads - 1) / threads;
+    const int start = min(local_id * chunk, image_width);
+    const int end = min(start + chunk, image_width);
+
+    ulong sum = 0;
+    ulong sq_sum = 0;
+    for (int x = start; x < end; x++) {
+        ulong pixel = image[row_start + x];
+        sum += pixel;
+        sq_sum += pixel * pixel;
+    }
+    chunk_sums[local_id] = sum;
+    chunk_sq_sums[local_id] = sq_sum;
+    barrier(CLK_LOCAL_MEM_FENCE);
+
+    // inclusive scan of chunk sums
+    for (int offset = 1; offset < threads; offset *= 2) {
+        ulong add = (local_id >= offset) ? chunk_sums[local_id - offset] : 0;
+        ulong add_sq = (local_id >= offset) ? chunk_sq_sums[local_id - offset] : 0;
+        barrier(CLK_LOCAL_MEM_FENCE);
+        chunk_sums[local_id] += add;
+        chunk_sq_sums[local_id] += add_sq;
+        barrier(CLK_LOCAL_MEM_FENCE);
+    }
+
+    sum = (local_id > 0) ? chunk_sums[local_id - 1] : 0;
+    sq_sum = (local_id > 0) ? chunk_sq_sums[local_id - 1] : 0;
+    for (int x = start; x < end; x++) {
+        ulong pixel = image[row_start + x];
+        sum += pixel;
+        sq_sum += pixel * pixel;
+        integral[row_start + x] = sum;
+        integral_sq[row_start + x] = sq_sum;
+    }
+}
+
+
+__kernel void integral_columns(
+    __global ulong* integral,
+    __global ulong* integral_sq,
+    const int image_width,
+    const int image_height
+) {
+    const int x = get_global_id(0);
+    if (x >= image_width) return;
+    ulong sum = 0;
+    ulong sq_sum = 0;
+    for (int y = 0; y < image_height; y++) {
+        int index = y * image_width + x;
+        sum += integral[index];
+        sq_sum += integral_sq[index];
+        integral[index] = sum;
+        integral_sq[index] = sq_sum;
+    }
+}
+
+
 inline ulong sum_region(
     __global const ulong* integral,
     int x,
@@ -383,4 +456,129 @@ __kernel void v2_segmented_match_integral_slow_pass (
 }
 
 
+// Dense normalized cross correlation, computing correlation with every template pixel, which does not depend
+// on how well template can be segmented. Workgroup of tile x tile threads computes tile x tile positions.
+// Template is processed in blocks of tile x tile pixels. For every block, image patch of 2tile x 2tile pixels,
+// covering block shifted by all positions of workgroup, and the template block are loaded into local memory
+// once, and every thread accumulates products for its position from local memory only.
+// Local memory needed is 5 * tile * tile floats, which fits devices with little local memory, and every thread
+// reaches every barrier, so kernel also runs on CPU implementations
+__kernel void dense_ncc_match(
+    __global const uchar* image,
+    __global const float* template_zero_mean,
+    __global const ulong* integral,
+    __global const ulong* integral_sq,
+    const int image_width,
+    const int image_height,
+    const int template_width,
+    const int template_height,
+    const float template_sq_dev,
+    __global float* results,
+    __local float* image_tile,
+    __local float* template_tile
+) {
+    const int tile = get_local_size(0);
+    const int patch_width = 2 * tile;
+    const int local_x = get_local_id(0);
+    const int local_y = get_local_id(1);
+    const int x = get_global_id(0);
+    const int y = get_global_id(1);
+    const int group_x = get_group_id(0) * tile;
+    const int group_y = get_group_id(1) * tile;
+    const int result_width = image_width - template_width + 1;
+    const int result_height = image_height - template_height + 1;
+
+    float nominator = 0.0f;
+    for (int block_y = 0; block_y < template_height; block_y += tile) {
+        for (int block_x = 0; block_x < template_width; block_x += tile) {
+            // every thread loads 4 pixels of image patch, pixels outside of image are zero
+            for (int patch_y = local_y; patch_y < patch_width; patch_y += tile) {
+                for (int patch_x = local_x; patch_x < patch_width; patch_x += tile) {
+                    int image_x = group_x + block_x + patch_x;
+                    int image_y = group_y + block_y + patch_y;
+                    image_tile[patch_y * patch_width + patch_x] =
+                        (image_x < image_width && image_y < image_height)
+                            ? (float)image[image_y * image_width + image_x]
+                            : 0.0f;
+                }
+            }
+            // and 1 pixel of template block, pixels outside of template are zero and do not contribute
+            int template_x = block_x + local_x;
+            int template_y = block_y + local_y;
+            template_tile[local_y * tile + local_x] =
+                (template_x < template_width && template_y < template_height)
+                    ? template_zero_mean[template_y * template_width + template_x]
+                    : 0.0f;
+            barrier(CLK_LOCAL_MEM_FENCE);
+
+            for (int j = 0; j < tile; j++) {
+                for (int i = 0; i < tile; i++) {
+                    nominator += image_tile[(local_y + j) * patch_width + local_x + i]
+                        * template_tile[j * tile + i];
+                }
+            }
+            barrier(CLK_LOCAL_MEM_FENCE);
+        }
+    }
+
+    if (x >= result_width || y >= result_height) return;
+    // template is zero mean, so nominator does not need image mean subtracted
+    float area = (float)(template_width * template_height);
+    ulong patch_sum = sum_region(integral, x, y, template_width, template_height, image_width);
+    ulong patch_sq_sum = sum_region_squared(integral_sq, x, y, template_width, template_height, image_width);
+    float var_img = (float)patch_sq_sum - ((float)patch_sum * (float)patch_sum) / area;
+    float denominator = sqrt(var_img * template_sq_dev);
+    float corr = (denominator > 0.0f) ? (nominator / denominator) : -1.0f;
+    results[y * result_width + x] = (corr < 2.0f) ? corr : -1.0f;
+}
+
+// Stream compaction of results buffer, so only positions above threshold are read back instead of
+// whole correlation map. Every thread appends its position when correlation is high enough, while
+// slots past max_found are only counted, so host can see that the threshold was too low.
+// Best correlation is kept with atomic_max on integer key that preserves order of floats
+// (negative floats have their value bits flipped), and second kernel finds position holding it
+inline int corr_key(float corr) {
+    int bits = as_int(corr);
+    return (bits >= 0) ? bits : (bits ^ 0x7FFFFFFF);
+}
+
+__kernel void compact_results(
+    __global const float* results,
+    const int result_count,
+    const int result_width,
+    __global const float* min_corr_buff,
+    const int max_found,
+    __global int2* found_positions,
+    __global float* found_corrs,
+    __global int* found_count,
+    __global int* best_key
+) {
+    int idx = get_global_id(0);
+    if (idx >= result_count) return;
+    float corr = results[idx];
+    if (isnan(corr)) return;
+    atomic_max(best_key, corr_key(corr));
+    if (corr >= min_corr_buff[0]) {
+        int index = atomic_add(found_count, 1);
+        if (index < max_found) {
+            found_positions[index] = (int2)(idx % result_width, idx / result_width);
+            found_corrs[index] = corr;
+        }
+    }
+}
+
+__kernel void best_result_position(
+    __global const float* results,
+    const int result_count,
+    __global const int* best_key,
+    __global int* best_index
+) {
+    int idx = get_global_id(0);
+    if (idx >= result_count) return;
+    float corr = results[idx];
+    if (!isnan(corr) && corr_key(corr) == best_key[0]) {
+        atomic_min(best_index, idx);
+    }
+}
+
 "#;
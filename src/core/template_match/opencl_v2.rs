@@ -39,8 +39,6 @@ false positives, but too high threshold can slow down fast process itself
 
 pub fn gui_opencl_ncc_v2(
     v2_kernel_fast_pass: &Kernel,
-    image_integral: &[u64],
-    squared_image_integral: &[u64],
     image_width: u32,
     image_height: u32,
     template_width: u32,
@@ -57,14 +55,6 @@ pub fn gui_opencl_ncc_v2(
     workgroup_size: i32,
     precision: f32,
 ) -> ocl::Result<Vec<(u32, u32, f32)>> {
-    gpu_memory_pointers
-        .buffer_image_integral
-        .write(image_integral)
-        .enq()?;
-    gpu_memory_pointers
-        .buffer_image_integral_squared
-        .write(squared_image_integral)
-        .enq()?;
     gpu_memory_pointers
         .buffer_precision
         .write(&vec![precision])
@@ -0,0 +1,245 @@
+/*
+Coarse to fine search on image pyramid. Template and searched image are downscaled by averaging
+blocks of pixels, candidates are searched on downscaled images with relaxed precision, and only
+small windows around the candidates are searched at full resolution with the regular segmented
+or FFT algorithm. Downscaling by 2 leaves a quarter of positions to score, downscaling by 4 a
+sixteenth, while full resolution verification keeps the returned correlations exact.
+*/
+
+use crate::core::template_match::{fft_ncc, segmented_ncc, with_scratch};
+use crate::data::{PreparedData, PyramidData};
+use crate::{imgtools, ImageProcessingError, MatchMode};
+use image::{ImageBuffer, Luma};
+use rayon::prelude::*;
+
+/// Settings of coarse to fine search. Factor by which images are downscaled is 2 or 4.
+/// Candidates are searched on downscaled images with precision lowered by precision_margin,
+/// since downscaling blurs details and lowers correlation of the real match.
+/// At most max_candidates best candidates, not overlapping each other, are verified at full resolution
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PyramidSearch {
+    pub factor: u32,
+    pub precision_margin: f32,
+    pub max_candidates: usize,
+}
+
+impl PyramidSearch {
+    pub fn new(factor: u32) -> Self {
+        Self {
+            factor,
+            precision_margin: 0.1,
+            max_candidates: 10,
+        }
+    }
+
+    pub fn with_precision_margin(mut self, precision_margin: f32) -> Self {
+        self.precision_margin = precision_margin.max(0.0);
+        self
+    }
+
+    pub fn with_max_candidates(mut self, max_candidates: usize) -> Self {
+        self.max_candidates = max_candidates.max(1);
+        self
+    }
+
+    /// distance in full resolution pixels that coarse position can be off from the real one
+    fn search_radius(&self) -> u32 {
+        self.factor * 2
+    }
+}
+
+/// Prepares downscaled template for candidate search, and full size template for verification
+/// in windows around candidates. Region size is size of the searched image
+pub fn prepare_template_picture(
+    template: &ImageBuffer<Luma<u8>, Vec<u8>>,
+    match_mode: &MatchMode,
+    region_width: u32,
+    region_height: u32,
+    settings: PyramidSearch,
+    user_threshold: Option<f32>,
+) -> Result<PyramidData, ImageProcessingError> {
+    if !matches!(settings.factor, 2 | 4) {
+        return Err(ImageProcessingError::new(
+            "Pyramid downscale factor needs to be 2 or 4",
+        ));
+    }
+    let (template_width, template_height) = template.dimensions();
+    let coarse_template = downscale(template, settings.factor);
+    if (coarse_template.width() < 3) | (coarse_template.height() < 3) {
+        return Err(ImageProcessingError::new(
+            "Template is too small for selected pyramid factor. Use smaller factor or search without pyramid",
+        ));
+    }
+    let (coarse, fine) = match match_mode {
+        MatchMode::Segmented => {
+            let coarse =
+                segmented_ncc::prepare_template_picture(&coarse_template, &false, user_threshold);
+            let fine = segmented_ncc::prepare_template_picture(template, &false, user_threshold);
+            for data in [&coarse, &fine] {
+                if let PreparedData::Segmented(segmented) = data {
+                    // mostly happens due to using too complex image with small max segments value
+                    if (segmented.template_segments_fast.len() == 1)
+                        | (segmented.template_segments_slow.len() == 1)
+                    {
+                        return Err(ImageProcessingError::new("Error in creating segmented template image for pyramid search. To resolve: either use FFT matching mode or use smaller pyramid factor"));
+                    }
+                }
+            }
+            (coarse, fine)
+        }
+        MatchMode::FFT => {
+            // full resolution search only covers window around candidate, so it is prepared for window size
+            let window_width = template_width + 2 * settings.search_radius();
+            let window_height = template_height + 2 * settings.search_radius();
+            (
+                PreparedData::FFT(fft_ncc::prepare_template_picture(
+                    &coarse_template,
+                    region_width / settings.factor,
+                    region_height / settings.factor,
+                )),
+                PreparedData::FFT(fft_ncc::prepare_template_picture(
+                    template,
+                    window_width.min(region_width),
+                    window_height.min(region_height),
+                )),
+            )
+        }
+        _ => {
+            return Err(ImageProcessingError::new(
+                "Pyramid search supports only Segmented and FFT match modes",
+            ))
+        }
+    };
+    Ok(PyramidData {
+        settings,
+        coarse,
+        fine,
+        template_width,
+        template_height,
+    })
+}
+
+/// Searches for template prepared for pyramid search. Returns positions above precision,
+/// sorted from highest correlation, with same correlations as regular search
+pub fn pyramid_template_match(
+    image: &ImageBuffer<Luma<u8>, Vec<u8>>,
+    precision: f32,
+    data: &PyramidData,
+) -> Vec<(u32, u32, f32)> {
+    let (image_width, image_height) = image.dimensions();
+    if (image_width < data.template_width) | (image_height < data.template_height) {
+        return Vec::new();
+    }
+    let settings = data.settings;
+    let coarse_image = downscale(image, settings.factor);
+    let coarse_precision = precision - settings.precision_margin;
+    let coarse_found: Vec<(u32, u32, f32)> = match &data.coarse {
+        PreparedData::Segmented(coarse) => {
+            segmented_ncc::fast_ncc_template_match(&coarse_image, coarse_precision, coarse, &false)
+        }
+        PreparedData::FFT(coarse) => fft_ncc::fft_ncc(&coarse_image, coarse_precision, coarse)
+            .into_iter()
+            .map(|(x, y, corr)| (x, y, corr as f32))
+            .collect(),
+        _ => return Vec::new(),
+    };
+    let candidates = best_candidates(&coarse_found, data);
+
+    // full resolution windows around candidates, clamped to image
+    let radius = settings.search_radius();
+    let windows = candidates.iter().map(|&(x, y)| {
+        let window_x = (x * settings.factor).saturating_sub(radius);
+        let window_y = (y * settings.factor).saturating_sub(radius);
+        (
+            window_x,
+            window_y,
+            (data.template_width + 2 * radius).min(image_width - window_x),
+            (data.template_height + 2 * radius).min(image_height - window_y),
+        )
+    });
+    let mut found: Vec<(u32, u32, f32)> = match &data.fine {
+        PreparedData::Segmented(fine) => with_scratch(&fine.scratch, |integral_image| {
+            integral_image.compute(image);
+            windows
+                .flat_map(|window| {
+                    segmented_ncc::fast_ncc_template_match_in_region(
+                        integral_image,
+                        window,
+                        precision,
+                        fine,
+                    )
+                    .into_iter()
+                    .map(move |(x, y, corr)| (x + window.0, y + window.1, corr))
+                })
+                .collect()
+        }),
+        PreparedData::FFT(fine) => windows
+            .flat_map(|window| {
+                let window_image =
+                    imgtools::cut_screen_region(window.0, window.1, window.2, window.3, image);
+                fft_ncc::fft_ncc(&window_image, precision, fine)
+                    .into_iter()
+                    .map(move |(x, y, corr)| (x + window.0, y + window.1, corr as f32))
+            })
+            .collect(),
+        _ => return Vec::new(),
+    };
+    // windows of neighbouring candidates can overlap
+    found.sort_by_key(|&(x, y, _)| (x, y));
+    found.dedup_by_key(|&mut (x, y, _)| (x, y));
+    found.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap());
+    found
+}
+
+/// Best coarse positions, skipping positions overlapping already selected ones,
+/// since they are the same match found at neighbouring positions
+fn best_candidates(coarse_found: &[(u32, u32, f32)], data: &PyramidData) -> Vec<(u32, u32)> {
+    let min_distance_x = (data.template_width / data.settings.factor / 2).max(1);
+    let min_distance_y = (data.template_height / data.settings.factor / 2).max(1);
+    let mut candidates: Vec<(u32, u32)> = Vec::new();
+    for &(x, y, _) in coarse_found {
+        if candidates.len() >= data.settings.max_candidates {
+            break;
+        }
+        let overlaps = candidates
+            .iter()
+            .any(|&(cx, cy)| (cx.abs_diff(x) < min_distance_x) & (cy.abs_diff(y) < min_distance_y));
+        if !overlaps {
+            candidates.push((x, y));
+        }
+    }
+    candidates
+}
+
+/// Downscales image by averaging blocks of factor x factor pixels. Pixels that do not fill
+/// a whole block on right and bottom edge are dropped
+pub fn downscale(
+    image: &ImageBuffer<Luma<u8>, Vec<u8>>,
+    factor: u32,
+) -> ImageBuffer<Luma<u8>, Vec<u8>> {
+    let (width, height) = image.dimensions();
+    let (small_width, small_height) = (width / factor, height / factor);
+    let mut small_image = ImageBuffer::new(small_width, small_height);
+    if small_width * small_height == 0 {
+        return small_image;
+    }
+    let block_area = factor * factor;
+    let pixels = image.as_raw();
+    small_image
+        .par_chunks_mut(small_width as usize)
+        .enumerate()
+        .for_each(|(small_y, small_row)| {
+            for (small_x, small_pixel) in small_row.iter_mut().enumerate() {
+                let mut sum = 0;
+                for y in small_y as u32 * factor..(small_y as u32 + 1) * factor {
+                    let row_start = (y * width) as usize + small_x * factor as usize;
+                    sum += pixels[row_start..row_start + factor as usize]
+                        .iter()
+                        .map(|pixel| *pixel as u32)
+                        .sum::<u32>();
+                }
+                *small_pixel = ((sum + block_area / 2) / block_area) as u8;
+            }
+        });
+    small_image
+}
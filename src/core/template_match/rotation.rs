@@ -0,0 +1,101 @@
+/*
+Rotated template variants. Each variant is prepared and searched as a regular template with
+segmented or FFT algorithm, and angle of the best matching variant is reported.
+Rotated template is placed on canvas large enough to hold it, and the corners left empty
+are filled with template mean. Flat corners add nothing to correlation nominator, but screen
+pixels under them still add to image variance, so even exact match correlates lower than 1.
+*/
+
+use crate::ImageProcessingError;
+use image::{ImageBuffer, Luma};
+
+/// Range of angles, in degrees, for which rotated template variants are prepared.
+/// Positive angles rotate template counter clockwise, as seen on screen.
+/// Rotated variant includes corners around the template, whose screen content lowers correlation
+/// of exact match. Loss grows with share of corners in variant area, largest at 45 degrees, where
+/// square template reaches only about 0.7 when surroundings vary as much as the template itself.
+/// Precision used for rotated search should be lowered accordingly
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RotationRange {
+    pub min_angle: f32,
+    pub max_angle: f32,
+    pub step: f32,
+}
+
+impl RotationRange {
+    pub fn new(min_angle: f32, max_angle: f32, step: f32) -> Self {
+        Self {
+            min_angle,
+            max_angle,
+            step,
+        }
+    }
+
+    /// angles of all variants, from min_angle up to max_angle, spaced by step
+    pub fn angles(&self) -> Result<Vec<f32>, ImageProcessingError> {
+        let valid = (self.step > 0.0) & (self.max_angle >= self.min_angle);
+        if !valid {
+            return Err(ImageProcessingError::new(
+                "Rotation step needs to be positive and max angle not smaller than min angle",
+            ));
+        }
+        // small tolerance so max angle is included despite float rounding
+        let count = ((self.max_angle - self.min_angle) / self.step + 1e-4).floor() as u32;
+        Ok((0..=count)
+            .map(|i| self.min_angle + i as f32 * self.step)
+            .collect())
+    }
+}
+
+/// Rotates template around its middle with bilinear interpolation.
+/// Canvas grows to hold whole rotated template, and corners outside it are filled with template mean
+pub fn rotate_template(
+    template: &ImageBuffer<Luma<u8>, Vec<u8>>,
+    angle: f32,
+) -> ImageBuffer<Luma<u8>, Vec<u8>> {
+    let (width, height) = template.dimensions();
+    let (sin, cos) = angle.to_radians().sin_cos();
+    // tolerance keeps size unchanged for multiples of 90 degrees
+    let new_width = ((width as f32 * cos.abs() + height as f32 * sin.abs()) - 1e-3)
+        .ceil()
+        .max(1.0) as u32;
+    let new_height = ((width as f32 * sin.abs() + height as f32 * cos.abs()) - 1e-3)
+        .ceil()
+        .max(1.0) as u32;
+    let pixel_count = (width as u64 * height as u64).max(1);
+    let mean = (template.as_raw().iter().map(|v| *v as u64).sum::<u64>() / pixel_count) as u8;
+
+    let center_x = (width as f32 - 1.0) / 2.0;
+    let center_y = (height as f32 - 1.0) / 2.0;
+    let new_center_x = (new_width as f32 - 1.0) / 2.0;
+    let new_center_y = (new_height as f32 - 1.0) / 2.0;
+    ImageBuffer::from_fn(new_width, new_height, |x, y| {
+        let dx = x as f32 - new_center_x;
+        let dy = y as f32 - new_center_y;
+        // inverse rotation, y axis points down
+        let source_x = dx * cos - dy * sin + center_x;
+        let source_y = dx * sin + dy * cos + center_y;
+        if (source_x < -0.5)
+            | (source_y < -0.5)
+            | (source_x > width as f32 - 0.5)
+            | (source_y > height as f32 - 0.5)
+        {
+            return Luma([mean]);
+        }
+        Luma([bilinear(template, source_x, source_y)])
+    })
+}
+
+fn bilinear(image: &ImageBuffer<Luma<u8>, Vec<u8>>, x: f32, y: f32) -> u8 {
+    let max_x = image.width() - 1;
+    let max_y = image.height() - 1;
+    let x = x.clamp(0.0, max_x as f32);
+    let y = y.clamp(0.0, max_y as f32);
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(max_x), (y0 + 1).min(max_y));
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+    let pixel = |px: u32, py: u32| image.get_pixel(px, py)[0] as f32;
+    let top = pixel(x0, y0) * (1.0 - fx) + pixel(x1, y0) * fx;
+    let bottom = pixel(x0, y1) * (1.0 - fx) + pixel(x1, y1) * fx;
+    (top * (1.0 - fy) + bottom * fy).round() as u8
+}
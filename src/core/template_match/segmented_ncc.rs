@@ -6,7 +6,8 @@
  * Please read NOTICE.md file
  */
 
-use crate::core::template_match::{compute_integral_images, sum_region};
+use crate::core::template_match::segmented_simd::{segment_nominators, LANES};
+use crate::core::template_match::{with_scratch, IntegralImage};
 
 use crate::{
     data::{PreparedData, SegmentedData},
@@ -19,6 +20,8 @@ use rustfft::num_traits::Pow;
 use std::collections::HashSet;
 use std::fs;
 use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
 
 pub fn fast_ncc_template_match(
     image: &ImageBuffer<Luma<u8>, Vec<u8>>,
@@ -31,15 +34,6 @@ pub fn fast_ncc_template_match(
     /// Template is
     let (image_width, image_height) = image.dimensions();
 
-    // compute image integral, or in other words sum tables where each pixel
-    // corresponds to sum of all the pixels above and left
-    let image_vec: Vec<Vec<u8>> = imgtools::imagebuffer_to_vec(image);
-    let (image_integral, squared_image_integral) = compute_integral_images(&image_vec);
-
-    // calculate precision into expected correlation
-    let adjusted_fast_expected_corr: f32 = precision * template_data.expected_corr_fast - 0.0001;
-    let adjusted_slow_expected_corr: f32 = precision * template_data.expected_corr_slow - 0.0001;
-
     if *debug {
         let fast_name = "debug/fast.png";
         save_template_segmented_images(
@@ -57,34 +51,19 @@ pub fn fast_ncc_template_match(
         );
     }
 
-    let coords: Vec<(u32, u32)> = (0..=(image_height - template_data.template_height))
-        .flat_map(|y| (0..=(image_width - template_data.template_width)).map(move |x| (x, y)))
-        .collect();
-    let mut found_points: Vec<(u32, u32, f32)> = coords
-        .par_iter()
-        .map(|&(x, y)| {
-            let corr = fast_correlation_calculation(
-                &image_integral,
-                &squared_image_integral,
-                &template_data.template_segments_fast,
-                &template_data.template_segments_slow,
-                template_data.template_width,
-                template_data.template_height,
-                template_data.segment_sum_squared_deviations_fast,
-                template_data.segment_sum_squared_deviations_slow,
-                template_data.segments_mean_fast,
-                template_data.segments_mean_slow,
-                x,
-                y,
-                adjusted_fast_expected_corr,
-            );
-            (x, y, corr as f32)
-        })
-        .filter(|&(_, _, corr)| corr >= adjusted_slow_expected_corr)
-        .collect();
+    // compute image integral, or in other words sum tables where each pixel
+    // corresponds to sum of all the pixels above and left
+    let mut found_points = with_scratch(&template_data.scratch, |integral_image| {
+        integral_image.compute(image);
+        fast_ncc_template_match_in_region(
+            integral_image,
+            (0, 0, image_width, image_height),
+            precision,
+            template_data,
+        )
+    });
 
     // returned list of found points
-
     found_points.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap());
     if *debug {
         let found_point_len = found_points.len();
@@ -96,6 +75,259 @@ pub fn fast_ncc_template_match(
     found_points
 }
 
+/// Runs segmented template match on region of image whose integral images are already computed.
+/// Used when multiple templates are searched on same screen capture, so integrals are computed once.
+/// Returned positions are relative to region start and are not sorted
+pub fn fast_ncc_template_match_in_region(
+    integral_image: &IntegralImage,
+    region: (u32, u32, u32, u32),
+    precision: f32,
+    template_data: &SegmentedData,
+) -> Vec<(u32, u32, f32)> {
+    let (region_x, region_y, region_width, region_height) = region;
+    if (region_width < template_data.template_width)
+        || (region_height < template_data.template_height)
+    {
+        return Vec::new();
+    }
+    // calculate precision into expected correlation
+    let adjusted_fast_expected_corr: f32 = precision * template_data.expected_corr_fast - 0.0001;
+    let adjusted_slow_expected_corr: f32 = precision * template_data.expected_corr_slow - 0.0001;
+
+    let positions_x = region_width - template_data.template_width + 1;
+    let positions_y = region_height - template_data.template_height + 1;
+    // positions in a row are evaluated in batches of LANES consecutive positions
+    let batches_x = positions_x.div_ceil(LANES as u32);
+    (0..batches_x * positions_y)
+        .into_par_iter()
+        .flat_map_iter(|i| {
+            let (x, y) = ((i % batches_x) * LANES as u32, i / batches_x);
+            let count = (positions_x - x).min(LANES as u32);
+            let corrs = batch_correlation_calculation(
+                integral_image,
+                template_data,
+                region_x + x,
+                region_y + y,
+                count,
+                adjusted_fast_expected_corr,
+            );
+            (0..count).map(move |lane| (x + lane, y, corrs[lane as usize]))
+        })
+        .filter(|&(_, _, corr)| corr >= adjusted_slow_expected_corr)
+        .collect()
+}
+
+/// Same as fast_ncc_template_match, but returns first position above precision that is found,
+/// without scoring the rest of the image
+pub fn fast_ncc_template_match_first(
+    image: &ImageBuffer<Luma<u8>, Vec<u8>>,
+    precision: f32,
+    template_data: &SegmentedData,
+) -> Option<(u32, u32, f32)> {
+    let (image_width, image_height) = image.dimensions();
+    with_scratch(&template_data.scratch, |integral_image| {
+        integral_image.compute(image);
+        fast_ncc_find_first_in_region(
+            integral_image,
+            (0, 0, image_width, image_height),
+            precision,
+            template_data,
+        )
+    })
+}
+
+/// Returns first position in region whose correlation passes precision. Rows are scanned in parallel
+/// and remaining rows are cancelled once any thread finds a position, so found position is not
+/// necessarily the top left or the best one. Returned position is relative to region start
+pub fn fast_ncc_find_first_in_region(
+    integral_image: &IntegralImage,
+    region: (u32, u32, u32, u32),
+    precision: f32,
+    template_data: &SegmentedData,
+) -> Option<(u32, u32, f32)> {
+    let (region_x, region_y, region_width, region_height) = region;
+    if (region_width < template_data.template_width)
+        || (region_height < template_data.template_height)
+    {
+        return None;
+    }
+    let adjusted_fast_expected_corr: f32 = precision * template_data.expected_corr_fast - 0.0001;
+    let adjusted_slow_expected_corr: f32 = precision * template_data.expected_corr_slow - 0.0001;
+
+    let positions_x = region_width - template_data.template_width + 1;
+    let positions_y = region_height - template_data.template_height + 1;
+    (0..positions_y).into_par_iter().find_map_any(|y| {
+        (0..positions_x).step_by(LANES).find_map(|x| {
+            let count = (positions_x - x).min(LANES as u32);
+            let corrs = batch_correlation_calculation(
+                integral_image,
+                template_data,
+                region_x + x,
+                region_y + y,
+                count,
+                adjusted_fast_expected_corr,
+            );
+            (0..count)
+                .map(|lane| (x + lane, y, corrs[lane as usize]))
+                .find(|&(_, _, corr)| corr >= adjusted_slow_expected_corr)
+        })
+    })
+}
+
+/// Returns position and correlation of the best match in region, no matter how low its correlation is.
+/// Rough correlation gives no bound on detailed one, so detailed correlation is calculated at every
+/// position, which makes result exact and independent of thread scheduling.
+/// Returned position is relative to region start. None if template is larger than region
+pub fn fast_ncc_best_match_in_region(
+    integral_image: &IntegralImage,
+    region: (u32, u32, u32, u32),
+    template_data: &SegmentedData,
+) -> Option<(u32, u32, f32)> {
+    let (region_x, region_y, region_width, region_height) = region;
+    if (region_width < template_data.template_width)
+        || (region_height < template_data.template_height)
+    {
+        return None;
+    }
+    let positions_x = region_width - template_data.template_width + 1;
+    let positions_y = region_height - template_data.template_height + 1;
+    let batches_x = positions_x.div_ceil(LANES as u32);
+    (0..batches_x * positions_y)
+        .into_par_iter()
+        .filter_map(|i| {
+            let (x, y) = ((i % batches_x) * LANES as u32, i / batches_x);
+            let count = (positions_x - x).min(LANES as u32);
+            // no minimum, so detailed correlation is calculated for every position
+            let corrs = batch_correlation_calculation(
+                integral_image,
+                template_data,
+                region_x + x,
+                region_y + y,
+                count,
+                f32::NEG_INFINITY,
+            );
+            // -100 marks invalid position
+            (0..count)
+                .map(|lane| (x + lane, y, corrs[lane as usize]))
+                .filter(|&(_, _, corr)| corr > -100.0)
+                .max_by(|a, b| a.2.total_cmp(&b.2))
+        })
+        .max_by(|a, b| a.2.total_cmp(&b.2))
+}
+
+/// Correlation of template at single image position, calculated without SIMD.
+/// Detailed segments are used only where rough correlation reaches min_expected_corr,
+/// and -100 marks position where correlation is not valid
+pub fn correlation_at(
+    integral_image: &IntegralImage,
+    template_data: &SegmentedData,
+    x: u32,
+    y: u32,
+    min_expected_corr: f32,
+) -> f32 {
+    fast_correlation_calculation(
+        integral_image,
+        &template_data.template_segments_fast,
+        &template_data.template_segments_slow,
+        template_data.template_width,
+        template_data.template_height,
+        template_data.segment_sum_squared_deviations_fast,
+        template_data.segment_sum_squared_deviations_slow,
+        template_data.segments_mean_fast,
+        template_data.segments_mean_slow,
+        x,
+        y,
+        min_expected_corr,
+    ) as f32
+}
+
+/// Correlations at count consecutive positions in a row, starting at x, y. Full batch of LANES
+/// positions shares segment loads in SIMD registers, while shorter batch at the end of a row
+/// is calculated position by position. Results are same as from correlation_at
+fn batch_correlation_calculation(
+    integral_image: &IntegralImage,
+    template_data: &SegmentedData,
+    x: u32,
+    y: u32,
+    count: u32,
+    min_expected_corr: f32,
+) -> [f32; LANES] {
+    let mut corrs = [-100.0; LANES];
+    if (count as usize) < LANES {
+        for lane in 0..count {
+            corrs[lane as usize] = correlation_at(
+                integral_image,
+                template_data,
+                x + lane,
+                y,
+                min_expected_corr,
+            );
+        }
+        return corrs;
+    }
+    let (template_width, template_height) =
+        (template_data.template_width, template_data.template_height);
+    let template_area = template_width * template_height;
+
+    // means and squared deviations of image under template, as in fast_correlation_calculation
+    let mut mean_image = [0.0; LANES];
+    let mut image_sum_squared_deviations = [0.0; LANES];
+    for lane in 0..LANES {
+        let lane_x = x + lane as u32;
+        let sum_image = integral_image.sum_region(lane_x, y, template_width, template_height);
+        let sum_squared_image =
+            integral_image.squared_sum_region(lane_x, y, template_width, template_height);
+        mean_image[lane] = sum_image as f32 / template_area as f32;
+        image_sum_squared_deviations[lane] =
+            sum_squared_image as f32 - (sum_image as f32).powi(2) / template_area as f32;
+    }
+
+    let nominators = segment_nominators(
+        integral_image,
+        x,
+        y,
+        &template_data.template_segments_fast,
+        template_data.segments_mean_fast,
+        &mean_image,
+    );
+    let mut detailed = [false; LANES];
+    for lane in 0..LANES {
+        let denominator = (image_sum_squared_deviations[lane]
+            * template_data.segment_sum_squared_deviations_fast)
+            .sqrt();
+        let corr = nominators[lane] / denominator;
+        if corr > 1.1 || corr.is_nan() {
+            continue;
+        }
+        corrs[lane] = corr;
+        detailed[lane] = corr >= min_expected_corr;
+    }
+
+    // second calculation with more detailed picture, only if any of positions needs it
+    if detailed.contains(&true) {
+        let nominators = segment_nominators(
+            integral_image,
+            x,
+            y,
+            &template_data.template_segments_slow,
+            template_data.segments_mean_slow,
+            &mean_image,
+        );
+        for lane in (0..LANES).filter(|lane| detailed[*lane]) {
+            let denominator = (image_sum_squared_deviations[lane]
+                * template_data.segment_sum_squared_deviations_slow)
+                .sqrt();
+            let corr = nominators[lane] / denominator;
+            corrs[lane] = if corr > 1.1 || corr.is_nan() {
+                -100.0
+            } else {
+                corr
+            };
+        }
+    }
+    corrs
+}
+
 fn save_template_segmented_images(
     template_segments: &[(u32, u32, u32, u32, f32)],
     template_width: u32,
@@ -157,8 +389,7 @@ fn save_template_segmented_images(
 }
 
 fn fast_correlation_calculation(
-    image_integral: &[Vec<u64>],
-    squared_image_integral: &[Vec<u64>],
+    integral_image: &IntegralImage,
     template_segments_fast: &[(u32, u32, u32, u32, f32)], // roughly segmented, low number of segments
     template_segments_slow: &[(u32, u32, u32, u32, f32)], // precisely segmented, high number of segments
     template_width: u32,
@@ -174,18 +405,13 @@ fn fast_correlation_calculation(
     let template_area = template_height * template_width;
 
     /////////// numerator calculation
-    let sum_image: u64 = sum_region(image_integral, x, y, template_width, template_height);
+    let sum_image: u64 = integral_image.sum_region(x, y, template_width, template_height);
     let mean_image = sum_image as f32 / (template_height * template_width) as f32;
     let mut nominator = 0.0;
 
     for (x1, y1, segment_width, segment_height, segment_value) in template_segments_fast {
-        let segment_image_sum = sum_region(
-            image_integral,
-            x + x1,
-            y + y1,
-            *segment_width,
-            *segment_height,
-        );
+        let segment_image_sum =
+            integral_image.sum_region(x + x1, y + y1, *segment_width, *segment_height);
         let segment_nominator_value: f32 = (segment_image_sum as f32
             - mean_image * (segment_height * segment_width) as f32)
             * (*segment_value - segments_fast_mean);
@@ -198,13 +424,8 @@ fn fast_correlation_calculation(
 
     ////////// denominator calculation
 
-    let sum_squared_image: u64 = sum_region(
-        squared_image_integral,
-        x,
-        y,
-        template_width,
-        template_height,
-    );
+    let sum_squared_image: u64 =
+        integral_image.squared_sum_region(x, y, template_width, template_height);
     let image_sum_squared_deviations =
         sum_squared_image as f32 - (sum_image as f32).powi(2) / template_area as f32;
     let denominator = (image_sum_squared_deviations * fast_segments_sum_squared_deviations).sqrt();
@@ -221,13 +442,8 @@ fn fast_correlation_calculation(
     if corr >= min_expected_corr {
         nominator = 0.0;
         for (x1, y1, segment_width, segment_height, segment_value) in template_segments_slow {
-            let segment_image_sum = sum_region(
-                image_integral,
-                x + x1,
-                y + y1,
-                *segment_width,
-                *segment_height,
-            );
+            let segment_image_sum =
+                integral_image.sum_region(x + x1, y + y1, *segment_width, *segment_height);
             let segment_nominator_value: f32 = (segment_image_sum as f32
                 - mean_image * (segment_height * segment_width) as f32)
                 * (*segment_value - segments_slow_mean);
@@ -361,6 +577,7 @@ pub fn prepare_template_picture(
         expected_corr_slow,
         segments_mean_fast,
         segments_mean_slow,
+        scratch: Mutex::default(),
     })
 }
 
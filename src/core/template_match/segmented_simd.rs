@@ -0,0 +1,238 @@
+/*
+SIMD evaluation of template segments for segmented correlation. Consecutive template positions in a row
+read integral image values from consecutive memory, so segment sums of LANES positions are loaded
+with single vector loads. Numerator of every position is accumulated in the same order and with the same
+f32 operations as in scalar calculation, so results do not depend on which implementation is used.
+AVX2 is detected at runtime on x86_64 and NEON on aarch64, with scalar fallback everywhere else.
+*/
+
+use super::IntegralImage;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// number of positions evaluated at once
+pub const LANES: usize = 8;
+
+static SIMD_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Enables or disables SIMD evaluation, mostly for benchmarks comparing both implementations
+#[allow(dead_code)]
+pub fn set_simd_enabled(enabled: bool) {
+    SIMD_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// name of implementation used on this machine
+#[allow(dead_code)]
+pub fn simd_backend() -> &'static str {
+    if !SIMD_ENABLED.load(Ordering::Relaxed) {
+        return "scalar";
+    }
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        return "avx2";
+    }
+    #[cfg(target_arch = "aarch64")]
+    if std::arch::is_aarch64_feature_detected!("neon") {
+        return "neon";
+    }
+    "scalar"
+}
+
+/// Numerators of segmented correlation for LANES consecutive positions starting at x, y.
+/// Mean image holds mean of image under template at each of the positions
+pub fn segment_nominators(
+    integral_image: &IntegralImage,
+    x: u32,
+    y: u32,
+    segments: &[(u32, u32, u32, u32, f32)],
+    segments_mean: f32,
+    mean_image: &[f32; LANES],
+) -> [f32; LANES] {
+    let (sums, stride) = integral_image.sum_buffer();
+    if SIMD_ENABLED.load(Ordering::Relaxed) {
+        #[cfg(target_arch = "x86_64")]
+        if is_x86_feature_detected!("avx2") {
+            // safe since avx2 support was checked
+            return unsafe {
+                segment_nominators_avx2(sums, stride, x, y, segments, segments_mean, mean_image)
+            };
+        }
+        #[cfg(target_arch = "aarch64")]
+        if std::arch::is_aarch64_feature_detected!("neon") {
+            // safe since neon support was checked
+            return unsafe {
+                segment_nominators_neon(sums, stride, x, y, segments, segments_mean, mean_image)
+            };
+        }
+    }
+    segment_nominators_scalar(sums, stride, x, y, segments, segments_mean, mean_image)
+}
+
+/// indexes of bottom right, top left, top right and bottom left corner of segment in integral image
+#[inline(always)]
+fn corner_indexes(
+    stride: usize,
+    x: u32,
+    y: u32,
+    segment: &(u32, u32, u32, u32, f32),
+) -> [usize; 4] {
+    let (x1, y1, segment_width, segment_height, _) = *segment;
+    let (left, top) = ((x + x1) as usize, (y + y1) as usize);
+    let (right, bottom) = (left + segment_width as usize, top + segment_height as usize);
+    [
+        bottom * stride + right,
+        top * stride + left,
+        top * stride + right,
+        bottom * stride + left,
+    ]
+}
+
+fn segment_nominators_scalar(
+    sums: &[u32],
+    stride: usize,
+    x: u32,
+    y: u32,
+    segments: &[(u32, u32, u32, u32, f32)],
+    segments_mean: f32,
+    mean_image: &[f32; LANES],
+) -> [f32; LANES] {
+    let mut nominators = [0.0f32; LANES];
+    for segment in segments {
+        let [bottom_right, top_left, top_right, bottom_left] =
+            corner_indexes(stride, x, y, segment);
+        let segment_area = (segment.2 * segment.3) as f32;
+        let segment_deviation = segment.4 - segments_mean;
+        for lane in 0..LANES {
+            let segment_image_sum = sums[bottom_right + lane]
+                .wrapping_add(sums[top_left + lane])
+                .wrapping_sub(sums[top_right + lane])
+                .wrapping_sub(sums[bottom_left + lane]);
+            nominators[lane] +=
+                (segment_image_sum as f32 - mean_image[lane] * segment_area) * segment_deviation;
+        }
+    }
+    nominators
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn segment_nominators_avx2(
+    sums: &[u32],
+    stride: usize,
+    x: u32,
+    y: u32,
+    segments: &[(u32, u32, u32, u32, f32)],
+    segments_mean: f32,
+    mean_image: &[f32; LANES],
+) -> [f32; LANES] {
+    use std::arch::x86_64::*;
+    // slicing checks bounds, so loads never read outside of integral image
+    let load = |index: usize| {
+        let values = &sums[index..index + LANES];
+        _mm256_loadu_si256(values.as_ptr() as *const __m256i)
+    };
+    let mean_image = _mm256_loadu_ps(mean_image.as_ptr());
+    let mut nominators = _mm256_setzero_ps();
+    for segment in segments {
+        let [bottom_right, top_left, top_right, bottom_left] =
+            corner_indexes(stride, x, y, segment);
+        let segment_image_sums = _mm256_sub_epi32(
+            _mm256_sub_epi32(
+                _mm256_add_epi32(load(bottom_right), load(top_left)),
+                load(top_right),
+            ),
+            load(bottom_left),
+        );
+        // avx2 only converts signed integers, while sums of large segments reach above 2^31.
+        // Halves of 16 bits convert exactly and their sum is rounded once, same as u32 to f32 cast
+        let segment_image_sums = _mm256_add_ps(
+            _mm256_mul_ps(
+                _mm256_cvtepi32_ps(_mm256_srli_epi32::<16>(segment_image_sums)),
+                _mm256_set1_ps(65536.0),
+            ),
+            _mm256_cvtepi32_ps(_mm256_and_si256(
+                segment_image_sums,
+                _mm256_set1_epi32(0xFFFF),
+            )),
+        );
+        let deviations = _mm256_sub_ps(
+            segment_image_sums,
+            _mm256_mul_ps(mean_image, _mm256_set1_ps((segment.2 * segment.3) as f32)),
+        );
+        nominators = _mm256_add_ps(
+            nominators,
+            _mm256_mul_ps(deviations, _mm256_set1_ps(segment.4 - segments_mean)),
+        );
+    }
+    let mut result = [0.0f32; LANES];
+    _mm256_storeu_ps(result.as_mut_ptr(), nominators);
+    result
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn segment_nominators_neon(
+    sums: &[u32],
+    stride: usize,
+    x: u32,
+    y: u32,
+    segments: &[(u32, u32, u32, u32, f32)],
+    segments_mean: f32,
+    mean_image: &[f32; LANES],
+) -> [f32; LANES] {
+    use std::arch::aarch64::*;
+    // neon registers hold 4 values, so positions are split into two halves
+    let load = |index: usize| {
+        let values = &sums[index..index + LANES];
+        (vld1q_u32(values.as_ptr()), vld1q_u32(values[4..].as_ptr()))
+    };
+    let mean_image = (
+        vld1q_f32(mean_image.as_ptr()),
+        vld1q_f32(mean_image[4..].as_ptr()),
+    );
+    let mut nominators = (vdupq_n_f32(0.0), vdupq_n_f32(0.0));
+    for segment in segments {
+        let [bottom_right, top_left, top_right, bottom_left] =
+            corner_indexes(stride, x, y, segment);
+        let (bottom_right, top_left, top_right, bottom_left) = (
+            load(bottom_right),
+            load(top_left),
+            load(top_right),
+            load(bottom_left),
+        );
+        let segment_area = vdupq_n_f32((segment.2 * segment.3) as f32);
+        let segment_deviation = vdupq_n_f32(segment.4 - segments_mean);
+        let half = |bottom_right, top_left, top_right, bottom_left, mean_image, nominators| {
+            let segment_image_sums = vsubq_u32(
+                vsubq_u32(vaddq_u32(bottom_right, top_left), top_right),
+                bottom_left,
+            );
+            let deviations = vsubq_f32(
+                vcvtq_f32_u32(segment_image_sums),
+                vmulq_f32(mean_image, segment_area),
+            );
+            vaddq_f32(nominators, vmulq_f32(deviations, segment_deviation))
+        };
+        nominators = (
+            half(
+                bottom_right.0,
+                top_left.0,
+                top_right.0,
+                bottom_left.0,
+                mean_image.0,
+                nominators.0,
+            ),
+            half(
+                bottom_right.1,
+                top_left.1,
+                top_right.1,
+                bottom_left.1,
+                mean_image.1,
+                nominators.1,
+            ),
+        );
+    }
+    let mut result = [0.0f32; LANES];
+    vst1q_f32(result.as_mut_ptr(), nominators.0);
+    vst1q_f32(result[4..].as_mut_ptr(), nominators.1);
+    result
+}
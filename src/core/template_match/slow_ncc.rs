@@ -1,8 +1,5 @@
-use crate::core::template_match::{
-    compute_integral_image, compute_squared_integral_image, sum_region,
-};
+use crate::core::template_match::IntegralImage;
 
-use crate::imgtools;
 use rayon::prelude::*;
 
 use image::{ImageBuffer, Luma};
@@ -19,10 +16,7 @@ pub fn slow_ncc_template_match(
     let mut min_corr = -100.0;
 
     // Compute integral images
-    let image_vec: Vec<Vec<u8>> = imgtools::imagebuffer_to_vec(image);
-
-    let image_integral = compute_integral_image(&image_vec);
-    let squared_image_integral = compute_squared_integral_image(&image_vec);
+    let integral_image = IntegralImage::new(image);
 
     let mut sum_template = 0;
     let mut sum_squared_template = 0;
@@ -60,8 +54,7 @@ pub fn slow_ncc_template_match(
         .map(|&(x, y)| {
             let corr = calculate_corr_value(
                 image,
-                &image_integral,
-                &squared_image_integral,
+                &integral_image,
                 template,
                 template_width,
                 template_height,
@@ -85,8 +78,7 @@ pub fn slow_ncc_template_match(
 
 fn calculate_corr_value(
     image: &ImageBuffer<Luma<u8>, Vec<u8>>,
-    image_integral: &[Vec<u64>],
-    squared_image_integral: &[Vec<u64>],
+    integral_image: &IntegralImage,
     template: &ImageBuffer<Luma<u8>, Vec<u8>>,
     template_width: u32,
     template_height: u32,
@@ -95,14 +87,9 @@ fn calculate_corr_value(
     x: u32, // big image x value
     y: u32, // big image y value
 ) -> f64 {
-    let sum_image: u64 = sum_region(image_integral, x, y, template_width, template_height);
-    let sum_squared_image: u64 = sum_region(
-        squared_image_integral,
-        x,
-        y,
-        template_width,
-        template_height,
-    );
+    let sum_image: u64 = integral_image.sum_region(x, y, template_width, template_height);
+    let sum_squared_image: u64 =
+        integral_image.squared_sum_region(x, y, template_width, template_height);
     let mean_image = sum_image as f64 / (template_height * template_width) as f64;
     let mean_template = sum_template as f64 / (template_height * template_width) as f64;
     let mut numerator = 0.0;
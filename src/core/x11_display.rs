@@ -0,0 +1,52 @@
+use crate::errors::AutoGuiError;
+use std::ptr;
+use std::sync::{Arc, Once};
+use x11::xlib::{_XDisplay, XCloseDisplay, XInitThreads, XOpenDisplay};
+
+static XLIB_THREADS_INIT: Once = Once::new();
+
+/// Reference counted X11 display connection, shared between screen, mouse and keyboard.
+/// Xlib is initialized for multithreaded use before the first connection is opened,
+/// so handle can be sent and shared between threads. Connection is closed when last handle is dropped
+#[derive(Debug, Clone)]
+pub struct X11Display {
+    inner: Arc<DisplayConnection>,
+}
+
+#[derive(Debug)]
+struct DisplayConnection {
+    display: *mut _XDisplay,
+}
+
+// Xlib calls are serialized internally after XInitThreads, so the connection can be used from any thread
+unsafe impl Send for DisplayConnection {}
+unsafe impl Sync for DisplayConnection {}
+
+impl Drop for DisplayConnection {
+    fn drop(&mut self) {
+        unsafe {
+            XCloseDisplay(self.display);
+        }
+    }
+}
+
+impl X11Display {
+    /// opens connection to the default display (usually ":0")
+    pub fn open() -> Result<Self, AutoGuiError> {
+        XLIB_THREADS_INIT.call_once(|| unsafe {
+            XInitThreads();
+        });
+        let display = unsafe { XOpenDisplay(ptr::null()) };
+        if display.is_null() {
+            return Err(AutoGuiError::OSFailure("Error grabbing display. Unable to open X display. Possible x11 issue, check if it is activated and that you're not running wayland".to_string()));
+        }
+        Ok(Self {
+            inner: Arc::new(DisplayConnection { display }),
+        })
+    }
+
+    /// raw display pointer, valid as long as this handle exists
+    pub fn as_ptr(&self) -> *mut _XDisplay {
+        self.inner.display
+    }
+}
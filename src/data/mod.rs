@@ -1,23 +1,34 @@
 #[cfg(feature = "opencl")]
 pub mod opencl;
-#[cfg(not(feature = "lite"))]
-use crate::MatchMode;
 use crate::RustAutoGui;
+#[cfg(not(feature = "lite"))]
+use crate::{AutoModeChoice, MatchMode, PyramidSearch};
 #[cfg(feature = "opencl")]
 pub use opencl::*;
 #[cfg(not(feature = "lite"))]
 use rustfft::{num_complex::Complex, num_traits::ToPrimitive};
 
+#[cfg(not(feature = "lite"))]
+use crate::core::template_match::fft_ncc::ImageSpectrum;
+#[cfg(not(feature = "lite"))]
+use crate::core::template_match::IntegralImage;
 #[cfg(not(feature = "lite"))]
 use image::{ImageBuffer, Luma};
 #[cfg(not(feature = "lite"))]
+use realfft::{ComplexToReal, RealToComplex};
+#[cfg(not(feature = "lite"))]
 use std::collections::HashMap;
+#[cfg(not(feature = "lite"))]
+use std::sync::{Arc, Mutex};
 
 #[cfg(not(feature = "lite"))]
 pub struct TemplateMatchingData {
     pub template: Option<ImageBuffer<Luma<u8>, Vec<u8>>>,
     pub prepared_data: PreparedData, // used direct load and search
     pub prepared_data_stored: HashMap<String, (PreparedData, (u32, u32, u32, u32), MatchMode)>, //prepared data, region, matchmode
+    pub rotated_aliases: HashMap<String, Vec<(f32, String)>>, // alias -> angle and stored key of each variant
+    pub pyramids: HashMap<String, PyramidData>, // stored key -> data of coarse to fine search
+    pub auto_choices: HashMap<String, AutoModeChoice>, // stored key -> mode picked by MatchMode::Auto
     pub match_mode: Option<MatchMode>,
     pub region: (u32, u32, u32, u32),
     pub alias_used: String,
@@ -50,6 +61,12 @@ impl BackupData {
 pub enum PreparedData {
     Segmented(SegmentedData),
     FFT(FFTData),
+    // FFT data of template edge map
+    Edge(FFTData),
+    Features(FeaturesData),
+    // template data is kept on OpenCL device
+    #[cfg(feature = "opencl")]
+    Dense(DenseData),
     None,
 }
 #[cfg(not(feature = "lite"))]
@@ -58,6 +75,10 @@ impl Clone for PreparedData {
         match self {
             PreparedData::Segmented(data) => PreparedData::Segmented(data.clone()),
             PreparedData::FFT(data) => PreparedData::FFT(data.clone()),
+            PreparedData::Edge(data) => PreparedData::Edge(data.clone()),
+            PreparedData::Features(data) => PreparedData::Features(data.clone()),
+            #[cfg(feature = "opencl")]
+            PreparedData::Dense(data) => PreparedData::Dense(data.clone()),
             PreparedData::None => PreparedData::None,
         }
     }
@@ -75,6 +96,8 @@ pub struct SegmentedData {
     pub expected_corr_slow: f32,
     pub segments_mean_fast: f32,
     pub segments_mean_slow: f32,
+    // integral images of last searched image, reused by next search
+    pub scratch: Mutex<IntegralImage>,
 }
 #[cfg(not(feature = "lite"))]
 impl Clone for SegmentedData {
@@ -90,6 +113,7 @@ impl Clone for SegmentedData {
             expected_corr_slow: self.expected_corr_slow,
             segments_mean_fast: self.segments_mean_fast,
             segments_mean_slow: self.segments_mean_slow,
+            scratch: Mutex::default(),
         }
     }
 }
@@ -99,7 +123,26 @@ pub struct FFTData {
     pub template_sum_squared_deviations: f32,
     pub template_width: u32,
     pub template_height: u32,
-    pub padded_size: u32,
+    pub padded_width: u32,
+    pub padded_height: u32,
+    pub plans: FFTPlans,
+    pub scratch: Mutex<FFTScratch>,
+}
+#[cfg(not(feature = "lite"))]
+/// real to complex fft plans for padded image length, shared by all templates of the same padded size
+#[derive(Clone)]
+pub struct FFTPlans {
+    pub forward: Arc<dyn RealToComplex<f32>>,
+    pub inverse: Arc<dyn ComplexToReal<f32>>,
+}
+#[cfg(not(feature = "lite"))]
+/// buffers of FFT search, reused by next search
+#[derive(Default)]
+pub struct FFTScratch {
+    pub spectrum: ImageSpectrum,
+    pub product: Vec<Complex<f32>>,
+    pub correlation: Vec<f32>,
+    pub fft_scratch: Vec<Complex<f32>>,
 }
 #[cfg(not(feature = "lite"))]
 impl Clone for FFTData {
@@ -109,7 +152,40 @@ impl Clone for FFTData {
             template_sum_squared_deviations: self.template_sum_squared_deviations,
             template_width: self.template_width,
             template_height: self.template_height,
-            padded_size: self.padded_size,
+            padded_width: self.padded_width,
+            padded_height: self.padded_height,
+            plans: self.plans.clone(),
+            scratch: Mutex::default(),
         }
     }
 }
+
+#[cfg(not(feature = "lite"))]
+/// templates of coarse to fine search. Coarse one is prepared from downscaled template, and fine one
+/// from full size template, for FFT prepared for size of window searched around candidate
+#[derive(Clone)]
+pub struct PyramidData {
+    pub settings: PyramidSearch,
+    pub coarse: PreparedData,
+    pub fine: PreparedData,
+    pub template_width: u32,
+    pub template_height: u32,
+}
+
+#[cfg(not(feature = "lite"))]
+/// corner found with FAST detector, in coordinates of full size image
+#[derive(Clone, Copy, Debug)]
+pub struct KeyPoint {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[cfg(not(feature = "lite"))]
+#[derive(Clone)]
+pub struct FeaturesData {
+    pub keypoints: Vec<KeyPoint>,
+    // 256 bit binary descriptor of each keypoint
+    pub descriptors: Vec<[u64; 4]>,
+    pub template_width: u32,
+    pub template_height: u32,
+}
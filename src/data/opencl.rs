@@ -1,15 +1,22 @@
 use crate::AutoGuiError;
+use image::{ImageBuffer, Luma};
 use ocl::{Buffer, Context, Program, Queue};
 use std::collections::HashMap;
+use std::sync::Mutex;
 
 pub struct OpenClData {
     pub device_list: Vec<DevicesInfo>,
+    // position of used device in device list
+    pub selected_device: usize,
     pub ocl_program: Program,
     #[allow(dead_code)]
     pub ocl_context: Context,
     pub ocl_queue: Queue,
     pub ocl_buffer_storage: HashMap<String, GpuMemoryPointers>,
-    pub ocl_kernel_storage: HashMap<String, KernelStorage>,
+    // kernels hold non thread safe argument storage, so they are accessed only under lock
+    pub ocl_kernel_storage: Mutex<HashMap<String, KernelStorage>>,
+    // buffers and kernels of templates prepared for MatchMode::DenseOcl
+    pub ocl_dense_storage: Mutex<HashMap<String, DenseKernelStorage>>,
     pub ocl_workgroup_size: u32,
 }
 
@@ -17,6 +24,153 @@ pub struct OpenClData {
 pub struct KernelStorage {
     pub v1_kernel: ocl::Kernel,
     pub v2_kernel_fast: ocl::Kernel,
+    pub integral_kernels: IntegralKernels,
+    pub compaction: ResultCompaction,
+}
+
+/// Kernels computing integral image and squared integral image of uploaded grayscale image on device,
+/// into buffers that stay on device and are read by matching kernels
+#[derive(Debug)]
+pub struct IntegralKernels {
+    pub rows_kernel: ocl::Kernel,
+    pub columns_kernel: ocl::Kernel,
+}
+
+impl IntegralKernels {
+    pub fn new(
+        program: &ocl::Program,
+        queue: &ocl::Queue,
+        buffer_image: &Buffer<u8>,
+        buffer_image_integral: &Buffer<u64>,
+        buffer_image_integral_squared: &Buffer<u64>,
+        image_width: u32,
+        image_height: u32,
+        max_workgroup_size: usize,
+    ) -> Result<Self, AutoGuiError> {
+        // one workgroup scans one row, local memory holds 2 sums per thread
+        let row_threads = max_workgroup_size.clamp(1, 256);
+        let rows_kernel = ocl::Kernel::builder()
+            .program(program)
+            .name("integral_rows")
+            .queue(queue.clone())
+            .global_work_size(image_height as usize * row_threads)
+            .local_work_size(row_threads)
+            .arg(buffer_image)
+            .arg(buffer_image_integral)
+            .arg(buffer_image_integral_squared)
+            .arg(image_width as i32)
+            .arg_local::<u64>(row_threads) // chunk_sums
+            .arg_local::<u64>(row_threads) // chunk_sq_sums
+            .build()?;
+        let columns_kernel = ocl::Kernel::builder()
+            .program(program)
+            .name("integral_columns")
+            .queue(queue.clone())
+            .global_work_size(image_width as usize)
+            .arg(buffer_image_integral)
+            .arg(buffer_image_integral_squared)
+            .arg(image_width as i32)
+            .arg(image_height as i32)
+            .build()?;
+        Ok(Self {
+            rows_kernel,
+            columns_kernel,
+        })
+    }
+}
+
+/// maximum count of positions above threshold gathered on device by one compaction
+pub const MAX_COMPACTED_RESULTS: usize = 4096;
+
+/// Kernels gathering positions of results buffer above threshold on device, and finding the best
+/// correlation and its position, so whole correlation map never has to be read back
+#[derive(Debug)]
+pub struct ResultCompaction {
+    pub compact_kernel: ocl::Kernel,
+    pub best_position_kernel: ocl::Kernel,
+    pub buffer_min_corr: Buffer<f32>,
+    pub buffer_found_positions: Buffer<ocl::prm::Int2>,
+    pub buffer_found_corrs: Buffer<f32>,
+    pub buffer_found_count: Buffer<i32>,
+    pub buffer_best_key: Buffer<i32>,
+    pub buffer_best_index: Buffer<i32>,
+    pub capacity: usize,
+    pub result_width: u32,
+}
+
+impl ResultCompaction {
+    pub fn new(
+        program: &ocl::Program,
+        queue: &ocl::Queue,
+        results_buffer: &Buffer<f32>,
+        result_width: u32,
+        result_height: u32,
+    ) -> Result<Self, AutoGuiError> {
+        let result_count = (result_width * result_height) as usize;
+        let capacity = MAX_COMPACTED_RESULTS.min(result_count);
+        let buffer_min_corr = Buffer::<f32>::builder()
+            .queue(queue.clone())
+            .len(1)
+            .build()?;
+        let buffer_found_positions = Buffer::<ocl::prm::Int2>::builder()
+            .queue(queue.clone())
+            .len(capacity)
+            .build()?;
+        let buffer_found_corrs = Buffer::<f32>::builder()
+            .queue(queue.clone())
+            .len(capacity)
+            .build()?;
+        let buffer_found_count = Buffer::<i32>::builder()
+            .queue(queue.clone())
+            .len(1)
+            .build()?;
+        let buffer_best_key = Buffer::<i32>::builder()
+            .queue(queue.clone())
+            .len(1)
+            .build()?;
+        let buffer_best_index = Buffer::<i32>::builder()
+            .queue(queue.clone())
+            .len(1)
+            .build()?;
+
+        let compact_kernel = ocl::Kernel::builder()
+            .program(program)
+            .name("compact_results")
+            .queue(queue.clone())
+            .global_work_size(result_count)
+            .arg(results_buffer)
+            .arg(result_count as i32)
+            .arg(result_width as i32)
+            .arg(&buffer_min_corr)
+            .arg(capacity as i32)
+            .arg(&buffer_found_positions)
+            .arg(&buffer_found_corrs)
+            .arg(&buffer_found_count)
+            .arg(&buffer_best_key)
+            .build()?;
+        let best_position_kernel = ocl::Kernel::builder()
+            .program(program)
+            .name("best_result_position")
+            .queue(queue.clone())
+            .global_work_size(result_count)
+            .arg(results_buffer)
+            .arg(result_count as i32)
+            .arg(&buffer_best_key)
+            .arg(&buffer_best_index)
+            .build()?;
+        Ok(Self {
+            compact_kernel,
+            best_position_kernel,
+            buffer_min_corr,
+            buffer_found_positions,
+            buffer_found_corrs,
+            buffer_found_count,
+            buffer_best_key,
+            buffer_best_index,
+            capacity,
+            result_width,
+        })
+    }
 }
 
 impl KernelStorage {
@@ -122,9 +276,172 @@ impl KernelStorage {
             .arg(&gpu_memory_pointers.buffer_precision)
             .build()?;
 
+        let integral_kernels = IntegralKernels::new(
+            program,
+            queue,
+            &gpu_memory_pointers.buffer_image,
+            &gpu_memory_pointers.buffer_image_integral,
+            &gpu_memory_pointers.buffer_image_integral_squared,
+            image_width,
+            image_height,
+            max_workgroup_size,
+        )?;
+
+        let compaction = ResultCompaction::new(
+            program,
+            queue,
+            &gpu_memory_pointers.results_buffer,
+            result_width as u32,
+            result_height as u32,
+        )?;
+
         Ok(Self {
             v1_kernel: kernel_v1,
             v2_kernel_fast: v2_kernel_fast_pass,
+            integral_kernels,
+            compaction,
+        })
+    }
+}
+
+/// size of template searched with MatchMode::DenseOcl, everything else is kept on device
+#[derive(Clone)]
+pub struct DenseData {
+    pub template_width: u32,
+    pub template_height: u32,
+}
+
+/// Buffers and kernel of dense NCC search of single template in region of prepared size.
+/// Template is uploaded once, and grayscale image on every search
+#[derive(Debug)]
+pub struct DenseKernelStorage {
+    pub kernel: ocl::Kernel,
+    pub integral_kernels: IntegralKernels,
+    pub buffer_image: Buffer<u8>,
+    // only used by kernels on device, kept so they live as long as the kernels
+    #[allow(dead_code)]
+    pub buffer_template: Buffer<f32>,
+    #[allow(dead_code)]
+    pub buffer_image_integral: Buffer<u64>,
+    #[allow(dead_code)]
+    pub buffer_image_integral_squared: Buffer<u64>,
+    #[allow(dead_code)]
+    pub results_buffer: Buffer<f32>,
+    pub compaction: ResultCompaction,
+    pub image_width: u32,
+    pub image_height: u32,
+}
+
+impl DenseKernelStorage {
+    pub fn new(
+        template: &ImageBuffer<Luma<u8>, Vec<u8>>,
+        program: &ocl::Program,
+        queue: &ocl::Queue,
+        image_width: u32,
+        image_height: u32,
+        max_workgroup_size: usize,
+    ) -> Result<Self, AutoGuiError> {
+        let (template_width, template_height) = template.dimensions();
+        let result_width = (image_width - template_width + 1) as usize;
+        let result_height = (image_height - template_height + 1) as usize;
+        let image_size = (image_width * image_height) as usize;
+
+        let pixel_count = (template_width * template_height) as f32;
+        let template_mean = template
+            .as_raw()
+            .iter()
+            .map(|pixel| *pixel as f32)
+            .sum::<f32>()
+            / pixel_count;
+        let template_zero_mean: Vec<f32> = template
+            .as_raw()
+            .iter()
+            .map(|pixel| *pixel as f32 - template_mean)
+            .collect();
+        let template_sq_dev: f32 = template_zero_mean.iter().map(|value| value * value).sum();
+
+        // square workgroup of tile x tile threads, as large as device allows
+        let tile = match max_workgroup_size {
+            256.. => 16,
+            64.. => 8,
+            _ => 4,
+        };
+
+        let buffer_image = Buffer::<u8>::builder()
+            .queue(queue.clone())
+            .len(image_size)
+            .build()?;
+        let buffer_template = Buffer::<f32>::builder()
+            .queue(queue.clone())
+            .len(template_zero_mean.len())
+            .copy_host_slice(&template_zero_mean)
+            .build()?;
+        let buffer_image_integral = Buffer::<u64>::builder()
+            .queue(queue.clone())
+            .len(image_size)
+            .build()?;
+        let buffer_image_integral_squared = Buffer::<u64>::builder()
+            .queue(queue.clone())
+            .len(image_size)
+            .build()?;
+        let results_buffer = Buffer::<f32>::builder()
+            .queue(queue.clone())
+            .len(result_width * result_height)
+            .build()?;
+
+        let kernel = ocl::Kernel::builder()
+            .program(program)
+            .name("dense_ncc_match")
+            .queue(queue.clone())
+            .global_work_size([
+                result_width.next_multiple_of(tile),
+                result_height.next_multiple_of(tile),
+            ])
+            .local_work_size([tile, tile])
+            .arg(&buffer_image)
+            .arg(&buffer_template)
+            .arg(&buffer_image_integral)
+            .arg(&buffer_image_integral_squared)
+            .arg(image_width as i32)
+            .arg(image_height as i32)
+            .arg(template_width as i32)
+            .arg(template_height as i32)
+            .arg(template_sq_dev)
+            .arg(&results_buffer)
+            .arg_local::<f32>(4 * tile * tile) // image_tile
+            .arg_local::<f32>(tile * tile) // template_tile
+            .build()?;
+
+        let integral_kernels = IntegralKernels::new(
+            program,
+            queue,
+            &buffer_image,
+            &buffer_image_integral,
+            &buffer_image_integral_squared,
+            image_width,
+            image_height,
+            max_workgroup_size,
+        )?;
+
+        let compaction = ResultCompaction::new(
+            program,
+            queue,
+            &results_buffer,
+            result_width as u32,
+            result_height as u32,
+        )?;
+
+        Ok(Self {
+            kernel,
+            integral_kernels,
+            buffer_image,
+            buffer_template,
+            buffer_image_integral,
+            buffer_image_integral_squared,
+            results_buffer,
+            compaction,
+            image_width,
+            image_height,
         })
     }
 }
@@ -136,6 +453,7 @@ pub struct GpuMemoryPointers {
     pub segment_fast_values_buffer: Buffer<f32>,
     pub segment_slow_values_buffer: Buffer<f32>,
     pub results_buffer: Buffer<f32>,
+    pub buffer_image: Buffer<u8>,
     pub buffer_image_integral: Buffer<u64>,
     pub buffer_image_integral_squared: Buffer<u64>,
     pub buffer_results_fast_v2: Buffer<ocl::core::Int2>,
@@ -206,6 +524,12 @@ impl GpuMemoryPointers {
             .len(output_size)
             .build()?;
 
+        // grayscale image is uploaded on every search, integral images are computed from it on device
+        let buffer_image = Buffer::<u8>::builder()
+            .queue(queue.clone())
+            .len(image_width * image_height)
+            .build()?;
+
         let buffer_image_integral = Buffer::<u64>::builder()
             .queue(queue.clone())
             .len(image_width * image_height)
@@ -259,6 +583,7 @@ impl GpuMemoryPointers {
             segment_fast_values_buffer: buffer_segment_values_fast,
             segment_slow_values_buffer: buffer_segment_values_slow,
             results_buffer: buffer_results,
+            buffer_image,
             buffer_image_integral,
             buffer_image_integral_squared,
             buffer_results_fast_v2: buffer_results_fast,
@@ -271,45 +596,180 @@ impl GpuMemoryPointers {
     }
 }
 
-#[derive(Debug)]
+/// environment variable read by RustAutoGui::new() to select OpenCL device, through OclDeviceSelector::from_env()
+pub const OCL_DEVICE_ENV: &str = "RUSTAUTOGUI_OCL_DEVICE";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OclDeviceType {
+    Gpu,
+    Cpu,
+    Accelerator,
+    Other,
+}
+
+/// Selects which OpenCL device is used by OpenCL match modes. Devices are listed by list_devices().
+/// When more devices fit, the one with the highest score is used
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum OclDeviceSelector {
+    /// device with the highest score, preferring GPUs over other types
+    #[default]
+    Best,
+    /// device at index of list_devices()
+    Index(u32),
+    /// device whose name matches regular expression
+    NameRegex(String),
+    /// device whose vendor contains provided text, ignoring case
+    Vendor(String),
+    /// device of provided type
+    Type(OclDeviceType),
+}
+
+impl OclDeviceSelector {
+    /// Selector set in RUSTAUTOGUI_OCL_DEVICE environment variable, as read by RustAutoGui::new().
+    /// None when variable is not set, error when it is empty or not valid unicode
+    pub fn from_env() -> Result<Option<Self>, AutoGuiError> {
+        match std::env::var(OCL_DEVICE_ENV) {
+            Err(std::env::VarError::NotPresent) => Ok(None),
+            Ok(value) if !value.trim().is_empty() => Ok(Some(Self::parse(&value))),
+            _ => Err(
+                ocl::Error::from(format!("{} is empty or not valid unicode", OCL_DEVICE_ENV))
+                    .into(),
+            ),
+        }
+    }
+
+    /// Parses selector from device index, device type "gpu", "cpu" or "accelerator", "vendor:<text>"
+    /// or "name:<regex>". Any other text is used as regular expression of device name
+    pub fn parse(value: &str) -> Self {
+        let value = value.trim();
+        if let Ok(index) = value.parse::<u32>() {
+            return Self::Index(index);
+        }
+        match value.to_lowercase().as_str() {
+            "gpu" => return Self::Type(OclDeviceType::Gpu),
+            "cpu" => return Self::Type(OclDeviceType::Cpu),
+            "accelerator" => return Self::Type(OclDeviceType::Accelerator),
+            _ => (),
+        }
+        if let Some(vendor) = value.strip_prefix("vendor:") {
+            return Self::Vendor(vendor.to_string());
+        }
+        Self::NameRegex(value.strip_prefix("name:").unwrap_or(value).to_string())
+    }
+
+    /// position of selected device in device list
+    pub(crate) fn select(&self, devices: &[DevicesInfo]) -> Result<usize, AutoGuiError> {
+        let candidates: Vec<usize> = match self {
+            OclDeviceSelector::Best => {
+                let gpus: Vec<usize> = (0..devices.len())
+                    .filter(|&i| devices[i].device_type == OclDeviceType::Gpu)
+                    .collect();
+                if gpus.is_empty() {
+                    (0..devices.len()).collect()
+                } else {
+                    gpus
+                }
+            }
+            OclDeviceSelector::Index(index) => (0..devices.len())
+                .filter(|&i| devices[i].index == *index)
+                .collect(),
+            OclDeviceSelector::NameRegex(pattern) => {
+                let regex = regex::Regex::new(pattern).map_err(|err| {
+                    ocl::Error::from(format!("Invalid OpenCL device name regex: {}", err))
+                })?;
+                (0..devices.len())
+                    .filter(|&i| regex.is_match(&devices[i].name))
+                    .collect()
+            }
+            OclDeviceSelector::Vendor(vendor) => (0..devices.len())
+                .filter(|&i| {
+                    devices[i]
+                        .brand
+                        .to_lowercase()
+                        .contains(&vendor.to_lowercase())
+                })
+                .collect(),
+            OclDeviceSelector::Type(device_type) => (0..devices.len())
+                .filter(|&i| devices[i].device_type == *device_type)
+                .collect(),
+        };
+        candidates
+            .into_iter()
+            .max_by_key(|&i| devices[i].score)
+            .ok_or_else(|| {
+                ocl::Error::from(format!("No OpenCL device found for {:?}", self)).into()
+            })
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct DevicesInfo {
-    #[allow(dead_code)]
-    device: ocl::Device,
+    pub(crate) device: ocl::Device,
+    pub(crate) platform: ocl::Platform,
     pub index: u32,
     pub global_mem_size: u32,
     pub clock_frequency: u32,
     pub compute_units: u32,
+    pub max_workgroup_size: u32,
+    pub device_type: OclDeviceType,
     pub brand: String,
     pub name: String,
+    pub platform_name: String,
     pub score: u32,
 }
 #[cfg(feature = "opencl")]
 impl DevicesInfo {
     pub fn new(
         device: ocl::Device,
+        platform: ocl::Platform,
         index: u32,
-        global_mem_size: u32,
-        clock_frequency: u32,
-        compute_units: u32,
-        brand: String,
-        name: String,
-        score: u32,
-    ) -> Self {
-        Self {
+    ) -> Result<Self, AutoGuiError> {
+        let read_number = |info: ocl::enums::DeviceInfo| -> Result<u64, AutoGuiError> {
+            device
+                .info(info)?
+                .to_string()
+                .parse()
+                .map_err(|_| AutoGuiError::OSFailure("Failed to read GPU data".to_string()))
+        };
+        let device_type = match device.info(ocl::enums::DeviceInfo::Type)? {
+            ocl::enums::DeviceInfoResult::Type(device_type) => {
+                if device_type.contains(ocl::flags::DeviceType::GPU) {
+                    OclDeviceType::Gpu
+                } else if device_type.contains(ocl::flags::DeviceType::CPU) {
+                    OclDeviceType::Cpu
+                } else if device_type.contains(ocl::flags::DeviceType::ACCELERATOR) {
+                    OclDeviceType::Accelerator
+                } else {
+                    OclDeviceType::Other
+                }
+            }
+            _ => OclDeviceType::Other,
+        };
+        let global_mem_size =
+            (read_number(ocl::enums::DeviceInfo::GlobalMemSize)? / 1_048_576) as u32;
+        let compute_units = read_number(ocl::enums::DeviceInfo::MaxComputeUnits)? as u32;
+        let clock_frequency = read_number(ocl::enums::DeviceInfo::MaxClockFrequency)? as u32;
+        let max_workgroup_size = read_number(ocl::enums::DeviceInfo::MaxWorkGroupSize)? as u32;
+        let score = global_mem_size * 2 + compute_units * 10 + clock_frequency;
+        Ok(Self {
             device,
+            platform,
             index,
             global_mem_size,
             clock_frequency,
             compute_units,
-            brand,
-            name,
+            max_workgroup_size,
+            device_type,
+            brand: device.vendor()?,
+            name: device.name()?,
+            platform_name: platform.name()?,
             score,
-        }
+        })
     }
     pub fn print_device(&self) -> String {
         format!(
-            "Device brand: {}, name: {}\nMemory: {} MB, Compute units: {}, Clock Freq :{} mhz, index: {}, score: {}",
-            self.brand, self.name, self.global_mem_size, self.compute_units, self.clock_frequency, self.index, self.score
+            "Device brand: {}, name: {}, type: {:?}, platform: {}\nMemory: {} MB, Compute units: {}, Clock Freq :{} mhz, index: {}, score: {}",
+            self.brand, self.name, self.device_type, self.platform_name, self.global_mem_size, self.compute_units, self.clock_frequency, self.index, self.score
         )
     }
 }
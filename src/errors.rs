@@ -13,6 +13,7 @@ pub enum AutoGuiError {
     IoError(std::io::Error),
     AliasError(String),
     OutOfBoundsError(String),
+    FailSafeTriggered,
     #[cfg(not(feature = "lite"))]
     ImageError(ImageProcessingError),
     ImgError(String),
@@ -29,6 +30,10 @@ impl fmt::Display for AutoGuiError {
             AutoGuiError::IoError(err) => write!(f, "IO Error: {}", err),
             AutoGuiError::AliasError(err) => write!(f, "Alias Error: {}", err),
             AutoGuiError::OutOfBoundsError(err) => write!(f, "Out of bounds error: {}", err),
+            AutoGuiError::FailSafeTriggered => write!(
+                f,
+                "Fail safe triggered: mouse and keyboard input stopped until fail safe is reset"
+            ),
             #[cfg(not(feature = "lite"))]
             AutoGuiError::ImageError(err) => write!(f, "Image Error: {}", err),
             AutoGuiError::ImgError(err) => write!(f, "Image Error: {}", err),
@@ -182,6 +182,33 @@ where
     sub_image
 }
 
+#[cfg(not(feature = "lite"))]
+/// Edge map of image, as Sobel gradient magnitude. Gradient magnitude does not depend on
+/// whether edge goes from dark to light or the other way, so light and dark variants of
+/// the same interface give similar edge maps. Pixels outside of image repeat border pixels
+pub fn edge_map(image: &ImageBuffer<Luma<u8>, Vec<u8>>) -> ImageBuffer<Luma<u8>, Vec<u8>> {
+    let (width, height) = image.dimensions();
+    let pixel = |x: i64, y: i64| {
+        let x = x.clamp(0, width as i64 - 1) as u32;
+        let y = y.clamp(0, height as i64 - 1) as u32;
+        image.get_pixel(x, y)[0] as i32
+    };
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let (x, y) = (x as i64, y as i64);
+        let gradient_x = pixel(x + 1, y - 1) + 2 * pixel(x + 1, y) + pixel(x + 1, y + 1)
+            - pixel(x - 1, y - 1)
+            - 2 * pixel(x - 1, y)
+            - pixel(x - 1, y + 1);
+        let gradient_y = pixel(x - 1, y + 1) + 2 * pixel(x, y + 1) + pixel(x + 1, y + 1)
+            - pixel(x - 1, y - 1)
+            - 2 * pixel(x, y - 1)
+            - pixel(x + 1, y - 1);
+        // sobel kernel sums 4 pixel differences, so magnitude is scaled back to pixel range
+        let magnitude = ((gradient_x.pow(2) + gradient_y.pow(2)) as f32).sqrt() / 4.0;
+        Luma([magnitude.min(255.0) as u8])
+    })
+}
+
 #[cfg(not(feature = "lite"))]
 ///Converts Imagebuffer to Vector format
 pub fn imagebuffer_to_vec<T: Copy + Primitive + 'static>(
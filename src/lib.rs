@@ -17,8 +17,12 @@ pub mod core;
 #[cfg(any(test, feature = "dev"))]
 pub mod data;
 
+#[cfg(feature = "async")]
+pub mod async_gui;
 pub mod errors;
 pub mod imgtools;
+#[cfg(not(feature = "lite"))]
+pub mod matcher;
 mod rustautogui_impl;
 
 #[cfg(not(feature = "lite"))]
@@ -30,6 +34,7 @@ use std::{collections::HashMap, env};
 #[cfg(not(feature = "lite"))]
 use core::template_match;
 use core::{
+    failsafe::FailSafe,
     keyboard::Keyboard,
     mouse::{mouse_position, Mouse, MouseScroll},
     screen::Screen,
@@ -37,28 +42,64 @@ use core::{
 
 // opencl stuff
 #[cfg(feature = "opencl")]
-use crate::data::{DevicesInfo, OpenClData};
+use crate::data::OpenClData;
 #[cfg(feature = "opencl")]
-use ocl::{enums, Buffer, Context, Kernel, Program, Queue};
+use ocl::{Buffer, Context, Kernel, Program, Queue};
 
+#[cfg(feature = "async")]
+pub use async_gui::AsyncRustAutoGui;
+pub use core::failsafe::ScreenCorner;
 pub use core::mouse::mouse_position::print_mouse_position;
 pub use core::mouse::MouseClick;
+pub use core::polling::PollingPolicy;
+#[cfg(not(feature = "lite"))]
+pub use core::template_match::auto_mode::AutoModeChoice;
+#[cfg(not(feature = "lite"))]
+pub use core::template_match::features::FeatureMatch;
+#[cfg(not(feature = "lite"))]
+pub use core::template_match::pyramid::PyramidSearch;
+#[cfg(not(feature = "lite"))]
+pub use core::template_match::rotation::RotationRange;
+#[cfg(not(feature = "lite"))]
+pub use core::template_match::FindStrategy;
+#[cfg(feature = "opencl")]
+pub use data::opencl::{DevicesInfo, OclDeviceSelector, OclDeviceType};
+#[cfg(not(feature = "lite"))]
+pub use matcher::Matcher;
+#[cfg(not(feature = "lite"))]
+pub use rustautogui_impl::template_match_impl::frame_impl::Frame;
 
 #[cfg(not(feature = "lite"))]
 const DEFAULT_ALIAS: &str = "default_rsgui_!#123#!";
 #[cfg(not(feature = "lite"))]
 const DEFAULT_BCKP_ALIAS: &str = "bckp_tmpl_.#!123!#.";
-
-/// Matchmode Segmented correlation and Fourier transform correlation
+// separates alias and angle in keys of stored rotated variants
+#[cfg(not(feature = "lite"))]
+const ROTATED_ALIAS: &str = "_rot_.#!123!#.";
+// temporary alias of template prepared for OpenCL benchmark of auto match mode
+#[cfg(feature = "opencl")]
+const AUTO_BENCHMARK_ALIAS: &str = "auto_bench_.#!123!#.";
+
+/// Matchmode Segmented correlation and Fourier transform correlation.
+/// Edge runs Fourier transform correlation on edge maps of template and screen,
+/// so template captured in light theme is also found in dark theme.
+/// Features matches corner keypoints, so template is found when scaled or partially covered.
+/// Auto picks the fastest of Segmented, FFT and OpenCL modes when template is prepared.
+/// DenseOcl correlates every template pixel on OpenCL device, for templates that segment poorly
 #[derive(PartialEq, Debug)]
 #[cfg(not(feature = "lite"))]
 pub enum MatchMode {
     Segmented,
     FFT,
+    Edge,
+    Features,
+    Auto,
     #[cfg(feature = "opencl")]
     SegmentedOcl,
     #[cfg(feature = "opencl")]
     SegmentedOclV2,
+    #[cfg(feature = "opencl")]
+    DenseOcl,
 }
 #[cfg(not(feature = "lite"))]
 impl Clone for MatchMode {
@@ -66,10 +107,15 @@ impl Clone for MatchMode {
         match self {
             MatchMode::Segmented => MatchMode::Segmented,
             MatchMode::FFT => MatchMode::FFT,
+            MatchMode::Edge => MatchMode::Edge,
+            MatchMode::Features => MatchMode::Features,
+            MatchMode::Auto => MatchMode::Auto,
             #[cfg(feature = "opencl")]
             MatchMode::SegmentedOcl => MatchMode::SegmentedOcl,
             #[cfg(feature = "opencl")]
             MatchMode::SegmentedOclV2 => MatchMode::SegmentedOclV2,
+            #[cfg(feature = "opencl")]
+            MatchMode::DenseOcl => MatchMode::DenseOcl,
         }
     }
 }
@@ -82,6 +128,15 @@ pub struct RustAutoGui {
     #[cfg(not(feature = "lite"))]
     template_data: TemplateMatchingData,
     debug: bool,
+    // folder where debug mode saves annotated search images and heatmaps
+    #[cfg(not(feature = "lite"))]
+    debug_directory: std::path::PathBuf,
+    // whether debug mode computes full correlation map for heatmap, which runs the search again
+    #[cfg(not(feature = "lite"))]
+    debug_heatmap: bool,
+    // which of the found positions find functions return
+    #[cfg(not(feature = "lite"))]
+    find_strategy: FindStrategy,
     template_height: u32,
     template_width: u32,
     keyboard: Keyboard,
@@ -89,9 +144,25 @@ pub struct RustAutoGui {
     screen: Screen,
 
     suppress_warnings: bool,
-
+    polling_policy: PollingPolicy,
+    failsafe: FailSafe,
+    // sleep after every mouse and keyboard action
+    pause: std::time::Duration,
+    // when true, mouse and keyboard actions are only logged
+    dry_run: bool,
+    // position mouse would be at if dry run actions were executed
+    dry_run_cursor: std::sync::Mutex<Option<(i32, i32)>>,
+
+    // when set, template search captures this window instead of the whole screen
+    #[cfg(all(target_os = "linux", not(feature = "lite")))]
+    capture_window: Option<u64>,
+
+    // None when no OpenCL platform was found, so only CPU match modes can be used
     #[cfg(feature = "opencl")]
-    opencl_data: OpenClData,
+    opencl_data: Option<OpenClData>,
+    // devices of all OpenCL platforms, kept even when setup of selected device failed
+    #[cfg(feature = "opencl")]
+    ocl_devices: Vec<DevicesInfo>,
 }
 impl RustAutoGui {
     /// initiation of screen, keyboard and mouse that are assigned to new rustautogui struct.
@@ -107,11 +178,11 @@ impl RustAutoGui {
         let mouse_struct: Mouse = Mouse::new();
 
         #[cfg(target_os = "linux")]
-        let screen = Screen::new();
+        let screen = Screen::new()?;
         #[cfg(target_os = "linux")]
-        let keyboard = Keyboard::new(screen.display);
+        let keyboard = Keyboard::new(screen.display.clone());
         #[cfg(target_os = "linux")]
-        let mouse_struct: Mouse = Mouse::new(screen.display, screen.root_window);
+        let mouse_struct: Mouse = Mouse::new(screen.display.clone(), screen.root_window);
 
         // check for env variable to suppress warnings, otherwise set default false value
         let suppress_warnings = env::var("RUSTAUTOGUI_SUPPRESS_WARNINGS")
@@ -119,14 +190,20 @@ impl RustAutoGui {
             .unwrap_or(false); // Default: warnings are NOT suppressed
 
         // OCL INITIALIZATION
+        // device can be selected with env variable, otherwise the best one is used
         #[cfg(feature = "opencl")]
-        let opencl_data = Self::setup_opencl(None)?;
+        let ocl_devices = Self::list_ocl_devices();
+        #[cfg(feature = "opencl")]
+        let opencl_data = Self::setup_opencl_from_env(ocl_devices.clone(), suppress_warnings);
 
         #[cfg(not(feature = "lite"))]
         let template_match_data = TemplateMatchingData {
             template: None,
             prepared_data: PreparedData::None,
             prepared_data_stored: HashMap::new(),
+            rotated_aliases: HashMap::new(),
+            pyramids: HashMap::new(),
+            auto_choices: HashMap::new(),
             match_mode: None,
             region: (0, 0, 0, 0),
             alias_used: DEFAULT_ALIAS.to_string(),
@@ -136,108 +213,172 @@ impl RustAutoGui {
             #[cfg(not(feature = "lite"))]
             template_data: template_match_data,
             debug,
+            #[cfg(not(feature = "lite"))]
+            debug_directory: std::path::PathBuf::from("debug"),
+            #[cfg(not(feature = "lite"))]
+            debug_heatmap: false,
+            #[cfg(not(feature = "lite"))]
+            find_strategy: FindStrategy::default(),
             template_width: 0,
             template_height: 0,
             keyboard,
             mouse: mouse_struct,
             screen,
             suppress_warnings,
+            polling_policy: PollingPolicy::default(),
+            failsafe: FailSafe::new(),
+            pause: std::time::Duration::ZERO,
+            dry_run: false,
+            dry_run_cursor: std::sync::Mutex::new(None),
+
+            #[cfg(all(target_os = "linux", not(feature = "lite")))]
+            capture_window: None,
 
             #[cfg(feature = "opencl")]
             opencl_data: opencl_data,
+            #[cfg(feature = "opencl")]
+            ocl_devices,
         })
     }
 
     #[cfg(feature = "dev")]
     pub fn dev_setup_opencl(device_id: Option<u32>) -> Result<OpenClData, AutoGuiError> {
-        Self::setup_opencl(device_id)
+        let selector = match device_id {
+            Some(index) => OclDeviceSelector::Index(index),
+            None => OclDeviceSelector::Best,
+        };
+        Self::setup_opencl(&selector)?.ok_or(ocl::Error::from("No OpenCL platform found").into())
     }
 
+    /// Lists devices of all OpenCL platforms and prepares selected one.
+    /// None when there is no OpenCL platform or device
     #[cfg(feature = "opencl")]
-    fn setup_opencl(device_id: Option<u32>) -> Result<OpenClData, AutoGuiError> {
-        let context = Context::builder().build()?;
-        let available_devices = context.devices();
-        let device_count = available_devices.len();
+    fn setup_opencl(selector: &OclDeviceSelector) -> Result<Option<OpenClData>, AutoGuiError> {
+        let device_list = Self::list_ocl_devices();
+        if device_list.is_empty() {
+            return Ok(None);
+        }
+        let selected_device = selector.select(&device_list)?;
+        Self::prepare_ocl_device(device_list, selected_device).map(Some)
+    }
+
+    /// OpenCL data for RustAutoGui::new(), of device selected with RUSTAUTOGUI_OCL_DEVICE or the best one.
+    /// OpenCL is optional, so this never fails. Selector that is empty, not valid unicode or fits
+    /// no device falls back to the best device, and failed device setup falls back to CPU match modes,
+    /// both with a warning
+    #[cfg(feature = "opencl")]
+    fn setup_opencl_from_env(
+        device_list: Vec<DevicesInfo>,
+        suppress_warnings: bool,
+    ) -> Option<OpenClData> {
+        let warn = |message: String| {
+            if !suppress_warnings {
+                eprintln!("Warning: {}", message);
+            }
+        };
+        if device_list.is_empty() {
+            warn("no OpenCL platform found, OpenCL match modes are not available".to_string());
+            return None;
+        }
+        let selector = match OclDeviceSelector::from_env() {
+            Ok(selector) => selector.unwrap_or_default(),
+            Err(err) => {
+                warn(format!("{}, using the best OpenCL device", err));
+                OclDeviceSelector::Best
+            }
+        };
+        let selected_device = match selector.select(&device_list) {
+            Ok(selected_device) => selected_device,
+            Err(err) => {
+                warn(format!(
+                    "{} selects no OpenCL device ({}), using the best OpenCL device",
+                    OCL_DEVICE_ENV, err
+                ));
+                // best selector only fails on empty device list
+                OclDeviceSelector::Best.select(&device_list).ok()?
+            }
+        };
+        match Self::prepare_ocl_device(device_list, selected_device) {
+            Ok(opencl_data) => Some(opencl_data),
+            Err(err) => {
+                warn(format!(
+                    "OpenCL device setup failed ({}), OpenCL match modes are not available",
+                    err
+                ));
+                None
+            }
+        }
+    }
+
+    /// Devices of all OpenCL platforms. Platforms and devices that fail to be queried are skipped,
+    /// so one broken driver does not hide devices of other platforms
+    #[cfg(feature = "opencl")]
+    fn list_ocl_devices() -> Vec<DevicesInfo> {
+        let platforms = match ocl::core::get_platform_ids() {
+            Ok(platforms) => ocl::Platform::list_from_core(platforms),
+            Err(_) => return Vec::new(),
+        };
         let mut device_list: Vec<DevicesInfo> = Vec::new();
-        let mut highest_score = 0;
-        let mut best_device_index = 0;
-        let mut max_workgroup_size = 0;
-        for (i, device) in available_devices.into_iter().enumerate() {
-            let device_type = device.info(enums::DeviceInfo::Type)?.to_string();
-            let workgroup_size: u32 = device
-                .info(enums::DeviceInfo::MaxWorkGroupSize)?
-                .to_string()
-                .parse()
-                .map_err(|_| AutoGuiError::OSFailure("Failed to read GPU data".to_string()))?;
-            let global_mem: u64 = device
-                .info(enums::DeviceInfo::GlobalMemSize)?
-                .to_string()
-                .parse()
-                .map_err(|_| AutoGuiError::OSFailure("Failed to read GPU data".to_string()))?;
-            let compute_units: u32 = device
-                .info(enums::DeviceInfo::MaxComputeUnits)?
-                .to_string()
-                .parse()
-                .map_err(|_| AutoGuiError::OSFailure("Failed to read GPU data".to_string()))?;
-
-            let clock_frequency = device
-                .info(enums::DeviceInfo::MaxClockFrequency)?
-                .to_string()
-                .parse()
-                .map_err(|_| AutoGuiError::OSFailure("Failed to read GPU data".to_string()))?;
-            let device_vendor = device.info(enums::DeviceInfo::Vendor)?.to_string();
-            let device_name = device.info(enums::DeviceInfo::Name)?.to_string();
-            let global_mem_gb = global_mem / 1_048_576;
-            let score = global_mem_gb as u32 * 2 + compute_units * 10 + clock_frequency;
-            let gui_device = DevicesInfo::new(
-                device,
-                i as u32,
-                global_mem_gb as u32,
-                clock_frequency,
-                compute_units,
-                device_vendor,
-                device_name,
-                score,
-            );
-
-            device_list.push(gui_device);
-            match device_id {
-                Some(x) => {
-                    if x as usize > device_count {
-                        return Err(ocl::Error::from("No device found for the given index"))?;
-                    }
-                    if i == x as usize {
-                        highest_score = score;
-                        best_device_index = i;
-                        max_workgroup_size = workgroup_size;
-                    }
-                }
-                None => {
-                    if score >= highest_score && device_type.contains("GPU") {
-                        highest_score = score;
-                        best_device_index = i;
-                        max_workgroup_size = workgroup_size;
-                    }
+        for platform in platforms {
+            let Ok(devices) = ocl::Device::list_all(platform) else {
+                continue;
+            };
+            for device in devices {
+                let index = device_list.len() as u32;
+                if let Ok(device_info) = DevicesInfo::new(device, platform, index) {
+                    device_list.push(device_info);
                 }
             }
         }
-        let used_device = context.devices()[best_device_index as usize];
-        let queue = Queue::new(&context, used_device, None)?;
+        device_list
+    }
+
+    /// creates context, queue and program for device at selected position of device list
+    #[cfg(feature = "opencl")]
+    fn prepare_ocl_device(
+        device_list: Vec<DevicesInfo>,
+        selected_device: usize,
+    ) -> Result<OpenClData, AutoGuiError> {
+        let used_device = &device_list[selected_device];
+        let context = Context::builder()
+            .platform(used_device.platform)
+            .devices(used_device.device)
+            .build()?;
+        let queue = Queue::new(&context, used_device.device, None)?;
         let program_source = template_match::opencl_kernel::OCL_KERNEL;
         let program = Program::builder().src(program_source).build(&context)?;
 
         let opencl_data = OpenClData {
-            device_list: device_list,
+            ocl_workgroup_size: used_device.max_workgroup_size,
+            device_list,
+            selected_device,
             ocl_program: program,
             ocl_context: context,
             ocl_queue: queue,
             ocl_buffer_storage: HashMap::new(),
-            ocl_kernel_storage: HashMap::new(),
-            ocl_workgroup_size: max_workgroup_size,
+            ocl_kernel_storage: std::sync::Mutex::new(HashMap::new()),
+            ocl_dense_storage: std::sync::Mutex::new(HashMap::new()),
         };
         Ok(opencl_data)
     }
 
+    /// OpenCL data of selected device, error when OpenCL is not available
+    #[cfg(feature = "opencl")]
+    fn opencl(&self) -> Result<&OpenClData, AutoGuiError> {
+        self.opencl_data.as_ref().ok_or(
+            ocl::Error::from("No OpenCL platform found, OpenCL match modes are not available")
+                .into(),
+        )
+    }
+
+    #[cfg(feature = "opencl")]
+    fn opencl_mut(&mut self) -> Result<&mut OpenClData, AutoGuiError> {
+        self.opencl_data.as_mut().ok_or(
+            ocl::Error::from("No OpenCL platform found, OpenCL match modes are not available")
+                .into(),
+        )
+    }
+
     /// set true to turn off warnings.
     pub fn set_suppress_warnings(&mut self, suppress: bool) {
         self.suppress_warnings = suppress;
@@ -248,6 +389,30 @@ impl RustAutoGui {
         self.debug = state;
     }
 
+    /// Sets folder where debug mode saves annotated search images and correlation heatmaps.
+    /// Default is "debug" folder in working directory. Folder is created if it does not exist
+    #[cfg(not(feature = "lite"))]
+    pub fn set_debug_directory<P: AsRef<std::path::Path>>(&mut self, path: P) {
+        self.debug_directory = path.as_ref().to_path_buf();
+    }
+
+    /// When true, debug mode also saves correlation heatmap of whole searched region and draws
+    /// best candidates above and below threshold. Computing the heatmap runs correlation at every
+    /// position, which doubles the search time. Default is false, where found locations are drawn,
+    /// or the single best match below threshold when nothing was found
+    #[cfg(not(feature = "lite"))]
+    pub fn set_debug_heatmap(&mut self, heatmap: bool) {
+        self.debug_heatmap = heatmap;
+    }
+
+    /// Sets which of the found positions find functions return: all of them, only the best one,
+    /// or the first one found, which lets segmented search stop without scoring whole region.
+    /// Default is FindStrategy::All
+    #[cfg(not(feature = "lite"))]
+    pub fn set_find_strategy(&mut self, strategy: FindStrategy) {
+        self.find_strategy = strategy;
+    }
+
     /// returns screen width and height
     pub fn get_screen_size(&self) -> (i32, i32) {
         self.screen.dimension()
@@ -258,22 +423,42 @@ impl RustAutoGui {
         self.screen.grab_screenshot(path)?;
         Ok(())
     }
+    /// Returns OpenCL devices of all platforms, also when setup of selected device failed.
+    /// Empty when no OpenCL platform was found
     #[cfg(feature = "opencl")]
-    pub fn list_devices(&self) {
-        for (i, item) in (&self.opencl_data.device_list).iter().enumerate() {
-            println!("Device {i}:");
-            println!("{}", item.print_device());
-            println!("\n");
-        }
+    pub fn list_devices(&self) -> &[DevicesInfo] {
+        &self.ocl_devices
     }
+
+    /// Returns OpenCL device used by OpenCL match modes, None when no OpenCL platform was found
+    #[cfg(feature = "opencl")]
+    pub fn selected_ocl_device(&self) -> Option<&DevicesInfo> {
+        self.opencl_data
+            .as_ref()
+            .map(|opencl_data| &opencl_data.device_list[opencl_data.selected_device])
+    }
+
+    /// Changes OpenCL device to the one at index of list_devices()
     #[cfg(feature = "opencl")]
     pub fn change_ocl_device(&mut self, device_index: u32) -> Result<(), AutoGuiError> {
-        let new_opencl_data = Self::setup_opencl(Some(device_index))?;
-        self.opencl_data = new_opencl_data;
+        self.select_ocl_device(OclDeviceSelector::Index(device_index))
+    }
+
+    /// Changes OpenCL device to the one picked by selector, for instance by name regex, vendor or type.
+    /// Returns error when no device fits
+    #[cfg(feature = "opencl")]
+    pub fn select_ocl_device(&mut self, selector: OclDeviceSelector) -> Result<(), AutoGuiError> {
+        let new_opencl_data =
+            Self::setup_opencl(&selector)?.ok_or(ocl::Error::from("No OpenCL platform found"))?;
+        self.ocl_devices = new_opencl_data.device_list.clone();
+        self.opencl_data = Some(new_opencl_data);
 
         self.template_data.template = None;
         self.template_data.prepared_data = PreparedData::None;
         self.template_data.prepared_data_stored = HashMap::new();
+        self.template_data.rotated_aliases = HashMap::new();
+        self.template_data.pyramids = HashMap::new();
+        self.template_data.auto_choices = HashMap::new();
         self.template_width = 0;
         self.template_height = 0;
         self.template_data.alias_used = DEFAULT_ALIAS.to_string();
@@ -327,13 +512,6 @@ impl RustAutoGui {
     }
 }
 
-#[cfg(target_os = "linux")]
-impl Drop for RustAutoGui {
-    fn drop(&mut self) {
-        self.screen.destroy();
-    }
-}
-
 #[cfg(not(feature = "lite"))]
 #[cfg(target_os = "windows")]
 impl Drop for RustAutoGui {
@@ -0,0 +1,434 @@
+/*
+Template matching on arbitrary images, without capturing the screen.
+Runs the same Segmented, FFT, Edge and Features algorithms used by RustAutoGui find functions, so it can be
+used on saved screenshots, video frames or images from remote machines, without display server.
+*/
+#![allow(clippy::type_complexity)]
+
+use crate::core::template_match;
+use crate::data::{FFTData, PreparedData, PyramidData};
+use crate::{
+    imgtools, AutoGuiError, AutoModeChoice, FeatureMatch, FindStrategy, ImageProcessingError,
+    MatchMode, PyramidSearch,
+};
+use image::{ImageBuffer, Luma, Pixel, Primitive};
+use rustfft::num_traits::ToPrimitive;
+use std::sync::{Arc, Mutex};
+
+/// Prepared template which can be searched for in any image.
+/// OpenCL match modes are not supported
+pub struct Matcher {
+    template: ImageBuffer<Luma<u8>, Vec<u8>>,
+    match_mode: MatchMode,
+    prepared_data: PreparedData,
+    // FFT preparation depends on searched image size, so it is done for the last searched size.
+    // Data is shared out of the lock, so concurrent searches do not wait for each other
+    fft_data: Mutex<Option<((u32, u32), Arc<FFTData>)>>,
+    // coarse to fine search settings, with data prepared for the last searched size
+    pyramid: Option<PyramidSearch>,
+    pyramid_data: Mutex<Option<((u32, u32), Arc<PyramidData>)>>,
+    // mode picked when prepared with MatchMode::Auto
+    auto_choice: Option<AutoModeChoice>,
+}
+
+impl Matcher {
+    /// prepares template from imagebuffer, works only on types RGB/RGBA/Luma
+    pub fn prepare<P, T>(
+        template: &ImageBuffer<P, Vec<T>>,
+        match_mode: MatchMode,
+    ) -> Result<Self, AutoGuiError>
+    where
+        P: Pixel<Subpixel = T> + 'static,
+        T: Primitive + ToPrimitive + 'static,
+    {
+        Self::prepare_luma(to_luma(template)?, match_mode, None, None)
+    }
+
+    /// same as prepare, with user defined threshold for segmented match mode.
+    /// Threshold is ignored for FFT match mode
+    pub fn prepare_custom<P, T>(
+        template: &ImageBuffer<P, Vec<T>>,
+        match_mode: MatchMode,
+        threshold: f32,
+    ) -> Result<Self, AutoGuiError>
+    where
+        P: Pixel<Subpixel = T> + 'static,
+        T: Primitive + ToPrimitive + 'static,
+    {
+        Self::prepare_luma(to_luma(template)?, match_mode, Some(threshold), None)
+    }
+
+    /// loads template from file and prepares it
+    pub fn prepare_from_file(path: &str, match_mode: MatchMode) -> Result<Self, AutoGuiError> {
+        Self::prepare_luma(imgtools::load_image_bw(path)?, match_mode, None, None)
+    }
+
+    /// Prepares template for searching images of provided size. Makes a difference for
+    /// FFT match mode, where preparation depends on searched image size and is otherwise
+    /// done on first search, and for Auto match mode, which picks mode for that size
+    pub fn prepare_for_size<P, T>(
+        template: &ImageBuffer<P, Vec<T>>,
+        match_mode: MatchMode,
+        image_width: u32,
+        image_height: u32,
+    ) -> Result<Self, AutoGuiError>
+    where
+        P: Pixel<Subpixel = T> + 'static,
+        T: Primitive + ToPrimitive + 'static,
+    {
+        let matcher = Self::prepare_luma(
+            to_luma(template)?,
+            match_mode,
+            None,
+            Some((image_width, image_height)),
+        )?;
+        if matches!(matcher.match_mode, MatchMode::FFT | MatchMode::Edge) {
+            matcher.fft_data_for((image_width, image_height));
+        }
+        Ok(matcher)
+    }
+
+    /// Prepares template searched coarse to fine with Segmented or FFT match mode. Candidates are
+    /// searched in downscaled image and verified at full resolution only around candidates.
+    /// find_best_in always searches the whole image at full resolution
+    pub fn prepare_pyramid<P, T>(
+        template: &ImageBuffer<P, Vec<T>>,
+        match_mode: MatchMode,
+        pyramid: PyramidSearch,
+    ) -> Result<Self, AutoGuiError>
+    where
+        P: Pixel<Subpixel = T> + 'static,
+        T: Primitive + ToPrimitive + 'static,
+    {
+        let mut matcher = Self::prepare(template, match_mode)?;
+        // checks that template and match mode can be searched with pyramid
+        let (template_width, template_height) = matcher.template.dimensions();
+        template_match::pyramid::prepare_template_picture(
+            &matcher.template,
+            &matcher.match_mode,
+            template_width,
+            template_height,
+            pyramid,
+            None,
+        )?;
+        matcher.pyramid = Some(pyramid);
+        Ok(matcher)
+    }
+
+    /// Auto match mode is resolved for provided image size, or without it by estimate for full HD image
+    fn prepare_luma(
+        template: ImageBuffer<Luma<u8>, Vec<u8>>,
+        match_mode: MatchMode,
+        user_threshold: Option<f32>,
+        image_size: Option<(u32, u32)>,
+    ) -> Result<Self, AutoGuiError> {
+        let auto_choice = match match_mode {
+            MatchMode::Auto => Some(template_match::auto_mode::select_match_mode(
+                &template,
+                image_size,
+                user_threshold,
+                false,
+            )),
+            _ => None,
+        };
+        let match_mode = match &auto_choice {
+            Some(choice) => choice.match_mode.clone(),
+            None => match_mode,
+        };
+        let prepared_data = match match_mode {
+            MatchMode::Segmented => {
+                let prepared_data = template_match::segmented_ncc::prepare_template_picture(
+                    &template,
+                    &false,
+                    user_threshold,
+                );
+                if let PreparedData::Segmented(ref segmented) = prepared_data {
+                    // mostly happens due to using too complex image with small max segments value
+                    if (segmented.template_segments_fast.len() == 1)
+                        | (segmented.template_segments_slow.len() == 1)
+                    {
+                        Err(ImageProcessingError::new("Error in creating segmented template image. To resolve: either use FFT matching mode or use smaller template image"))?;
+                    }
+                }
+                prepared_data
+            }
+            MatchMode::Features => PreparedData::Features(
+                template_match::features::prepare_template_picture(&template)?,
+            ),
+            MatchMode::FFT | MatchMode::Edge => PreparedData::None,
+            MatchMode::Auto => Err(ImageProcessingError::new(
+                "Auto match mode was not resolved",
+            ))?,
+            #[cfg(feature = "opencl")]
+            MatchMode::SegmentedOcl | MatchMode::SegmentedOclV2 | MatchMode::DenseOcl => Err(
+                ImageProcessingError::new("OpenCL match modes are not supported by Matcher"),
+            )?,
+        };
+        // edge mode searches edge map of template in edge map of image
+        let template = match match_mode {
+            MatchMode::Edge => imgtools::edge_map(&template),
+            _ => template,
+        };
+        Ok(Self {
+            template,
+            match_mode,
+            prepared_data,
+            fft_data: Mutex::new(None),
+            pyramid: None,
+            pyramid_data: Mutex::new(None),
+            auto_choice,
+        })
+    }
+
+    /// Searches for template in provided image, works only on types RGB/RGBA/Luma.
+    /// Returns positions of template middle, sorted from highest correlation, or None if not found
+    pub fn find_in<P, T>(
+        &self,
+        image: &ImageBuffer<P, Vec<T>>,
+        precision: f32,
+    ) -> Result<Option<Vec<(u32, u32, f32)>>, AutoGuiError>
+    where
+        P: Pixel<Subpixel = T> + 'static,
+        T: Primitive + ToPrimitive + 'static,
+    {
+        self.find_in_with_strategy(image, precision, FindStrategy::All)
+    }
+
+    /// same as find_in, returning only positions selected by find strategy.
+    /// FindStrategy::First stops segmented search as soon as any position is found
+    pub fn find_in_with_strategy<P, T>(
+        &self,
+        image: &ImageBuffer<P, Vec<T>>,
+        precision: f32,
+        strategy: FindStrategy,
+    ) -> Result<Option<Vec<(u32, u32, f32)>>, AutoGuiError>
+    where
+        P: Pixel<Subpixel = T> + 'static,
+        T: Primitive + ToPrimitive + 'static,
+    {
+        let image = self.searched_image(image)?;
+        let (template_width, template_height) = self.template.dimensions();
+        if (image.width() < template_width) | (image.height() < template_height) {
+            return Err(AutoGuiError::OutOfBoundsError(
+                "Template size larger than searched image".to_string(),
+            ));
+        }
+        let found_locations: Vec<(u32, u32, f32)> = match self.match_mode {
+            MatchMode::Segmented | MatchMode::FFT if self.pyramid.is_some() => {
+                let data = self.pyramid_data_for(image.dimensions())?;
+                template_match::pyramid::pyramid_template_match(&image, precision, &data)
+            }
+            MatchMode::Segmented => match &self.prepared_data {
+                PreparedData::Segmented(data) if strategy == FindStrategy::First => {
+                    template_match::segmented_ncc::fast_ncc_template_match_first(
+                        &image, precision, data,
+                    )
+                    .into_iter()
+                    .collect()
+                }
+                PreparedData::Segmented(data) => {
+                    template_match::segmented_ncc::fast_ncc_template_match(
+                        &image, precision, data, &false,
+                    )
+                }
+                _ => Err(ImageProcessingError::new(
+                    "error in prepared data type. Matchmode does not match prepare data type",
+                ))?,
+            },
+            MatchMode::FFT | MatchMode::Edge => {
+                let data = self.fft_data_for(image.dimensions());
+                template_match::fft_ncc::fft_ncc(&image, precision, &data)
+                    .into_iter()
+                    .map(|(x, y, corr)| (x, y, corr as f32))
+                    .collect()
+            }
+            MatchMode::Features => {
+                return Ok(self
+                    .features_match(&image)?
+                    .filter(|found| found.score >= precision)
+                    .map(|found| vec![(found.center.0, found.center.1, found.score)]));
+            }
+            MatchMode::Auto => Err(ImageProcessingError::new(
+                "Auto match mode is resolved when template is prepared",
+            ))?,
+            #[cfg(feature = "opencl")]
+            MatchMode::SegmentedOcl | MatchMode::SegmentedOclV2 | MatchMode::DenseOcl => Err(
+                ImageProcessingError::new("OpenCL match modes are not supported by Matcher"),
+            )?,
+        };
+        if found_locations.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(
+            strategy
+                .apply(found_locations)
+                .into_iter()
+                .map(|(x, y, corr)| (x + template_width / 2, y + template_height / 2, corr))
+                .collect(),
+        ))
+    }
+
+    /// Returns position of template middle and correlation of the best match in provided image,
+    /// no matter how low its correlation is. Useful for tuning precision values
+    pub fn find_best_in<P, T>(
+        &self,
+        image: &ImageBuffer<P, Vec<T>>,
+    ) -> Result<Option<(u32, u32, f32)>, AutoGuiError>
+    where
+        P: Pixel<Subpixel = T> + 'static,
+        T: Primitive + ToPrimitive + 'static,
+    {
+        let image = self.searched_image(image)?;
+        let (template_width, template_height) = self.template.dimensions();
+        if (image.width() < template_width) | (image.height() < template_height) {
+            return Err(AutoGuiError::OutOfBoundsError(
+                "Template size larger than searched image".to_string(),
+            ));
+        }
+        let best = match self.match_mode {
+            MatchMode::Segmented => match &self.prepared_data {
+                PreparedData::Segmented(data) => {
+                    template_match::with_scratch(&data.scratch, |integral_image| {
+                        integral_image.compute(&image);
+                        template_match::segmented_ncc::fast_ncc_best_match_in_region(
+                            integral_image,
+                            (0, 0, image.width(), image.height()),
+                            data,
+                        )
+                    })
+                }
+                _ => Err(ImageProcessingError::new(
+                    "error in prepared data type. Matchmode does not match prepare data type",
+                ))?,
+            },
+            MatchMode::FFT | MatchMode::Edge => {
+                let data = self.fft_data_for(image.dimensions());
+                template_match::fft_ncc::fft_ncc_best_match(&image, &data)
+                    .map(|(x, y, corr)| (x, y, corr as f32))
+            }
+            MatchMode::Features => {
+                return Ok(self
+                    .features_match(&image)?
+                    .map(|found| (found.center.0, found.center.1, found.score)));
+            }
+            MatchMode::Auto => Err(ImageProcessingError::new(
+                "Auto match mode is resolved when template is prepared",
+            ))?,
+            #[cfg(feature = "opencl")]
+            MatchMode::SegmentedOcl | MatchMode::SegmentedOclV2 | MatchMode::DenseOcl => Err(
+                ImageProcessingError::new("OpenCL match modes are not supported by Matcher"),
+            )?,
+        };
+        Ok(best.map(|(x, y, corr)| (x + template_width / 2, y + template_height / 2, corr)))
+    }
+
+    /// Searches for template prepared with Features match mode and returns corners of the
+    /// matched area, which can be scaled, rotated or partially covered
+    pub fn find_features_in<P, T>(
+        &self,
+        image: &ImageBuffer<P, Vec<T>>,
+        precision: f32,
+    ) -> Result<Option<FeatureMatch>, AutoGuiError>
+    where
+        P: Pixel<Subpixel = T> + 'static,
+        T: Primitive + ToPrimitive + 'static,
+    {
+        Ok(self
+            .features_match(&to_luma(image)?)?
+            .filter(|found| found.score >= precision))
+    }
+
+    /// returns width and height of the prepared template
+    pub fn template_size(&self) -> (u32, u32) {
+        self.template.dimensions()
+    }
+
+    /// returns match mode template was prepared for. For MatchMode::Auto it is the picked mode
+    pub fn match_mode(&self) -> MatchMode {
+        self.match_mode.clone()
+    }
+
+    /// returns mode picked by MatchMode::Auto with estimate and benchmark timings it was based on,
+    /// or None if template was not prepared with MatchMode::Auto. OpenCL modes are not considered
+    pub fn auto_mode_choice(&self) -> Option<&AutoModeChoice> {
+        self.auto_choice.as_ref()
+    }
+
+    /// image converted to grayscale, and to edge map for edge match mode
+    fn searched_image<P, T>(
+        &self,
+        image: &ImageBuffer<P, Vec<T>>,
+    ) -> Result<ImageBuffer<Luma<u8>, Vec<u8>>, AutoGuiError>
+    where
+        P: Pixel<Subpixel = T> + 'static,
+        T: Primitive + ToPrimitive + 'static,
+    {
+        let image = to_luma(image)?;
+        Ok(match self.match_mode {
+            MatchMode::Edge => imgtools::edge_map(&image),
+            _ => image,
+        })
+    }
+
+    fn features_match(
+        &self,
+        image: &ImageBuffer<Luma<u8>, Vec<u8>>,
+    ) -> Result<Option<FeatureMatch>, AutoGuiError> {
+        match &self.prepared_data {
+            PreparedData::Features(data) => Ok(template_match::features::find_match(image, data)),
+            _ => Err(ImageProcessingError::new(
+                "Matcher is not prepared with Features match mode",
+            ))?,
+        }
+    }
+
+    /// FFT data prepared for searched image size, prepared again when size changes.
+    /// Lock is only held while data is looked up or prepared, not during the search
+    fn fft_data_for(&self, image_size: (u32, u32)) -> Arc<FFTData> {
+        let mut fft_data = self.fft_data.lock().unwrap();
+        match &*fft_data {
+            Some((size, data)) if *size == image_size => data.clone(),
+            _ => {
+                let data = Arc::new(template_match::fft_ncc::prepare_template_picture(
+                    &self.template,
+                    image_size.0,
+                    image_size.1,
+                ));
+                *fft_data = Some((image_size, data.clone()));
+                data
+            }
+        }
+    }
+
+    /// pyramid data prepared for searched image size, prepared again when size changes.
+    /// Lock is only held while data is looked up or prepared, not during the search
+    fn pyramid_data_for(&self, image_size: (u32, u32)) -> Result<Arc<PyramidData>, AutoGuiError> {
+        let mut pyramid_data = self.pyramid_data.lock().unwrap();
+        if let Some((size, data)) = &*pyramid_data {
+            if *size == image_size {
+                return Ok(data.clone());
+            }
+        }
+        let data = Arc::new(template_match::pyramid::prepare_template_picture(
+            &self.template,
+            &self.match_mode,
+            image_size.0,
+            image_size.1,
+            self.pyramid.unwrap(),
+            None,
+        )?);
+        *pyramid_data = Some((image_size, data.clone()));
+        Ok(data)
+    }
+}
+
+fn to_luma<P, T>(
+    image: &ImageBuffer<P, Vec<T>>,
+) -> Result<ImageBuffer<Luma<u8>, Vec<u8>>, AutoGuiError>
+where
+    P: Pixel<Subpixel = T> + 'static,
+    T: Primitive + ToPrimitive + 'static,
+{
+    let color_scheme = imgtools::check_imagebuffer_color_scheme(image)?;
+    imgtools::convert_t_imgbuffer_to_luma(image, color_scheme)
+}
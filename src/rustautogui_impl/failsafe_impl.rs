@@ -0,0 +1,70 @@
+use crate::{AutoGuiError, ScreenCorner};
+use std::sync::{atomic::AtomicBool, Arc};
+
+impl crate::RustAutoGui {
+    /// Selects screen corners which trigger fail safe when mouse is moved into them.
+    /// Once triggered, every mouse and keyboard function except key_up and click_up returns
+    /// AutoGuiError::FailSafeTriggered, and mouse movements in progress are aborted, until reset_failsafe is called.
+    /// Empty slice disables corner detection, which is the default
+    pub fn set_failsafe_corners(&mut self, corners: &[ScreenCorner]) {
+        self.failsafe.corners = corners.to_vec();
+    }
+
+    /// size in pixels of the square in each corner that triggers fail safe. Default is 1 pixel
+    pub fn set_failsafe_corner_size(&mut self, size: u32) {
+        self.failsafe.corner_size = size.max(1);
+    }
+
+    /// Sets key which triggers fail safe while held down, for instance "escape". Uses same key names
+    /// as keyboard_command. None disables hotkey, which is the default.
+    /// Key is not watched in background, it is only checked when mouse or keyboard action starts
+    /// and on steps of mouse movement and typing. Key has to be held until the next action,
+    /// so while waiting for an image it is noticed only once the wait ends and next action is called
+    pub fn set_failsafe_hotkey(&mut self, key: Option<&str>) -> Result<(), AutoGuiError> {
+        if let Some(key) = key {
+            // fails for unsupported keys
+            self.keyboard.is_key_pressed(key)?;
+        }
+        self.failsafe.hotkey = key.map(String::from);
+        Ok(())
+    }
+
+    /// Returns shared switch of the fail safe. Setting it to true from any thread
+    /// triggers fail safe, same as moving mouse into corner
+    pub fn failsafe_switch(&self) -> Arc<AtomicBool> {
+        self.failsafe.switch()
+    }
+
+    pub fn is_failsafe_triggered(&self) -> bool {
+        self.failsafe.is_triggered()
+    }
+
+    /// allows mouse and keyboard input again after fail safe was triggered
+    pub fn reset_failsafe(&self) {
+        self.failsafe.reset();
+    }
+
+    /// Returns error if fail safe was triggered earlier or trigger condition is met now.
+    /// Called before every mouse and keyboard action except releases, and on every step of mouse movement
+    pub(crate) fn check_failsafe(&self) -> Result<(), AutoGuiError> {
+        if self.failsafe.is_triggered() {
+            return Err(AutoGuiError::FailSafeTriggered);
+        }
+        let mut trigger = false;
+        if !self.failsafe.corners.is_empty() {
+            let position = self.system_mouse_position()?;
+            trigger |= self.failsafe.is_in_corner(
+                position,
+                (self.screen.screen_width, self.screen.screen_height),
+            );
+        }
+        if let Some(key) = &self.failsafe.hotkey {
+            trigger |= self.keyboard.is_key_pressed(key)?;
+        }
+        if trigger {
+            self.failsafe.trigger();
+            return Err(AutoGuiError::FailSafeTriggered);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+use crate::AutoGuiError;
+use std::time::Duration;
+
+impl crate::RustAutoGui {
+    /// sets pause inserted after every mouse and keyboard action. Default is no pause
+    pub fn set_pause(&mut self, pause: Duration) {
+        self.pause = pause;
+    }
+
+    /// returns pause inserted after every mouse and keyboard action
+    pub fn get_pause(&self) -> Duration {
+        self.pause
+    }
+
+    /// In dry run mode mouse and keyboard actions are printed out with their resolved coordinates
+    /// instead of being executed. Template matching still runs on the live screen.
+    /// Mouse position is tracked virtually, so relative movements resolve as if previous moves were executed
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+        *self.dry_run_cursor.lock().unwrap() = None;
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Every mouse and keyboard action goes through here. Checks fail safe, then executes action,
+    /// or only logs it in dry run mode, and sleeps the set pause afterwards.
+    /// Target is position mouse ends up at, used to track mouse in dry run mode
+    pub(crate) fn input_action<D, F>(
+        &self,
+        describe: D,
+        target: Option<(i32, i32)>,
+        action: F,
+    ) -> Result<(), AutoGuiError>
+    where
+        D: FnOnce() -> String,
+        F: FnOnce() -> Result<(), AutoGuiError>,
+    {
+        self.check_failsafe()?;
+        self.execute_action(describe, target, action)
+    }
+
+    /// Used for releasing keys and mouse buttons. Does not check fail safe,
+    /// so keys and buttons pressed before it was triggered can still be released
+    pub(crate) fn release_action<D, F>(&self, describe: D, action: F) -> Result<(), AutoGuiError>
+    where
+        D: FnOnce() -> String,
+        F: FnOnce() -> Result<(), AutoGuiError>,
+    {
+        self.execute_action(describe, None, action)
+    }
+
+    fn execute_action<D, F>(
+        &self,
+        describe: D,
+        target: Option<(i32, i32)>,
+        action: F,
+    ) -> Result<(), AutoGuiError>
+    where
+        D: FnOnce() -> String,
+        F: FnOnce() -> Result<(), AutoGuiError>,
+    {
+        if self.dry_run {
+            println!("Dry run: {}", describe());
+            if target.is_some() {
+                *self.dry_run_cursor.lock().unwrap() = target;
+            }
+        } else {
+            action()?;
+        }
+        if !self.pause.is_zero() {
+            std::thread::sleep(self.pause);
+        }
+        Ok(())
+    }
+
+    /// mouse position as seen by actions, which is virtual position in dry run mode
+    pub(crate) fn dry_run_position(&self) -> Option<(i32, i32)> {
+        if !self.dry_run {
+            return None;
+        }
+        *self.dry_run_cursor.lock().unwrap()
+    }
+}
@@ -3,18 +3,28 @@ use crate::AutoGuiError;
 impl crate::RustAutoGui {
     /// accepts string and mimics keyboard key presses for each character in string
     pub fn keyboard_input(&self, input: &str) -> Result<(), AutoGuiError> {
-        let input_string = String::from(input);
-        for letter in input_string.chars() {
-            self.keyboard.send_char(&letter)?;
-        }
-        Ok(())
+        self.input_action(
+            || format!("type {:?}", input),
+            None,
+            || {
+                for letter in input.chars() {
+                    // long inputs can be stopped midway
+                    self.check_failsafe()?;
+                    self.keyboard.send_char(&letter)?;
+                }
+                Ok(())
+            },
+        )
     }
 
     /// executes keyboard command like "return" or "escape"
     pub fn keyboard_command(&self, input: &str) -> Result<(), AutoGuiError> {
-        let input_string = String::from(input);
-        // return automatically the result of send_command function
-        self.keyboard.send_command(&input_string)
+        self.input_action(
+            || format!("press {}", input),
+            None,
+            // return automatically the result of send_command function
+            || self.keyboard.send_command(input),
+        )
     }
 
     pub fn keyboard_multi_key(
@@ -23,16 +33,29 @@ impl crate::RustAutoGui {
         input2: &str,
         input3: Option<&str>,
     ) -> Result<(), AutoGuiError> {
-        let input3 = input3.map(String::from);
-
-        self.keyboard.send_multi_key(input1, input2, input3)
+        self.input_action(
+            || match input3 {
+                Some(input3) => format!("press {} + {} + {}", input1, input2, input3),
+                None => format!("press {} + {}", input1, input2),
+            },
+            None,
+            || {
+                self.keyboard
+                    .send_multi_key(input1, input2, input3.map(String::from))
+            },
+        )
     }
 
     pub fn key_down(&self, key: &str) -> Result<(), AutoGuiError> {
-        self.keyboard.key_down(key)
+        self.input_action(
+            || format!("key down {}", key),
+            None,
+            || self.keyboard.key_down(key),
+        )
     }
 
+    /// releases key. Works after fail safe was triggered, so held keys can be released
     pub fn key_up(&self, key: &str) -> Result<(), AutoGuiError> {
-        self.keyboard.key_up(key)
+        self.release_action(|| format!("key up {}", key), || self.keyboard.key_up(key))
     }
 }
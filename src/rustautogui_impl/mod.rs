@@ -1,5 +1,10 @@
 use crate::RustAutoGui;
 
+pub mod failsafe_impl;
+pub mod input_impl;
 pub mod keyboard_impl;
 pub mod mouse_impl;
 pub mod template_match_impl;
+pub mod wait_impl;
+#[cfg(target_os = "linux")]
+pub mod window_impl;
@@ -4,7 +4,16 @@ use crate::core::mouse::{mouse_position, Mouse, MouseClick, MouseScroll};
 use crate::AutoGuiError;
 
 impl crate::RustAutoGui {
+    /// returns current mouse position. In dry run mode returns position mouse would be at
     pub fn get_mouse_position(&self) -> Result<(i32, i32), AutoGuiError> {
+        if let Some(position) = self.dry_run_position() {
+            return Ok(position);
+        }
+        self.system_mouse_position()
+    }
+
+    /// position of the real mouse, regardless of dry run
+    pub(crate) fn system_mouse_position(&self) -> Result<(i32, i32), AutoGuiError> {
         #[cfg(target_os = "linux")]
         return self.mouse.get_mouse_position();
         #[cfg(target_os = "windows")]
@@ -15,24 +24,19 @@ impl crate::RustAutoGui {
 
     /// Move mouse to x,y pixel coordinate
     pub fn move_mouse_to_pos(&self, x: u32, y: u32, moving_time: f32) -> Result<(), AutoGuiError> {
+        self.check_mouse_target(x, y)?;
+        self.execute_move(x as i32, y as i32, moving_time)
+    }
+
+    /// checks that mouse target position is on screen
+    pub(crate) fn check_mouse_target(&self, x: u32, y: u32) -> Result<(), AutoGuiError> {
         if (x as i32 > self.screen.screen_width) | (y as i32 > self.screen.screen_height) {
             return Err(AutoGuiError::OutOfBoundsError(format!(
                 "Out of bounds at positions x,y :{}, {}",
                 x, y
             )));
         }
-
-        #[cfg(target_os = "windows")]
-        {
-            Mouse::move_mouse_to_pos(x as i32, y as i32, moving_time);
-            Ok(())
-        }
-        #[cfg(target_os = "linux")]
-        return self
-            .mouse
-            .move_mouse_to_pos(x as i32, y as i32, moving_time);
-        #[cfg(target_os = "macos")]
-        return Mouse::move_mouse_to_pos(x as i32, y as i32, moving_time);
+        Ok(())
     }
 
     /// Very similar to move mouse to pos, but takes Option<x> and Option<y>, where None value just keeps the current mouse x or y value
@@ -56,15 +60,7 @@ impl crate::RustAutoGui {
             )));
         }
 
-        #[cfg(target_os = "windows")]
-        {
-            Mouse::move_mouse_to_pos(x, y, moving_time);
-            Ok(())
-        }
-        #[cfg(target_os = "linux")]
-        return self.mouse.move_mouse_to_pos(x, y, moving_time);
-        #[cfg(target_os = "macos")]
-        return Mouse::move_mouse_to_pos(x, y, moving_time);
+        self.execute_move(x, y, moving_time)
     }
 
     /// Move mouse in relative position. Accepts both positive and negative values, where negative X moves left, positive moves right
@@ -81,15 +77,7 @@ impl crate::RustAutoGui {
             ));
         }
 
-        #[cfg(target_os = "windows")]
-        {
-            Mouse::move_mouse_to_pos(x, y, moving_time);
-            Ok(())
-        }
-        #[cfg(target_os = "linux")]
-        return self.mouse.move_mouse_to_pos(x, y, moving_time);
-        #[cfg(target_os = "macos")]
-        return Mouse::move_mouse_to_pos(x, y, moving_time);
+        self.execute_move(x, y, moving_time)
     }
 
     /// executes left click down, move to position relative to current position, left click up
@@ -103,28 +91,7 @@ impl crate::RustAutoGui {
                 format!("Out of bounds at positions x,y :{}, {}", x, y), // "Mouse movement out of screen boundaries".to_string(),
             ));
         };
-        #[cfg(target_os = "windows")]
-        {
-            Mouse::drag_mouse(x, y, moving_time);
-
-            Ok(())
-        }
-        #[cfg(target_os = "macos")]
-        {
-            if moving_time < 0.5 && !self.suppress_warnings {
-                eprintln!("WARNING:Small moving time values may cause issues on mouse drag");
-            }
-            return Mouse::drag_mouse(x as i32, y as i32, moving_time);
-        }
-        #[cfg(target_os = "linux")]
-        {
-            if moving_time < 0.5 {
-                if !self.suppress_warnings {
-                    eprintln!("WARNING:Small moving time values may cause issues on mouse drag");
-                }
-            }
-            return self.mouse.drag_mouse(x as i32, y as i32, moving_time);
-        }
+        self.execute_drag(x, y, moving_time)
     }
 
     /// Moves to position x,y. None values maintain current position. Useful for vertical and horizontal movement
@@ -146,28 +113,7 @@ impl crate::RustAutoGui {
                 x, y
             )));
         }
-        #[cfg(target_os = "windows")]
-        {
-            Mouse::drag_mouse(x, y, moving_time);
-
-            Ok(())
-        }
-        #[cfg(target_os = "macos")]
-        {
-            if moving_time < 0.5 && !self.suppress_warnings {
-                eprintln!("WARNING:Small moving time values may cause issues on mouse drag");
-            }
-            return Mouse::drag_mouse(x as i32, y as i32, moving_time);
-        }
-        #[cfg(target_os = "linux")]
-        {
-            if moving_time < 0.5 {
-                if !self.suppress_warnings {
-                    eprintln!("WARNING:Small moving time values may cause issues on mouse drag");
-                }
-            }
-            return self.mouse.drag_mouse(x as i32, y as i32, moving_time);
-        }
+        self.execute_drag(x, y, moving_time)
     }
 
     /// moves mouse to x, y pixel coordinate
@@ -178,137 +124,180 @@ impl crate::RustAutoGui {
             ));
         }
 
-        #[cfg(target_os = "windows")]
-        {
-            Mouse::drag_mouse(x as i32, y as i32, moving_time);
-
-            Ok(())
-        }
-        #[cfg(target_os = "macos")]
-        {
-            if moving_time < 0.5 && !self.suppress_warnings {
-                eprintln!("WARNING:Small moving time values may cause issues on mouse drag");
-            }
-            return Mouse::drag_mouse(x as i32, y as i32, moving_time);
-        }
-        #[cfg(target_os = "linux")]
-        {
-            if moving_time < 0.5 {
-                if !self.suppress_warnings {
-                    eprintln!("WARNING:Small moving time values may cause issues on mouse drag");
-                }
-            }
-            return self.mouse.drag_mouse(x as i32, y as i32, moving_time);
-        }
+        self.execute_drag(x as i32, y as i32, moving_time)
     }
 
     /// Mouse click. Choose button Mouseclick::{LEFT,RIGHT,MIDDLE}
     pub fn click(&self, button: MouseClick) -> Result<(), AutoGuiError> {
-        #[cfg(target_os = "linux")]
-        return self.mouse.mouse_click(button);
-        #[cfg(target_os = "windows")]
-        return Ok(Mouse::mouse_click(button));
-        #[cfg(target_os = "macos")]
-        return Mouse::mouse_click(button);
+        self.input_action(
+            || format!("{:?} click", button),
+            None,
+            || {
+                #[cfg(target_os = "linux")]
+                return self.mouse.mouse_click(button);
+                #[cfg(target_os = "windows")]
+                return Ok(Mouse::mouse_click(button));
+                #[cfg(target_os = "macos")]
+                return Mouse::mouse_click(button);
+            },
+        )
     }
 
     /// executes left mouse click
     pub fn left_click(&self) -> Result<(), AutoGuiError> {
-        #[cfg(target_os = "linux")]
-        return self.mouse.mouse_click(MouseClick::LEFT);
-        #[cfg(target_os = "windows")]
-        return Ok(Mouse::mouse_click(MouseClick::LEFT));
-        #[cfg(target_os = "macos")]
-        return Mouse::mouse_click(MouseClick::LEFT);
+        self.click(MouseClick::LEFT)
     }
 
     /// executes right mouse click
     pub fn right_click(&self) -> Result<(), AutoGuiError> {
-        #[cfg(target_os = "linux")]
-        return self.mouse.mouse_click(MouseClick::RIGHT);
-        #[cfg(target_os = "macos")]
-        return Mouse::mouse_click(MouseClick::RIGHT);
-        #[cfg(target_os = "windows")]
-        return Ok(Mouse::mouse_click(MouseClick::RIGHT));
+        self.click(MouseClick::RIGHT)
     }
 
     /// executes middle mouse click
     pub fn middle_click(&self) -> Result<(), AutoGuiError> {
-        #[cfg(target_os = "linux")]
-        return self.mouse.mouse_click(MouseClick::MIDDLE);
-        #[cfg(target_os = "windows")]
-        return Ok(Mouse::mouse_click(MouseClick::MIDDLE));
-        #[cfg(target_os = "macos")]
-        return Mouse::mouse_click(MouseClick::MIDDLE);
+        self.click(MouseClick::MIDDLE)
     }
 
     /// executes double left mouse click
     pub fn double_click(&self) -> Result<(), AutoGuiError> {
-        #[cfg(target_os = "linux")]
-        {
-            self.mouse.mouse_click(MouseClick::LEFT)?;
-            self.mouse.mouse_click(MouseClick::LEFT)
-        }
-        #[cfg(target_os = "windows")]
-        {
-            Mouse::mouse_click(MouseClick::LEFT);
-            Mouse::mouse_click(MouseClick::LEFT);
-            Ok(())
-        }
-        #[cfg(target_os = "macos")]
-        Mouse::double_click()
+        self.input_action(
+            || "double click".to_string(),
+            None,
+            || {
+                #[cfg(target_os = "linux")]
+                {
+                    self.mouse.mouse_click(MouseClick::LEFT)?;
+                    self.mouse.mouse_click(MouseClick::LEFT)
+                }
+                #[cfg(target_os = "windows")]
+                {
+                    Mouse::mouse_click(MouseClick::LEFT);
+                    Mouse::mouse_click(MouseClick::LEFT);
+                    Ok(())
+                }
+                #[cfg(target_os = "macos")]
+                Mouse::double_click()
+            },
+        )
     }
 
     pub fn click_down(&self, button: MouseClick) -> Result<(), AutoGuiError> {
-        #[cfg(target_os = "linux")]
-        return self.mouse.mouse_down(button);
-        #[cfg(target_os = "macos")]
-        return Mouse::mouse_down(button);
-        #[cfg(target_os = "windows")]
-        return Ok(Mouse::mouse_down(button));
+        self.input_action(
+            || format!("{:?} click down", button),
+            None,
+            || {
+                #[cfg(target_os = "linux")]
+                return self.mouse.mouse_down(button);
+                #[cfg(target_os = "macos")]
+                return Mouse::mouse_down(button);
+                #[cfg(target_os = "windows")]
+                return Ok(Mouse::mouse_down(button));
+            },
+        )
     }
+    /// releases mouse button. Works after fail safe was triggered, so held buttons can be released
     pub fn click_up(&self, button: MouseClick) -> Result<(), AutoGuiError> {
-        #[cfg(target_os = "linux")]
-        return self.mouse.mouse_up(button);
-        #[cfg(target_os = "macos")]
-        return Mouse::mouse_up(button);
-        #[cfg(target_os = "windows")]
-        return Ok(Mouse::mouse_up(button));
+        self.release_action(
+            || format!("{:?} click up", button),
+            || {
+                #[cfg(target_os = "linux")]
+                return self.mouse.mouse_up(button);
+                #[cfg(target_os = "macos")]
+                return Mouse::mouse_up(button);
+                #[cfg(target_os = "windows")]
+                return Ok(Mouse::mouse_up(button));
+            },
+        )
     }
 
     pub fn scroll_up(&self, intensity: u32) -> Result<(), AutoGuiError> {
-        #[cfg(target_os = "linux")]
-        return Ok(self.mouse.scroll(MouseScroll::UP, intensity));
-        #[cfg(target_os = "windows")]
-        return Ok(Mouse::scroll(MouseScroll::UP, intensity));
-        #[cfg(target_os = "macos")]
-        return Mouse::scroll(MouseScroll::UP, intensity);
+        self.scroll(MouseScroll::UP, intensity)
     }
 
     pub fn scroll_down(&self, intensity: u32) -> Result<(), AutoGuiError> {
-        #[cfg(target_os = "linux")]
-        return Ok(self.mouse.scroll(MouseScroll::DOWN, intensity));
-        #[cfg(target_os = "windows")]
-        return Ok(Mouse::scroll(MouseScroll::DOWN, intensity));
-        #[cfg(target_os = "macos")]
-        return Mouse::scroll(MouseScroll::DOWN, intensity);
+        self.scroll(MouseScroll::DOWN, intensity)
     }
 
     pub fn scroll_left(&self, intensity: u32) -> Result<(), AutoGuiError> {
-        #[cfg(target_os = "linux")]
-        return Ok(self.mouse.scroll(MouseScroll::LEFT, intensity));
-        #[cfg(target_os = "windows")]
-        return Ok(Mouse::scroll(MouseScroll::LEFT, intensity));
-        #[cfg(target_os = "macos")]
-        return Mouse::scroll(MouseScroll::LEFT, intensity);
+        self.scroll(MouseScroll::LEFT, intensity)
     }
 
     pub fn scroll_right(&self, intensity: u32) -> Result<(), AutoGuiError> {
-        #[cfg(target_os = "linux")]
-        return Ok(self.mouse.scroll(MouseScroll::RIGHT, intensity));
+        self.scroll(MouseScroll::RIGHT, intensity)
+    }
+
+    fn scroll(&self, direction: MouseScroll, intensity: u32) -> Result<(), AutoGuiError> {
+        self.input_action(
+            || format!("scroll {:?} with intensity {}", direction, intensity),
+            None,
+            || {
+                #[cfg(target_os = "linux")]
+                return Ok(self.mouse.scroll(direction, intensity));
+                #[cfg(target_os = "windows")]
+                return Ok(Mouse::scroll(direction, intensity));
+                #[cfg(target_os = "macos")]
+                return Mouse::scroll(direction, intensity);
+            },
+        )
+    }
+
+    /// moves mouse to already resolved and bounds checked position
+    fn execute_move(&self, x: i32, y: i32, moving_time: f32) -> Result<(), AutoGuiError> {
+        self.input_action(
+            || format!("move mouse to {}, {} in {} seconds", x, y, moving_time),
+            Some((x, y)),
+            || {
+                #[cfg(target_os = "windows")]
+                return Mouse::move_mouse_to_pos(x, y, moving_time, &|| self.check_failsafe());
+                #[cfg(target_os = "linux")]
+                return self
+                    .mouse
+                    .move_mouse_to_pos(x, y, moving_time, &|| self.check_failsafe());
+                #[cfg(target_os = "macos")]
+                return Mouse::move_mouse_to_pos(x, y, moving_time, &|| self.check_failsafe());
+            },
+        )
+    }
+
+    #[cfg(feature = "async")]
+    /// Single instant step of movement driven from outside, like async mouse movement.
+    /// Checks fail safe, but is not logged nor followed by pause, that is done once for whole movement
+    pub(crate) fn move_mouse_step(&self, x: u32, y: u32) -> Result<(), AutoGuiError> {
+        self.check_failsafe()?;
+        if self.dry_run {
+            return Ok(());
+        }
+        let (x, y) = (
+            (x as i32).min(self.screen.screen_width),
+            (y as i32).min(self.screen.screen_height),
+        );
         #[cfg(target_os = "windows")]
-        return Ok(Mouse::scroll(MouseScroll::RIGHT, intensity));
+        return Mouse::move_mouse_to_pos(x, y, 0.0, &|| Ok(()));
+        #[cfg(target_os = "linux")]
+        return self.mouse.move_mouse_to_pos(x, y, 0.0, &|| Ok(()));
         #[cfg(target_os = "macos")]
-        return Mouse::scroll(MouseScroll::RIGHT, intensity);
+        return Mouse::move_mouse_to_pos(x, y, 0.0, &|| Ok(()));
+    }
+
+    /// drags mouse to already resolved and bounds checked position
+    fn execute_drag(&self, x: i32, y: i32, moving_time: f32) -> Result<(), AutoGuiError> {
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        if moving_time < 0.5 && !self.suppress_warnings {
+            eprintln!("WARNING:Small moving time values may cause issues on mouse drag");
+        }
+        self.input_action(
+            || format!("drag mouse to {}, {} in {} seconds", x, y, moving_time),
+            Some((x, y)),
+            || {
+                #[cfg(target_os = "windows")]
+                return Mouse::drag_mouse(x, y, moving_time, &|| self.check_failsafe());
+                #[cfg(target_os = "macos")]
+                return Mouse::drag_mouse(x, y, moving_time, &|| self.check_failsafe());
+                #[cfg(target_os = "linux")]
+                return self
+                    .mouse
+                    .drag_mouse(x, y, moving_time, &|| self.check_failsafe());
+            },
+        )
     }
 }
@@ -1,19 +1,19 @@
 #![allow(clippy::type_complexity)]
 
 #[cfg(not(feature = "lite"))]
-use crate::core::template_match;
+use crate::core::{debug_artifacts, template_match};
 #[cfg(not(feature = "lite"))]
 use crate::data::*;
 #[cfg(feature = "opencl")]
 use crate::template_match::open_cl::OclVersion;
 #[cfg(not(feature = "lite"))]
-use crate::{AutoGuiError, ImageProcessingError, MatchMode};
+use crate::{imgtools, AutoGuiError, FeatureMatch, FindStrategy, ImageProcessingError, MatchMode};
 #[cfg(not(feature = "lite"))]
-use crate::{DEFAULT_ALIAS, DEFAULT_BCKP_ALIAS};
+use crate::{PollingPolicy, DEFAULT_ALIAS, DEFAULT_BCKP_ALIAS};
 #[cfg(not(feature = "lite"))]
 use image::{ImageBuffer, Luma};
 #[cfg(not(feature = "lite"))]
-pub use std::{collections::HashMap, env, fmt, fs, path::Path, str::FromStr};
+pub use std::{collections::HashMap, env, fmt, fs, path::Path, str::FromStr, time::Duration};
 #[cfg(not(feature = "lite"))]
 impl crate::RustAutoGui {
     /// Searches for prepared template on screen.
@@ -26,45 +26,34 @@ impl crate::RustAutoGui {
         precision: f32,
     ) -> Result<Option<Vec<(u32, u32, f32)>>, AutoGuiError> {
         /// searches for image on screen and returns found locations in vector format
-        let image: ImageBuffer<Luma<u8>, Vec<u8>> = self
-            .screen
-            .grab_screen_image_grayscale(&self.template_data.region)?;
+        let image: ImageBuffer<Luma<u8>, Vec<u8>> = self.grab_search_image()?;
 
-        if self.debug {
-            let debug_path = Path::new("debug");
-            if !debug_path.exists() {
-                match fs::create_dir_all(debug_path) {
-                    Ok(_) => {
-                        println!("Created a debug folder in your root for saving segmented template images");
-                        match image.save("debug/screen_capture.png") {
-                            Ok(_) => (),
-                            Err(x) => println!("{}", x),
-                        };
-                    }
-                    Err(x) => {
-                        println!("Failed to create debug folder");
-                        println!("{}", x);
-                    }
-                };
-            }
-        };
+        // search consumes the image, so debug mode keeps a copy to draw found locations on
+        let debug_image = self.debug.then(|| image.clone());
 
         #[cfg(target_os = "macos")]
-        let locations = match self.run_macos_xcorr_with_backup(image, precision)? {
-            Some(x) => x,
-            None => return Ok(None),
-        };
+        let locations = self.run_macos_xcorr_with_backup(image, precision)?;
         #[cfg(not(target_os = "macos"))]
-        let locations = match self.run_x_corr(image, precision)? {
+        let locations = self.run_x_corr(image, precision)?;
+
+        if let Some(debug_image) = debug_image {
+            self.save_debug_artifacts(
+                &debug_image,
+                precision,
+                locations.as_deref().unwrap_or_default(),
+            );
+        }
+        let locations = match locations {
             Some(x) => x,
             None => return Ok(None),
         };
 
+        let (offset_x, offset_y) = self.match_offset();
         let locations_ajusted: Vec<(u32, u32, f32)> = locations
             .iter()
             .map(|(mut x, mut y, corr)| {
-                x = x + self.template_data.region.0 + (self.template_width / 2);
-                y = y + self.template_data.region.1 + (self.template_height / 2);
+                x = x + self.template_data.region.0 + offset_x;
+                y = y + self.template_data.region.1 + offset_y;
                 (x, y, *corr)
             })
             .collect();
@@ -72,6 +61,101 @@ impl crate::RustAutoGui {
         Ok(Some(locations_ajusted))
     }
 
+    /// offset from found location to the middle of the template. Features mode
+    /// already returns middle of the matched area, which can be scaled
+    #[cfg(not(feature = "lite"))]
+    fn match_offset(&self) -> (u32, u32) {
+        match self.template_data.match_mode {
+            Some(MatchMode::Features) => (0, 0),
+            _ => (self.template_width / 2, self.template_height / 2),
+        }
+    }
+
+    /// saves searched image annotated with found locations to debug directory, or with best match
+    /// below threshold if nothing was found. With debug heatmap on,
+    /// saves best candidates and correlation heatmap instead, computed by running correlation again.
+    /// Errors are only printed, since debug mode should not make search fail
+    #[cfg(not(feature = "lite"))]
+    fn save_debug_artifacts(
+        &self,
+        image: &ImageBuffer<Luma<u8>, Vec<u8>>,
+        precision: f32,
+        found: &[(u32, u32, f32)],
+    ) {
+        let alias = self
+            .template_data
+            .alias_used
+            .replace(DEFAULT_BCKP_ALIAS, "backup")
+            .replace(DEFAULT_ALIAS, "template");
+        if !self.debug_heatmap {
+            match debug_artifacts::save_found_artifacts(
+                &self.debug_directory,
+                &alias,
+                image,
+                &self.template_data.prepared_data,
+                found,
+            ) {
+                Ok(Some(annotated_path)) => {
+                    println!("Saved debug image {}", annotated_path.display())
+                }
+                Ok(None) => (),
+                Err(x) => println!("Failed to save debug images: {}", x),
+            }
+            return;
+        }
+        match debug_artifacts::save_match_artifacts(
+            &self.debug_directory,
+            &alias,
+            image,
+            &self.template_data.prepared_data,
+            precision,
+        ) {
+            Ok(Some((annotated_path, heatmap_path))) => println!(
+                "Saved debug images {} and {}",
+                annotated_path.display(),
+                heatmap_path.display()
+            ),
+            Ok(None) => (),
+            Err(x) => println!("Failed to save debug images: {}", x),
+        }
+    }
+
+    /// captures image which is searched. Either the screen region or, on linux,
+    /// region of the window selected with set_capture_window
+    #[cfg(not(feature = "lite"))]
+    fn grab_search_image(&mut self) -> Result<ImageBuffer<Luma<u8>, Vec<u8>>, AutoGuiError> {
+        #[cfg(target_os = "linux")]
+        if let Some(window) = self.capture_window {
+            let image = self
+                .screen
+                .grab_window_image_grayscale(window, &self.template_data.region)?;
+            // window can be smaller than prepared region, so it is cut to window size
+            if (image.width() < self.template_width) | (image.height() < self.template_height) {
+                return Err(AutoGuiError::OutOfBoundsError(
+                    "Template size larger than captured window region".to_string(),
+                ));
+            }
+            // opencl buffers are allocated for exact region size
+            #[cfg(feature = "opencl")]
+            if matches!(
+                self.template_data.match_mode,
+                Some(MatchMode::SegmentedOcl)
+                    | Some(MatchMode::SegmentedOclV2)
+                    | Some(MatchMode::DenseOcl)
+            ) && ((image.width() != self.template_data.region.2)
+                | (image.height() != self.template_data.region.3))
+            {
+                return Err(AutoGuiError::OutOfBoundsError(
+                    "OpenCL match modes require the prepared region to fit inside the captured window"
+                        .to_string(),
+                ));
+            }
+            return Ok(image);
+        }
+        self.screen
+            .grab_screen_image_grayscale(&self.template_data.region)
+    }
+
     // for macOS with retina display, two runs are made. One for resized template
     // and if not found , then second for normal sized template
     // since the function recursively calls find_stored_image_on_screen -> run_macos_xcorr_with_backup
@@ -108,31 +192,30 @@ impl crate::RustAutoGui {
         first_match
     }
     #[cfg(not(feature = "lite"))]
-    /// loops until image is found and returns found values, or until it times out
+    /// loops until image is found and returns found values, or until it times out.
+    /// Checks are spaced by polling policy set with set_polling_policy
     pub fn loop_find_image_on_screen(
         &mut self,
         precision: f32,
-        timeout: u64,
+        timeout: Duration,
     ) -> Result<Option<Vec<(u32, u32, f32)>>, AutoGuiError> {
-        if (timeout == 0) & (!self.suppress_warnings) {
-            eprintln!(
-                "Warning: setting a timeout to 0 on a loop find image initiates an infinite loop"
-            )
-        }
+        self.loop_find_image_on_screen_with_policy(precision, timeout, self.polling_policy)
+    }
 
-        let timeout_start = std::time::Instant::now();
-        loop {
-            if (timeout_start.elapsed().as_secs() > timeout) & (timeout > 0) {
-                Err(ImageProcessingError::new(
-                    "loop find image timed out. Could not find image",
-                ))?;
-            }
-            let result = self.find_image_on_screen(precision)?;
-            match result {
-                Some(r) => return Ok(Some(r)),
-                None => continue,
-            }
-        }
+    #[cfg(not(feature = "lite"))]
+    /// same as loop_find_image_on_screen, with polling policy used only for this call
+    pub fn loop_find_image_on_screen_with_policy(
+        &mut self,
+        precision: f32,
+        timeout: Duration,
+        policy: PollingPolicy,
+    ) -> Result<Option<Vec<(u32, u32, f32)>>, AutoGuiError> {
+        let found = self
+            .poll(&policy, timeout, |gui| gui.find_image_on_screen(precision))?
+            .ok_or(ImageProcessingError::new(
+                "loop find image timed out or ran out of attempts. Could not find image",
+            ))?;
+        Ok(Some(found))
     }
     #[cfg(not(feature = "lite"))]
     /// find image stored under provided alias
@@ -165,7 +248,7 @@ impl crate::RustAutoGui {
         self.template_data.region = *region;
         self.template_data.match_mode = Some(match_mode.clone());
         match prepared_data {
-            PreparedData::FFT(data) => {
+            PreparedData::FFT(data) | PreparedData::Edge(data) => {
                 self.template_width = data.template_width;
                 self.template_height = data.template_height;
             }
@@ -173,6 +256,15 @@ impl crate::RustAutoGui {
                 self.template_width = data.template_width;
                 self.template_height = data.template_height;
             }
+            PreparedData::Features(data) => {
+                self.template_width = data.template_width;
+                self.template_height = data.template_height;
+            }
+            #[cfg(feature = "opencl")]
+            PreparedData::Dense(data) => {
+                self.template_width = data.template_width;
+                self.template_height = data.template_height;
+            }
             PreparedData::None => Err(ImageProcessingError::new("No prepared data loaded"))?,
         };
         let points = self.find_image_on_screen(precision)?;
@@ -183,32 +275,87 @@ impl crate::RustAutoGui {
     }
 
     #[cfg(not(feature = "lite"))]
-    /// loops until stored image is found and returns found values, or until it times out
+    /// Returns screen position and correlation of the best match of image stored under provided alias,
+    /// no matter how low its correlation is. Useful for tuning precision values and logging near misses,
+    /// when find functions return None. Correlation is in same form as returned by find functions
+    pub fn find_best_match(
+        &mut self,
+        alias: &str,
+    ) -> Result<Option<(u32, u32, f32)>, AutoGuiError> {
+        let region = self.stored_regions_bounds(&[alias])?;
+        self.capture_frame_region(region)?.find_best_match(alias)
+    }
+
+    #[cfg(not(feature = "lite"))]
+    /// Searches for image stored with Features match mode and returns corners of the matched area
+    /// in screen coordinates. Matched area can be scaled, rotated or partially covered.
+    /// Score of the match is share of matched feature points that agree on the found position
+    pub fn find_stored_image_on_screen_features(
+        &mut self,
+        precision: f32,
+        alias: &str,
+    ) -> Result<Option<FeatureMatch>, AutoGuiError> {
+        let region = self.stored_regions_bounds(&[alias])?;
+        self.capture_frame_region(region)?
+            .find_features(precision, alias)
+    }
+
+    #[cfg(not(feature = "lite"))]
+    /// loops until stored image is found and returns found values, or until it times out.
+    /// Checks are spaced by polling policy set with set_polling_policy
     pub fn loop_find_stored_image_on_screen(
         &mut self,
         precision: f32,
-        timeout: u64,
+        timeout: Duration,
         alias: &str,
     ) -> Result<Option<Vec<(u32, u32, f32)>>, AutoGuiError> {
-        if (timeout == 0) & (!self.suppress_warnings) {
-            eprintln!(
-                "Warning: setting a timeout to 0 on a loop find image initiates an infinite loop"
-            )
-        }
-        let timeout_start = std::time::Instant::now();
-        loop {
-            if (timeout_start.elapsed().as_secs() > timeout) & (timeout > 0) {
-                Err(ImageProcessingError::new(
-                    "loop find image timed out. Could not find image",
-                ))?;
-            }
-            let result = self.find_stored_image_on_screen(precision, alias)?;
-            match result {
-                Some(r) => return Ok(Some(r)),
-                None => continue,
-            }
-        }
+        self.loop_find_stored_image_on_screen_with_policy(
+            precision,
+            timeout,
+            self.polling_policy,
+            alias,
+        )
+    }
+
+    #[cfg(not(feature = "lite"))]
+    /// same as loop_find_stored_image_on_screen, with polling policy used only for this call
+    pub fn loop_find_stored_image_on_screen_with_policy(
+        &mut self,
+        precision: f32,
+        timeout: Duration,
+        policy: PollingPolicy,
+        alias: &str,
+    ) -> Result<Option<Vec<(u32, u32, f32)>>, AutoGuiError> {
+        let found = self
+            .poll(&policy, timeout, |gui| {
+                gui.find_stored_image_on_screen(precision, alias)
+            })?
+            .ok_or(ImageProcessingError::new(
+                "loop find image timed out or ran out of attempts. Could not find image",
+            ))?;
+        Ok(Some(found))
     }
+    #[cfg(not(feature = "lite"))]
+    /// waits until image stored under provided alias is no longer found on screen,
+    /// for instance till a loading spinner or progress dialog is gone.
+    /// Returns Ok(true) if image vanished and Ok(false) if timeout passed while image was still visible.
+    /// Checks are spaced by polling policy set with set_polling_policy. Timeout of 0 waits indefinitely
+    pub fn wait_until_image_vanishes(
+        &mut self,
+        precision: f32,
+        timeout: Duration,
+        alias: &str,
+    ) -> Result<bool, AutoGuiError> {
+        let policy = self.polling_policy;
+        let vanished = self.poll(&policy, timeout, |gui| {
+            Ok(gui
+                .find_stored_image_on_screen(precision, alias)?
+                .is_none()
+                .then_some(()))
+        })?;
+        Ok(vanished.is_some())
+    }
+
     #[cfg(not(feature = "lite"))]
     /// searches for image stored under provided alias and moves mouse to position
     pub fn find_stored_image_on_screen_and_move_mouse(
@@ -240,7 +387,7 @@ impl crate::RustAutoGui {
         self.screen.screen_data.screen_region_height = region.3;
         self.template_data.match_mode = Some(match_mode.clone());
         match prepared_data {
-            PreparedData::FFT(data) => {
+            PreparedData::FFT(data) | PreparedData::Edge(data) => {
                 self.template_width = data.template_width;
                 self.template_height = data.template_height;
             }
@@ -248,6 +395,15 @@ impl crate::RustAutoGui {
                 self.template_width = data.template_width;
                 self.template_height = data.template_height;
             }
+            PreparedData::Features(data) => {
+                self.template_width = data.template_width;
+                self.template_height = data.template_height;
+            }
+            #[cfg(feature = "opencl")]
+            PreparedData::Dense(data) => {
+                self.template_width = data.template_width;
+                self.template_height = data.template_height;
+            }
             PreparedData::None => Err(ImageProcessingError::new("No prepared data loaded"))?,
         };
         let found_points = self.find_image_on_screen_and_move_mouse(precision, moving_time);
@@ -258,36 +414,47 @@ impl crate::RustAutoGui {
         found_points
     }
     #[cfg(not(feature = "lite"))]
-    /// loops until stored image is found and moves mouse
+    /// loops until stored image is found and moves mouse.
+    /// Checks are spaced by polling policy set with set_polling_policy
     pub fn loop_find_stored_image_on_screen_and_move_mouse(
         &mut self,
         precision: f32,
         moving_time: f32,
-        timeout: u64,
+        timeout: Duration,
         alias: &str,
     ) -> Result<Option<Vec<(u32, u32, f32)>>, AutoGuiError> {
-        if (timeout == 0) & (!self.suppress_warnings) {
-            eprintln!(
-                "Warning: setting a timeout to 0 on a loop find image initiates an infinite loop"
-            )
-        }
-        let timeout_start = std::time::Instant::now();
-        loop {
-            if (timeout_start.elapsed().as_secs() > timeout) & (timeout > 0) {
-                Err(ImageProcessingError::new(
-                    "loop find image timed out. Could not find image",
-                ))?;
-            }
-            let result =
-                self.find_stored_image_on_screen_and_move_mouse(precision, moving_time, alias)?;
-            match result {
-                Some(r) => return Ok(Some(r)),
-                None => continue,
-            }
-        }
+        self.loop_find_stored_image_on_screen_and_move_mouse_with_policy(
+            precision,
+            moving_time,
+            timeout,
+            self.polling_policy,
+            alias,
+        )
+    }
+
+    #[cfg(not(feature = "lite"))]
+    /// same as loop_find_stored_image_on_screen_and_move_mouse, with polling policy used only for this call
+    pub fn loop_find_stored_image_on_screen_and_move_mouse_with_policy(
+        &mut self,
+        precision: f32,
+        moving_time: f32,
+        timeout: Duration,
+        policy: PollingPolicy,
+        alias: &str,
+    ) -> Result<Option<Vec<(u32, u32, f32)>>, AutoGuiError> {
+        let found = self
+            .poll(&policy, timeout, |gui| {
+                gui.find_stored_image_on_screen_and_move_mouse(precision, moving_time, alias)
+            })?
+            .ok_or(ImageProcessingError::new(
+                "loop find image timed out or ran out of attempts. Could not find image",
+            ))?;
+        Ok(Some(found))
     }
     #[cfg(not(feature = "lite"))]
     /// executes find_image_on_screen and moves mouse to the middle of the image.
+    /// With capture window set, returned positions stay relative to the window, while mouse is moved
+    /// to the matching screen position
     pub fn find_image_on_screen_and_move_mouse(
         &mut self,
         precision: f32,
@@ -303,39 +470,53 @@ impl crate::RustAutoGui {
         };
 
         let (target_x, target_y, _) = locations[0];
+        #[cfg(target_os = "linux")]
+        let (target_x, target_y) = match self.capture_window {
+            Some(window) => self
+                .screen
+                .window_to_screen_position(window, target_x, target_y)?,
+            None => (target_x, target_y),
+        };
 
         self.move_mouse_to_pos(target_x, target_y, moving_time)?;
 
         Ok(Some(locations))
     }
     #[cfg(not(feature = "lite"))]
-    /// loops until image is found and returns found values, or until it times out
+    /// loops until image is found, moves mouse to it and returns found values, or until it times out.
+    /// Checks are spaced by polling policy set with set_polling_policy
     pub fn loop_find_image_on_screen_and_move_mouse(
         &mut self,
         precision: f32,
         moving_time: f32,
-        timeout: u64,
+        timeout: Duration,
     ) -> Result<Option<Vec<(u32, u32, f32)>>, AutoGuiError> {
-        if (timeout == 0) & (!self.suppress_warnings) {
-            eprintln!(
-                "Warning: setting a timeout to 0 on a loop find image initiates an infinite loop"
-            )
-        }
-        let timeout_start = std::time::Instant::now();
-        loop {
-            if (timeout_start.elapsed().as_secs() > timeout) & (timeout > 0) {
-                Err(ImageProcessingError::new(
-                    "loop find image timed out. Could not find image",
-                ))?;
-            }
-            let result = self.find_image_on_screen_and_move_mouse(precision, moving_time)?;
-            match result {
-                Some(e) => return Ok(Some(e)),
-                None => continue,
-            }
-        }
+        self.loop_find_image_on_screen_and_move_mouse_with_policy(
+            precision,
+            moving_time,
+            timeout,
+            self.polling_policy,
+        )
     }
 
+    #[cfg(not(feature = "lite"))]
+    /// same as loop_find_image_on_screen_and_move_mouse, with polling policy used only for this call
+    pub fn loop_find_image_on_screen_and_move_mouse_with_policy(
+        &mut self,
+        precision: f32,
+        moving_time: f32,
+        timeout: Duration,
+        policy: PollingPolicy,
+    ) -> Result<Option<Vec<(u32, u32, f32)>>, AutoGuiError> {
+        let found = self
+            .poll(&policy, timeout, |gui| {
+                gui.find_image_on_screen_and_move_mouse(precision, moving_time)
+            })?
+            .ok_or(ImageProcessingError::new(
+                "loop find image timed out or ran out of attempts. Could not find image",
+            ))?;
+        Ok(Some(found))
+    }
     #[cfg(not(feature = "lite"))]
     fn run_x_corr(
         &mut self,
@@ -343,7 +524,22 @@ impl crate::RustAutoGui {
         precision: f32,
     ) -> Result<Option<Vec<(u32, u32, f32)>>, AutoGuiError> {
         let match_mode = self.template_data.match_mode.clone().ok_or(ImageProcessingError::new("No template chosen and no template data prepared. Please run load_and_prepare_template before searching image on screen"))?;
+        let pyramid_data = self
+            .template_data
+            .pyramids
+            .get(&self.template_data.alias_used);
         let found_locations: Vec<(u32, u32, f32)> = match match_mode {
+            MatchMode::Segmented | MatchMode::FFT if pyramid_data.is_some() => {
+                println!("Running {:?} mode with pyramid search", match_mode);
+                template_match::pyramid::pyramid_template_match(
+                    &image,
+                    precision,
+                    pyramid_data.unwrap(),
+                )
+            }
+            MatchMode::Auto => Err(ImageProcessingError::new(
+                "Auto match mode is resolved when template is prepared",
+            ))?,
             MatchMode::FFT => {
                 println!("Running FFT mode");
                 let data = match &self.template_data.prepared_data {
@@ -359,6 +555,32 @@ impl crate::RustAutoGui {
                     .map(|(x, y, value)| (x, y, value as f32))
                     .collect()
             }
+            MatchMode::Edge => {
+                println!("Running Edge mode");
+                let data = match &self.template_data.prepared_data {
+                    PreparedData::Edge(data) => data,
+                    _ => Err(ImageProcessingError::new(
+                        "error in prepared data type. Matchmode does not match prepare data type",
+                    ))?,
+                };
+                template_match::fft_ncc::fft_ncc(&imgtools::edge_map(&image), precision, data)
+                    .into_iter()
+                    .map(|(x, y, value)| (x, y, value as f32))
+                    .collect()
+            }
+            MatchMode::Features => {
+                println!("Running Features mode");
+                let data = match &self.template_data.prepared_data {
+                    PreparedData::Features(data) => data,
+                    _ => Err(ImageProcessingError::new(
+                        "error in prepared data type. Matchmode does not match prepare data type",
+                    ))?,
+                };
+                template_match::features::find_match(&image, data)
+                    .filter(|found| found.score >= precision)
+                    .map(|found| vec![(found.center.0, found.center.1, found.score)])
+                    .unwrap_or_default()
+            }
             MatchMode::Segmented => {
                 println!("Running Segmented mode");
                 let data = match &self.template_data.prepared_data {
@@ -367,12 +589,21 @@ impl crate::RustAutoGui {
                         "error in prepared data type. Matchmode does not match prepare data type",
                     ))?,
                 };
-                template_match::segmented_ncc::fast_ncc_template_match(
-                    &image,
-                    precision,
-                    data,
-                    &self.debug,
-                )
+                match self.find_strategy {
+                    FindStrategy::First => {
+                        template_match::segmented_ncc::fast_ncc_template_match_first(
+                            &image, precision, data,
+                        )
+                        .into_iter()
+                        .collect()
+                    }
+                    _ => template_match::segmented_ncc::fast_ncc_template_match(
+                        &image,
+                        precision,
+                        data,
+                        &self.debug,
+                    ),
+                }
             }
             #[cfg(feature = "opencl")]
             MatchMode::SegmentedOcl => {
@@ -383,20 +614,22 @@ impl crate::RustAutoGui {
                     ))?,
                 };
                 let gpu_memory_pointers = self
-                    .opencl_data
+                    .opencl()?
                     .ocl_buffer_storage
                     .get(&self.template_data.alias_used)
                     .ok_or(ImageProcessingError::new("Error , no OCL data prepared"))?;
                 template_match::open_cl::gui_opencl_ncc_template_match(
-                    &self.opencl_data.ocl_queue,
-                    &self.opencl_data.ocl_program,
-                    self.opencl_data.ocl_workgroup_size,
-                    &self.opencl_data.ocl_kernel_storage[&self.template_data.alias_used],
+                    &self.opencl()?.ocl_queue,
+                    &self.opencl()?.ocl_program,
+                    self.opencl()?.ocl_workgroup_size,
+                    &self.opencl()?.ocl_kernel_storage.lock().unwrap()
+                        [&self.template_data.alias_used],
                     gpu_memory_pointers,
                     precision,
                     &image,
                     data,
                     OclVersion::V1,
+                    self.find_strategy.max_results(),
                 )?
             }
             #[cfg(feature = "opencl")]
@@ -408,29 +641,43 @@ impl crate::RustAutoGui {
                     ))?,
                 };
                 let gpu_memory_pointers = self
-                    .opencl_data
+                    .opencl()?
                     .ocl_buffer_storage
                     .get(&self.template_data.alias_used)
                     .ok_or(ImageProcessingError::new("Error , no OCL data prepared"))?;
                 template_match::open_cl::gui_opencl_ncc_template_match(
-                    &self.opencl_data.ocl_queue,
-                    &self.opencl_data.ocl_program,
-                    self.opencl_data.ocl_workgroup_size,
-                    &self.opencl_data.ocl_kernel_storage[&self.template_data.alias_used],
+                    &self.opencl()?.ocl_queue,
+                    &self.opencl()?.ocl_program,
+                    self.opencl()?.ocl_workgroup_size,
+                    &self.opencl()?.ocl_kernel_storage.lock().unwrap()
+                        [&self.template_data.alias_used],
                     gpu_memory_pointers,
                     precision,
                     &image,
                     data,
                     OclVersion::V2,
+                    self.find_strategy.max_results(),
                 )?
             }
+            #[cfg(feature = "opencl")]
+            MatchMode::DenseOcl => template_match::opencl_dense::gui_opencl_dense_ncc(
+                self.opencl()?
+                    .ocl_dense_storage
+                    .lock()
+                    .unwrap()
+                    .get(&self.template_data.alias_used)
+                    .ok_or(ImageProcessingError::new("Error , no OCL data prepared"))?,
+                precision,
+                &image,
+                self.find_strategy.max_results(),
+            )?,
         };
+        let found_locations = self.find_strategy.apply(found_locations);
         if !found_locations.is_empty() {
             if self.debug {
-                let x =
-                    found_locations[0].0 + (self.template_width / 2) + self.template_data.region.0;
-                let y =
-                    found_locations[0].1 + (self.template_height / 2) + self.template_data.region.1;
+                let (offset_x, offset_y) = self.match_offset();
+                let x = found_locations[0].0 + offset_x + self.template_data.region.0;
+                let y = found_locations[0].1 + offset_y + self.template_data.region.1;
                 let corr = found_locations[0].2;
                 let corrected_found_location = (x, y, corr);
 
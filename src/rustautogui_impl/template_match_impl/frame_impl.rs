@@ -0,0 +1,595 @@
+#![allow(clippy::type_complexity)]
+
+use crate::core::template_match;
+use crate::core::template_match::fft_ncc::ImageSpectrum;
+use crate::core::template_match::IntegralImage;
+use crate::data::*;
+#[cfg(feature = "opencl")]
+use crate::template_match::open_cl::OclVersion;
+#[cfg(target_os = "macos")]
+use crate::DEFAULT_BCKP_ALIAS;
+use crate::{
+    imgtools, AutoGuiError, FeatureMatch, FindStrategy, ImageProcessingError, MatchMode,
+    RustAutoGui,
+};
+use image::{ImageBuffer, Luma, Rgba};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+// searched area, whether it is searched on edge map, and padded size of the spectrum
+type SpectrumKey = ((u32, u32, u32, u32), bool, (u32, u32));
+
+/// Single screen capture that can be searched and inspected multiple times.
+/// All checks made on the same frame see the same screen state, and no additional captures are made.
+/// Created with capture_frame(). Returned positions are screen positions, same as in find functions
+pub struct Frame<'a> {
+    gui: &'a RustAutoGui,
+    image: ImageBuffer<Rgba<u8>, Vec<u8>>,
+    gray_image: ImageBuffer<Luma<u8>, Vec<u8>>,
+    // captured area of the screen, or of the window when capture window is set
+    region: (u32, u32, u32, u32),
+    // integral images are computed on first segmented search and reused afterwards
+    integrals: OnceLock<IntegralImage>,
+    // edge map is computed on first edge search and reused afterwards
+    edges: OnceLock<ImageBuffer<Luma<u8>, Vec<u8>>>,
+    // spectra of searched areas are computed on first FFT search and shared by all FFT templates
+    // prepared for the same area
+    spectra: Mutex<HashMap<SpectrumKey, Arc<ImageSpectrum>>>,
+}
+
+impl RustAutoGui {
+    /// Captures the screen once and returns a Frame which can be searched for stored images,
+    /// inspected for pixel colors or saved, all on the same capture.
+    /// On linux, captures the window selected with set_capture_window if one is set
+    pub fn capture_frame(&mut self) -> Result<Frame<'_>, AutoGuiError> {
+        #[cfg(target_os = "linux")]
+        if let Some(window) = self.capture_window {
+            let (width, height) = self.screen.window_dimension(window)?;
+            return self.capture_frame_region((0, 0, width, height));
+        }
+        let (width, height) = self.screen.dimension();
+        self.capture_frame_region((0, 0, width as u32, height as u32))
+    }
+
+    /// captures only the selected region into a frame. Stored images searched on it need to have
+    /// their regions inside of it
+    pub(crate) fn capture_frame_region(
+        &mut self,
+        region: (u32, u32, u32, u32),
+    ) -> Result<Frame<'_>, AutoGuiError> {
+        #[cfg(target_os = "linux")]
+        let (image, gray_image) = match self.capture_window {
+            Some(window) => self.screen.grab_window_images(window, &region)?,
+            None => self.screen.grab_screen_images(&region)?,
+        };
+        #[cfg(not(target_os = "linux"))]
+        let (image, gray_image) = self.screen.grab_screen_images(&region)?;
+        // window capture can cut the region to window size
+        let region = (region.0, region.1, gray_image.width(), gray_image.height());
+        Ok(Frame {
+            gui: self,
+            image,
+            gray_image,
+            region,
+            integrals: OnceLock::new(),
+            edges: OnceLock::new(),
+            spectra: Mutex::default(),
+        })
+    }
+}
+
+impl Frame<'_> {
+    /// searches for image stored under provided alias on this frame
+    pub fn find(
+        &self,
+        precision: f32,
+        alias: &str,
+    ) -> Result<Option<Vec<(u32, u32, f32)>>, AutoGuiError> {
+        Ok(self
+            .find_all(precision, &[alias])?
+            .pop()
+            .map(|(_, found)| found))
+    }
+
+    /// searches for multiple stored images on this frame and returns alias with the highest correlation
+    pub fn find_any(
+        &self,
+        precision: f32,
+        aliases: &[&str],
+    ) -> Result<Option<(String, Vec<(u32, u32, f32)>)>, AutoGuiError> {
+        let found = self.find_all(precision, aliases)?;
+        Ok(found
+            .into_iter()
+            .max_by(|a, b| a.1[0].2.partial_cmp(&b.1[0].2).unwrap()))
+    }
+
+    /// Searches for rotated variants stored under provided alias with store_template_*_rotated.
+    /// Returns angle of the variant with the highest correlation and its found locations
+    pub fn find_rotated(
+        &self,
+        precision: f32,
+        alias: &str,
+    ) -> Result<Option<(f32, Vec<(u32, u32, f32)>)>, AutoGuiError> {
+        let variants = self.gui.rotated_variants(alias)?;
+        let keys: Vec<&str> = variants.iter().map(|(_, key)| key.as_str()).collect();
+        Ok(self.find_any(precision, &keys)?.map(|(key, found)| {
+            let angle = variants
+                .iter()
+                .find(|(_, variant_key)| *variant_key == key)
+                .map(|(angle, _)| *angle)
+                .expect("found key is one of the variants");
+            (angle, found)
+        }))
+    }
+
+    /// Searches for multiple stored images on this frame, evaluating aliases in parallel.
+    /// Returns found locations for every alias that was found, in the order aliases were given.
+    /// OpenCL match modes are evaluated sequentially after the CPU ones
+    pub fn find_all(
+        &self,
+        precision: f32,
+        aliases: &[&str],
+    ) -> Result<Vec<(String, Vec<(u32, u32, f32)>)>, AutoGuiError> {
+        let stored = &self.gui.template_data.prepared_data_stored;
+        let searched_keys = self.gui.stored_keys_for_aliases(aliases)?;
+
+        // alias region relative to captured image. Cut to image size when capturing a window
+        let mut local_regions: Vec<(String, (u32, u32, u32, u32))> = Vec::new();
+        for key in searched_keys.iter().flatten() {
+            local_regions.push((key.clone(), self.local_region(key)?));
+        }
+
+        let needs_integrals = local_regions
+            .iter()
+            .any(|(key, _)| matches!(stored[key].2, MatchMode::Segmented));
+        let integral_image = if needs_integrals {
+            Some(self.integrals())
+        } else {
+            None
+        };
+
+        let pyramids = &self.gui.template_data.pyramids;
+        // image of every searched area is transformed once, no matter how many FFT templates search it
+        let spectra: HashMap<&str, Option<Arc<ImageSpectrum>>> = local_regions
+            .iter()
+            .filter(|(key, _)| matches!(stored[key].2, MatchMode::FFT | MatchMode::Edge))
+            .filter(|(key, _)| !pyramids.contains_key(key))
+            .map(|(key, local_region)| (key.as_str(), self.spectrum(key, local_region)))
+            .collect();
+
+        let gray_image = &self.gray_image;
+        let find_strategy = self.gui.find_strategy;
+        let mut results: Vec<(String, Option<Vec<(u32, u32, f32)>>)> = local_regions
+            .par_iter()
+            .map(|(key, local_region)| {
+                let (prepared_data, _, match_mode) = &stored[key];
+                let found = match (prepared_data, match_mode) {
+                    (PreparedData::Segmented(_), MatchMode::Segmented)
+                    | (PreparedData::FFT(_), MatchMode::FFT)
+                        if pyramids.contains_key(key) =>
+                    {
+                        Some(template_match::pyramid::pyramid_template_match(
+                            &cut_region(gray_image, local_region),
+                            precision,
+                            &pyramids[key],
+                        ))
+                    }
+                    (PreparedData::Segmented(data), MatchMode::Segmented) => {
+                        let integral_image =
+                            integral_image.expect("integral images computed for segmented search");
+                        // first found position is enough, rest of the region is not scored
+                        if find_strategy == FindStrategy::First {
+                            let found =
+                                template_match::segmented_ncc::fast_ncc_find_first_in_region(
+                                    integral_image,
+                                    *local_region,
+                                    precision,
+                                    data,
+                                );
+                            return (key.clone(), Some(found.into_iter().collect()));
+                        }
+                        let mut found =
+                            template_match::segmented_ncc::fast_ncc_template_match_in_region(
+                                integral_image,
+                                *local_region,
+                                precision,
+                                data,
+                            );
+                        found.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap());
+                        Some(found)
+                    }
+                    (PreparedData::FFT(data), MatchMode::FFT)
+                    | (PreparedData::Edge(data), MatchMode::Edge) => Some(
+                        spectra[key.as_str()]
+                            .as_ref()
+                            .map(|spectrum| {
+                                template_match::fft_ncc::fft_ncc_with_spectrum(
+                                    spectrum, precision, data,
+                                )
+                                .into_iter()
+                                .map(|(x, y, corr)| (x, y, corr as f32))
+                                .collect()
+                            })
+                            .unwrap_or_default(),
+                    ),
+                    (PreparedData::Features(data), MatchMode::Features) => Some(
+                        template_match::features::find_match(
+                            &cut_region(gray_image, local_region),
+                            data,
+                        )
+                        .filter(|found| found.score >= precision)
+                        .map(|found| vec![(found.center.0, found.center.1, found.score)])
+                        .unwrap_or_default(),
+                    ),
+                    // opencl modes are run afterwards
+                    _ => None,
+                };
+                (key.clone(), found)
+            })
+            .collect();
+
+        #[cfg(feature = "opencl")]
+        for ((key, found), (_, local_region)) in results.iter_mut().zip(local_regions.iter()) {
+            if found.is_some() {
+                continue;
+            }
+            let (prepared_data, region, match_mode) = &stored[key];
+            if !matches!(
+                match_mode,
+                MatchMode::SegmentedOcl | MatchMode::SegmentedOclV2 | MatchMode::DenseOcl
+            ) {
+                continue;
+            }
+            // opencl buffers are allocated for exact region size
+            if (local_region.2 != region.2) | (local_region.3 != region.3) {
+                return Err(AutoGuiError::OutOfBoundsError(
+                    "OpenCL match modes require the prepared region to fit inside the captured window"
+                        .to_string(),
+                ));
+            }
+            let opencl_data = self.gui.opencl()?;
+            let ocl_version = match match_mode {
+                MatchMode::SegmentedOcl => OclVersion::V1,
+                MatchMode::SegmentedOclV2 => OclVersion::V2,
+                _ => {
+                    *found = Some(template_match::opencl_dense::gui_opencl_dense_ncc(
+                        opencl_data
+                            .ocl_dense_storage
+                            .lock()
+                            .unwrap()
+                            .get(key)
+                            .ok_or(ImageProcessingError::new("Error , no OCL data prepared"))?,
+                        precision,
+                        &cut_region(&self.gray_image, local_region),
+                        self.gui.find_strategy.max_results(),
+                    )?);
+                    continue;
+                }
+            };
+            let data = match prepared_data {
+                PreparedData::Segmented(data) => data,
+                _ => Err(ImageProcessingError::new(
+                    "error in prepared data type. Matchmode does not match prepare data type",
+                ))?,
+            };
+            let gpu_memory_pointers = opencl_data
+                .ocl_buffer_storage
+                .get(key)
+                .ok_or(ImageProcessingError::new("Error , no OCL data prepared"))?;
+            *found = Some(template_match::open_cl::gui_opencl_ncc_template_match(
+                &opencl_data.ocl_queue,
+                &opencl_data.ocl_program,
+                opencl_data.ocl_workgroup_size,
+                &opencl_data.ocl_kernel_storage.lock().unwrap()[key],
+                gpu_memory_pointers,
+                precision,
+                &cut_region(&self.gray_image, local_region),
+                data,
+                ocl_version,
+                self.gui.find_strategy.max_results(),
+            )?);
+        }
+
+        // positions to screen coordinates, pointing to the middle of the template.
+        // When alias has a backup, first found of them is used
+        let mut found_per_alias = Vec::new();
+        let mut results = results.drain(..);
+        for (alias, keys) in aliases.iter().zip(searched_keys.iter()) {
+            let mut alias_found = None;
+            for key in keys {
+                let (_, found) = results.next().expect("result for every searched key");
+                if alias_found.is_some() {
+                    continue;
+                }
+                let found = match found {
+                    Some(found) if !found.is_empty() => self.gui.find_strategy.apply(found),
+                    _ => continue,
+                };
+                let (prepared_data, region, _) = &stored[key];
+                let (offset_x, offset_y) = match_offset(prepared_data)?;
+                alias_found = Some(
+                    found
+                        .into_iter()
+                        .map(|(x, y, corr)| {
+                            (x + region.0 + offset_x, y + region.1 + offset_y, corr)
+                        })
+                        .collect::<Vec<(u32, u32, f32)>>(),
+                );
+            }
+            if let Some(found) = alias_found {
+                found_per_alias.push((alias.to_string(), found));
+            }
+        }
+        Ok(found_per_alias)
+    }
+
+    /// Returns screen position and correlation of the best match of image stored under provided alias,
+    /// no matter how low its correlation is. Useful for tuning precision and logging near misses.
+    /// Segmented OpenCL match modes are evaluated with the CPU segmented algorithm.
+    /// None only if correlation could not be calculated at any position, for instance on a flat image
+    pub fn find_best_match(&self, alias: &str) -> Result<Option<(u32, u32, f32)>, AutoGuiError> {
+        let stored = &self.gui.template_data.prepared_data_stored;
+        let searched_keys = self.gui.stored_keys_for_aliases(&[alias])?;
+        let mut best: Option<(u32, u32, f32)> = None;
+        for key in searched_keys.iter().flatten() {
+            let local_region = self.local_region(key)?;
+            let (prepared_data, region, _) = &stored[key];
+            let found = match prepared_data {
+                PreparedData::Segmented(data) => {
+                    template_match::segmented_ncc::fast_ncc_best_match_in_region(
+                        self.integrals(),
+                        local_region,
+                        data,
+                    )
+                }
+                PreparedData::FFT(data) | PreparedData::Edge(data) => self
+                    .spectrum(key, &local_region)
+                    .and_then(|spectrum| {
+                        template_match::fft_ncc::fft_ncc_best_match_with_spectrum(&spectrum, data)
+                    })
+                    .map(|(x, y, corr)| (x, y, corr as f32)),
+                PreparedData::Features(data) => template_match::features::find_match(
+                    &cut_region(&self.gray_image, &local_region),
+                    data,
+                )
+                .map(|found| (found.center.0, found.center.1, found.score)),
+                #[cfg(feature = "opencl")]
+                PreparedData::Dense(_) => {
+                    template_match::opencl_dense::gui_opencl_dense_best_match(
+                        self.gui
+                            .opencl()?
+                            .ocl_dense_storage
+                            .lock()
+                            .unwrap()
+                            .get(key)
+                            .ok_or(ImageProcessingError::new("Error , no OCL data prepared"))?,
+                        &cut_region(&self.gray_image, &local_region),
+                    )?
+                }
+                PreparedData::None => Err(ImageProcessingError::new("No prepared data loaded"))?,
+            };
+            let (offset_x, offset_y) = match_offset(prepared_data)?;
+            // positions to screen coordinates, pointing to the middle of the template
+            if let Some((x, y, corr)) = found {
+                if best.is_none_or(|(_, _, best_corr)| corr > best_corr) {
+                    best = Some((x + region.0 + offset_x, y + region.1 + offset_y, corr));
+                }
+            }
+        }
+        Ok(best)
+    }
+
+    /// Searches for image stored with Features match mode and returns corners of the matched area,
+    /// which can be scaled, rotated or partially covered, in screen coordinates.
+    /// Returns None if score of the match is below precision
+    pub fn find_features(
+        &self,
+        precision: f32,
+        alias: &str,
+    ) -> Result<Option<FeatureMatch>, AutoGuiError> {
+        let prepared_data = self.gui.template_data.prepared_data_stored.get(alias);
+        let data = match prepared_data {
+            Some((PreparedData::Features(data), _, _)) => data,
+            Some(_) => Err(AutoGuiError::AliasError(format!(
+                "Alias {} is not stored with Features match mode",
+                alias
+            )))?,
+            None => Err(AutoGuiError::AliasError(format!(
+                "No template stored with alias {}",
+                alias
+            )))?,
+        };
+        let local_region = self.local_region(alias)?;
+        Ok(
+            template_match::features::find_match(
+                &cut_region(&self.gray_image, &local_region),
+                data,
+            )
+            .filter(|found| found.score >= precision)
+            .map(|found| {
+                found.translated(
+                    self.region.0 + local_region.0,
+                    self.region.1 + local_region.1,
+                )
+            }),
+        )
+    }
+
+    /// returns color of pixel at screen position x, y
+    pub fn pixel(&self, x: u32, y: u32) -> Result<Rgba<u8>, AutoGuiError> {
+        let (region_x, region_y, region_width, region_height) = self.region;
+        if (x < region_x)
+            | (y < region_y)
+            | (x >= region_x + region_width)
+            | (y >= region_y + region_height)
+        {
+            return Err(AutoGuiError::OutOfBoundsError(format!(
+                "Pixel {}, {} is outside of captured frame",
+                x, y
+            )));
+        }
+        // color image can have higher resolution than the searched one, for instance on retina displays
+        let image_x = ((x - region_x) as u64 * self.image.width() as u64 / region_width as u64)
+            .min(self.image.width() as u64 - 1) as u32;
+        let image_y = ((y - region_y) as u64 * self.image.height() as u64 / region_height as u64)
+            .min(self.image.height() as u64 - 1) as u32;
+        Ok(*self.image.get_pixel(image_x, image_y))
+    }
+
+    /// saves captured frame at provided path
+    pub fn save(&self, path: &str) -> Result<(), AutoGuiError> {
+        Ok(self.image.save(path)?)
+    }
+
+    /// returns captured RGBA image
+    pub fn image(&self) -> &ImageBuffer<Rgba<u8>, Vec<u8>> {
+        &self.image
+    }
+
+    /// returns x, y, width and height of captured area
+    pub fn region(&self) -> (u32, u32, u32, u32) {
+        self.region
+    }
+
+    /// region of stored key relative to captured image. Cut to image size when capturing a window
+    fn local_region(&self, key: &str) -> Result<(u32, u32, u32, u32), AutoGuiError> {
+        let (prepared_data, region, _) = &self.gui.template_data.prepared_data_stored[key];
+        let (template_width, template_height) = template_size(prepared_data)?;
+        if (region.0 < self.region.0) | (region.1 < self.region.1) {
+            return Err(AutoGuiError::OutOfBoundsError(format!(
+                "Region of alias {} is outside of captured frame",
+                key
+            )));
+        }
+        let x = region.0 - self.region.0;
+        let y = region.1 - self.region.1;
+        let width = region.2.min(self.region.2.saturating_sub(x));
+        let height = region.3.min(self.region.3.saturating_sub(y));
+        if (width < template_width) | (height < template_height) {
+            return Err(AutoGuiError::OutOfBoundsError(format!(
+                "Template size of alias {} larger than captured region",
+                key
+            )));
+        }
+        Ok((x, y, width, height))
+    }
+
+    fn edges(&self) -> &ImageBuffer<Luma<u8>, Vec<u8>> {
+        self.edges
+            .get_or_init(|| imgtools::edge_map(&self.gray_image))
+    }
+
+    /// Spectrum of area searched by FFT or Edge template stored under key, computed on first use.
+    /// None for other match modes or when template does not fit into the area
+    fn spectrum(
+        &self,
+        key: &str,
+        local_region: &(u32, u32, u32, u32),
+    ) -> Option<Arc<ImageSpectrum>> {
+        let (prepared_data, _, _) = &self.gui.template_data.prepared_data_stored[key];
+        let (data, edge) = match prepared_data {
+            PreparedData::FFT(data) => (data, false),
+            PreparedData::Edge(data) => (data, true),
+            _ => return None,
+        };
+        let spectrum_key = (*local_region, edge, (data.padded_width, data.padded_height));
+        if let Some(spectrum) = self.spectra.lock().unwrap().get(&spectrum_key) {
+            return spectrum.fits(data).then(|| spectrum.clone());
+        }
+        // computed without holding the lock, since transformation runs on the thread pool
+        let image = if edge { self.edges() } else { &self.gray_image };
+        let spectrum = Arc::new(ImageSpectrum::new(&cut_region(image, local_region), data)?);
+        let spectrum = self
+            .spectra
+            .lock()
+            .unwrap()
+            .entry(spectrum_key)
+            .or_insert(spectrum)
+            .clone();
+        spectrum.fits(data).then_some(spectrum)
+    }
+
+    fn integrals(&self) -> &IntegralImage {
+        self.integrals
+            .get_or_init(|| IntegralImage::new(&self.gray_image))
+    }
+}
+
+impl RustAutoGui {
+    /// angles and stored keys of rotated variants of alias
+    pub(crate) fn rotated_variants(&self, alias: &str) -> Result<&[(f32, String)], AutoGuiError> {
+        self.template_data
+            .rotated_aliases
+            .get(alias)
+            .map(Vec::as_slice)
+            .ok_or(AutoGuiError::AliasError(format!(
+                "No rotated template stored with alias {}",
+                alias
+            )))
+    }
+
+    /// stored keys searched per alias. On macOS backup template for retina displays
+    /// is searched if the original was not found
+    pub(crate) fn stored_keys_for_aliases(
+        &self,
+        aliases: &[&str],
+    ) -> Result<Vec<Vec<String>>, AutoGuiError> {
+        let mut searched_keys: Vec<Vec<String>> = Vec::new();
+        for alias in aliases {
+            if !self.template_data.prepared_data_stored.contains_key(*alias) {
+                return Err(AutoGuiError::AliasError(format!(
+                    "No template stored with alias {}",
+                    alias
+                )));
+            }
+            #[allow(unused_mut)]
+            let mut keys = vec![alias.to_string()];
+            #[cfg(target_os = "macos")]
+            {
+                let bckp_alias = format!("{}_{}", alias, DEFAULT_BCKP_ALIAS);
+                if self
+                    .template_data
+                    .prepared_data_stored
+                    .contains_key(&bckp_alias)
+                {
+                    keys.push(bckp_alias);
+                }
+            }
+            searched_keys.push(keys);
+        }
+        Ok(searched_keys)
+    }
+}
+
+fn template_size(prepared_data: &PreparedData) -> Result<(u32, u32), AutoGuiError> {
+    match prepared_data {
+        PreparedData::Segmented(data) => Ok((data.template_width, data.template_height)),
+        PreparedData::FFT(data) | PreparedData::Edge(data) => {
+            Ok((data.template_width, data.template_height))
+        }
+        PreparedData::Features(data) => Ok((data.template_width, data.template_height)),
+        #[cfg(feature = "opencl")]
+        PreparedData::Dense(data) => Ok((data.template_width, data.template_height)),
+        PreparedData::None => Err(ImageProcessingError::new("No prepared data loaded").into()),
+    }
+}
+
+/// offset from found position to the middle of the template. Features matching
+/// already returns middle of the matched area
+fn match_offset(prepared_data: &PreparedData) -> Result<(u32, u32), AutoGuiError> {
+    match prepared_data {
+        PreparedData::Features(_) => Ok((0, 0)),
+        _ => {
+            let (template_width, template_height) = template_size(prepared_data)?;
+            Ok((template_width / 2, template_height / 2))
+        }
+    }
+}
+
+fn cut_region(
+    image: &ImageBuffer<Luma<u8>, Vec<u8>>,
+    region: &(u32, u32, u32, u32),
+) -> ImageBuffer<Luma<u8>, Vec<u8>> {
+    imgtools::cut_screen_region(region.0, region.1, region.2, region.3, image)
+}
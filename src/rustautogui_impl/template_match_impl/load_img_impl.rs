@@ -4,8 +4,13 @@ use crate::core::template_match;
 use crate::data::*;
 #[cfg(not(feature = "lite"))]
 use crate::imgtools;
+#[cfg(feature = "opencl")]
+use crate::{template_match::open_cl::OclVersion, AUTO_BENCHMARK_ALIAS};
 #[cfg(not(feature = "lite"))]
-use crate::{AutoGuiError, ImageProcessingError, MatchMode, DEFAULT_ALIAS, DEFAULT_BCKP_ALIAS};
+use crate::{
+    AutoGuiError, AutoModeChoice, ImageProcessingError, MatchMode, PyramidSearch, RotationRange,
+    DEFAULT_ALIAS, DEFAULT_BCKP_ALIAS, ROTATED_ALIAS,
+};
 #[cfg(not(feature = "lite"))]
 use image::{
     imageops::{resize, FilterType::Nearest},
@@ -13,6 +18,8 @@ use image::{
 };
 #[cfg(not(feature = "lite"))]
 use rustfft::{num_complex::Complex, num_traits::ToPrimitive};
+#[cfg(feature = "opencl")]
+use std::time::Duration;
 #[cfg(not(feature = "lite"))]
 impl crate::RustAutoGui {
     #[cfg(not(feature = "lite"))]
@@ -26,6 +33,7 @@ impl crate::RustAutoGui {
         match_mode: MatchMode,
         alias: Option<&str>,
         user_threshold: Option<f32>,
+        pyramid: Option<PyramidSearch>,
     ) -> Result<(), AutoGuiError> {
         // resize and adjust if retina screen is used
         // prepare additionally backup template for 2 screen size variants
@@ -80,6 +88,16 @@ impl crate::RustAutoGui {
             region.3,
         )?;
 
+        // auto match mode is resolved into the fastest mode, which is then prepared as usual
+        let auto_choice = match match_mode {
+            MatchMode::Auto => Some(self.select_match_mode(&template, region, user_threshold)?),
+            _ => None,
+        };
+        let match_mode = match &auto_choice {
+            Some(choice) => choice.match_mode.clone(),
+            None => match_mode,
+        };
+
         // do the rest of preparation calculations depending on the matchmode
         // FFT pads the image, does fourier transformations,
         // calculates conjugate and inverses transformation on template
@@ -94,6 +112,27 @@ impl crate::RustAutoGui {
                 (prepared_data, match_mode)
             }
 
+            MatchMode::Edge => {
+                let prepared_data =
+                    PreparedData::Edge(template_match::fft_ncc::prepare_template_picture(
+                        &imgtools::edge_map(&template),
+                        region.2,
+                        region.3,
+                    ));
+                (prepared_data, Some(MatchMode::Edge))
+            }
+
+            MatchMode::Features => {
+                let prepared_data = PreparedData::Features(
+                    template_match::features::prepare_template_picture(&template)?,
+                );
+                (prepared_data, Some(MatchMode::Features))
+            }
+
+            MatchMode::Auto => Err(ImageProcessingError::new(
+                "Auto match mode was not resolved",
+            ))?,
+
             MatchMode::Segmented => {
                 let prepared_data: PreparedData =
                     template_match::segmented_ncc::prepare_template_picture(
@@ -143,15 +182,15 @@ impl crate::RustAutoGui {
                         region.3,
                         template_width,
                         template_height,
-                        &self.opencl_data.ocl_queue,
+                        &self.opencl()?.ocl_queue,
                         &prepared_data.template_segments_slow,
                         &prepared_data.template_segments_fast,
                     )?;
 
                     let kernels = KernelStorage::new(
                         &ocl_buffer_data,
-                        &self.opencl_data.ocl_program,
-                        &self.opencl_data.ocl_queue,
+                        &self.opencl()?.ocl_program,
+                        &self.opencl()?.ocl_queue,
                         region.2,
                         region.3,
                         template_width,
@@ -163,24 +202,28 @@ impl crate::RustAutoGui {
                         prepared_data.segment_sum_squared_deviations_fast,
                         prepared_data.segment_sum_squared_deviations_slow,
                         prepared_data.expected_corr_fast,
-                        self.opencl_data.ocl_workgroup_size as usize,
+                        self.opencl()?.ocl_workgroup_size as usize,
                     )?;
                     match alias {
                         Some(name) => {
-                            self.opencl_data
+                            self.opencl_mut()?
                                 .ocl_buffer_storage
                                 .insert(name.into(), ocl_buffer_data);
 
-                            self.opencl_data
+                            self.opencl()?
                                 .ocl_kernel_storage
+                                .lock()
+                                .unwrap()
                                 .insert(name.into(), kernels);
                         }
                         None => {
-                            self.opencl_data
+                            self.opencl_mut()?
                                 .ocl_buffer_storage
                                 .insert(DEFAULT_ALIAS.into(), ocl_buffer_data);
-                            self.opencl_data
+                            self.opencl()?
                                 .ocl_kernel_storage
+                                .lock()
+                                .unwrap()
                                 .insert(DEFAULT_ALIAS.into(), kernels);
                         }
                     }
@@ -188,8 +231,56 @@ impl crate::RustAutoGui {
 
                 (PreparedData::Segmented(prepared_data), match_mode)
             }
+
+            #[cfg(feature = "opencl")]
+            MatchMode::DenseOcl => {
+                // template and all buffers are kept on device, only its size is needed on CPU side
+                let kernels = DenseKernelStorage::new(
+                    &template,
+                    &self.opencl()?.ocl_program,
+                    &self.opencl()?.ocl_queue,
+                    region.2,
+                    region.3,
+                    self.opencl()?.ocl_workgroup_size as usize,
+                )?;
+                self.opencl()?
+                    .ocl_dense_storage
+                    .lock()
+                    .unwrap()
+                    .insert(alias.unwrap_or(DEFAULT_ALIAS).into(), kernels);
+                (
+                    PreparedData::Dense(DenseData {
+                        template_width,
+                        template_height,
+                    }),
+                    Some(MatchMode::DenseOcl),
+                )
+            }
+        };
+
+        // coarse to fine search additionally needs downscaled template
+        let pyramid_data = match pyramid {
+            Some(settings) => Some(template_match::pyramid::prepare_template_picture(
+                &template,
+                &match_mode,
+                region.2,
+                region.3,
+                settings,
+                user_threshold,
+            )?),
+            None => None,
         };
 
+        let choice_key = alias.unwrap_or(DEFAULT_ALIAS).to_string();
+        match auto_choice {
+            Some(choice) => {
+                self.template_data.auto_choices.insert(choice_key, choice);
+            }
+            None => {
+                self.template_data.auto_choices.remove(&choice_key);
+            }
+        }
+
         // Alias Some -> storing the image , we just save it to Hashmap
         // Alias None -> not storing, then we change struct attributes to fit the single loaded image search
         match alias {
@@ -197,6 +288,16 @@ impl crate::RustAutoGui {
                 self.template_data
                     .prepared_data_stored
                     .insert(name.into(), (template_data, region, match_mode));
+                match pyramid_data {
+                    Some(pyramid_data) => {
+                        self.template_data
+                            .pyramids
+                            .insert(name.into(), pyramid_data);
+                    }
+                    None => {
+                        self.template_data.pyramids.remove(name);
+                    }
+                }
             }
             None => {
                 self.template_data.region = region;
@@ -215,6 +316,134 @@ impl crate::RustAutoGui {
         Ok(())
     }
 
+    #[cfg(not(feature = "lite"))]
+    /// Picks match mode for MatchMode::Auto. CPU modes are chosen by estimate or benchmark,
+    /// and when OpenCL device is available, OpenCL segmented and dense searches are benchmarked as well
+    fn select_match_mode(
+        &mut self,
+        template: &ImageBuffer<Luma<u8>, Vec<u8>>,
+        region: (u32, u32, u32, u32),
+        user_threshold: Option<f32>,
+    ) -> Result<AutoModeChoice, AutoGuiError> {
+        #[cfg(not(feature = "opencl"))]
+        let opencl_available = false;
+        #[cfg(feature = "opencl")]
+        let opencl_available = self.opencl_data.is_some();
+        #[allow(unused_mut)]
+        let mut choice = template_match::auto_mode::select_match_mode(
+            template,
+            Some((region.2, region.3)),
+            user_threshold,
+            opencl_available,
+        );
+        #[cfg(feature = "opencl")]
+        if opencl_available {
+            // segmented search needs template that can be segmented
+            let mut ocl_modes = vec![MatchMode::DenseOcl];
+            if choice.segments_fast > 0 {
+                ocl_modes.push(MatchMode::SegmentedOclV2);
+            }
+            for match_mode in ocl_modes {
+                // failing device only excludes its mode, CPU choice stays available
+                match self.benchmark_opencl(
+                    template.clone(),
+                    region,
+                    user_threshold,
+                    match_mode.clone(),
+                ) {
+                    Ok(duration) => choice.add_timing(match_mode, duration),
+                    Err(x) => {
+                        if !self.suppress_warnings {
+                            eprintln!(
+                                "Warning: {:?} benchmark failed, it is skipped by auto mode. {}",
+                                match_mode, x
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        if self.debug {
+            println!("Auto match mode picked {:?}", choice);
+        }
+        Ok(choice)
+    }
+
+    #[cfg(feature = "opencl")]
+    /// shortest duration of several OpenCL searches on benchmark image of region size, after warm up search.
+    /// Template is prepared under temporary alias, which is removed afterwards, also when benchmark fails
+    fn benchmark_opencl(
+        &mut self,
+        template: ImageBuffer<Luma<u8>, Vec<u8>>,
+        region: (u32, u32, u32, u32),
+        user_threshold: Option<f32>,
+        match_mode: MatchMode,
+    ) -> Result<Duration, AutoGuiError> {
+        let duration = self.time_opencl_search(template, region, user_threshold, match_mode);
+        // also removes backup prepared for retina displays
+        self.remove_stored_keys(|key| key.starts_with(AUTO_BENCHMARK_ALIAS));
+        duration
+    }
+
+    #[cfg(feature = "opencl")]
+    fn time_opencl_search(
+        &mut self,
+        template: ImageBuffer<Luma<u8>, Vec<u8>>,
+        region: (u32, u32, u32, u32),
+        user_threshold: Option<f32>,
+        match_mode: MatchMode,
+    ) -> Result<Duration, AutoGuiError> {
+        let image = template_match::auto_mode::benchmark_image(&template, region.2, region.3);
+        self.prepare_template_picture_bw(
+            template,
+            Some(region),
+            match_mode.clone(),
+            Some(AUTO_BENCHMARK_ALIAS),
+            user_threshold,
+            None,
+        )?;
+        let opencl_data = self.opencl()?;
+        let stored = &self.template_data.prepared_data_stored[AUTO_BENCHMARK_ALIAS].0;
+        template_match::auto_mode::try_time_search(|| {
+            match (&match_mode, stored) {
+                (MatchMode::DenseOcl, _) => {
+                    template_match::opencl_dense::gui_opencl_dense_ncc(
+                        &opencl_data.ocl_dense_storage.lock().unwrap()[AUTO_BENCHMARK_ALIAS],
+                        0.9,
+                        &image,
+                        None,
+                    )?;
+                }
+                (_, PreparedData::Segmented(data)) => {
+                    template_match::open_cl::gui_opencl_ncc_template_match(
+                        &opencl_data.ocl_queue,
+                        &opencl_data.ocl_program,
+                        opencl_data.ocl_workgroup_size,
+                        &opencl_data.ocl_kernel_storage.lock().unwrap()[AUTO_BENCHMARK_ALIAS],
+                        &opencl_data.ocl_buffer_storage[AUTO_BENCHMARK_ALIAS],
+                        0.9,
+                        &image,
+                        data,
+                        OclVersion::V2,
+                        None,
+                    )?;
+                }
+                _ => Err(ImageProcessingError::new("Wrong data prepared  / stored."))?,
+            };
+            Ok::<(), AutoGuiError>(())
+        })
+    }
+
+    /// Returns match mode picked by MatchMode::Auto for stored template with provided alias, or for
+    /// template loaded with prepare_* functions when alias is None, together with estimate and benchmark
+    /// timings the choice was based on. Returns None if template was not prepared with MatchMode::Auto
+    #[cfg(not(feature = "lite"))]
+    pub fn get_auto_mode_choice(&self, alias: Option<&str>) -> Option<&AutoModeChoice> {
+        self.template_data
+            .auto_choices
+            .get(alias.unwrap_or(DEFAULT_ALIAS))
+    }
+
     #[cfg(not(feature = "lite"))]
     // prepares also unscaled variant of image if retina display is on
     // since it is recursively calling again preparation of template with another alias
@@ -265,7 +494,10 @@ impl crate::RustAutoGui {
     #[cfg(not(feature = "lite"))]
     #[allow(dead_code)]
     fn check_alias_name(alias: &str) -> Result<(), ImageProcessingError> {
-        if (alias.contains(DEFAULT_ALIAS)) | (alias.contains(DEFAULT_BCKP_ALIAS)) {
+        if (alias.contains(DEFAULT_ALIAS))
+            | (alias.contains(DEFAULT_BCKP_ALIAS))
+            | (alias.contains(ROTATED_ALIAS))
+        {
             return Err(ImageProcessingError::new(
                 "Please do not use built in default alias names",
             ));
@@ -282,7 +514,7 @@ impl crate::RustAutoGui {
         match_mode: MatchMode,
     ) -> Result<(), AutoGuiError> {
         let template: ImageBuffer<Luma<u8>, Vec<u8>> = imgtools::load_image_bw(template_path)?;
-        self.prepare_template_picture_bw(template, region, match_mode, None, None)
+        self.prepare_template_picture_bw(template, region, match_mode, None, None, None)
     }
     #[cfg(not(feature = "lite"))]
     /// Loads template from file on provided path
@@ -294,7 +526,7 @@ impl crate::RustAutoGui {
         threshold: f32,
     ) -> Result<(), AutoGuiError> {
         let template: ImageBuffer<Luma<u8>, Vec<u8>> = imgtools::load_image_bw(template_path)?;
-        self.prepare_template_picture_bw(template, region, match_mode, None, Some(threshold))
+        self.prepare_template_picture_bw(template, region, match_mode, None, Some(threshold), None)
     }
     #[cfg(not(feature = "lite"))]
     /// prepare from imagebuffer, works only on types RGB/RGBA/Luma
@@ -310,7 +542,7 @@ impl crate::RustAutoGui {
     {
         let color_scheme = imgtools::check_imagebuffer_color_scheme(&image)?;
         let luma_img = imgtools::convert_t_imgbuffer_to_luma(&image, color_scheme)?;
-        self.prepare_template_picture_bw(luma_img, region, match_mode, None, None)?;
+        self.prepare_template_picture_bw(luma_img, region, match_mode, None, None, None)?;
         Ok(())
     }
 
@@ -328,7 +560,14 @@ impl crate::RustAutoGui {
     {
         let color_scheme = imgtools::check_imagebuffer_color_scheme(&image)?;
         let luma_img = imgtools::convert_t_imgbuffer_to_luma(&image, color_scheme)?;
-        self.prepare_template_picture_bw(luma_img, region, match_mode, None, Some(threshold))?;
+        self.prepare_template_picture_bw(
+            luma_img,
+            region,
+            match_mode,
+            None,
+            Some(threshold),
+            None,
+        )?;
         Ok(())
     }
 
@@ -341,7 +580,7 @@ impl crate::RustAutoGui {
         match_mode: MatchMode,
     ) -> Result<(), AutoGuiError> {
         let image = image::load_from_memory(img_raw)?;
-        self.prepare_template_picture_bw(image.to_luma8(), region, match_mode, None, None)
+        self.prepare_template_picture_bw(image.to_luma8(), region, match_mode, None, None, None)
     }
 
     #[cfg(not(feature = "lite"))]
@@ -360,6 +599,7 @@ impl crate::RustAutoGui {
             match_mode,
             None,
             Some(threshold),
+            None,
         )
     }
 
@@ -375,7 +615,7 @@ impl crate::RustAutoGui {
     ) -> Result<(), AutoGuiError> {
         // RustAutoGui::check_alias_name(&alias)?;
         let template: ImageBuffer<Luma<u8>, Vec<u8>> = imgtools::load_image_bw(template_path)?;
-        self.prepare_template_picture_bw(template, region, match_mode, Some(alias), None)
+        self.prepare_template_picture_bw(template, region, match_mode, Some(alias), None, None)
     }
 
     #[cfg(not(feature = "lite"))]
@@ -390,7 +630,14 @@ impl crate::RustAutoGui {
     ) -> Result<(), AutoGuiError> {
         // RustAutoGui::check_alias_name(&alias)?;
         let template: ImageBuffer<Luma<u8>, Vec<u8>> = imgtools::load_image_bw(template_path)?;
-        self.prepare_template_picture_bw(template, region, match_mode, Some(alias), Some(threshold))
+        self.prepare_template_picture_bw(
+            template,
+            region,
+            match_mode,
+            Some(alias),
+            Some(threshold),
+            None,
+        )
     }
     #[cfg(not(feature = "lite"))]
     /// Load template from imagebuffer and store prepared template data for multiple image search
@@ -408,7 +655,7 @@ impl crate::RustAutoGui {
         // RustAutoGui::check_alias_name(&alias)?;
         let color_scheme = imgtools::check_imagebuffer_color_scheme(&image)?;
         let luma_img = imgtools::convert_t_imgbuffer_to_luma(&image, color_scheme)?;
-        self.prepare_template_picture_bw(luma_img, region, match_mode, Some(alias), None)
+        self.prepare_template_picture_bw(luma_img, region, match_mode, Some(alias), None, None)
     }
 
     #[cfg(not(feature = "lite"))]
@@ -428,7 +675,14 @@ impl crate::RustAutoGui {
         // RustAutoGui::check_alias_name(&alias)?;
         let color_scheme = imgtools::check_imagebuffer_color_scheme(&image)?;
         let luma_img = imgtools::convert_t_imgbuffer_to_luma(&image, color_scheme)?;
-        self.prepare_template_picture_bw(luma_img, region, match_mode, Some(alias), Some(threshold))
+        self.prepare_template_picture_bw(
+            luma_img,
+            region,
+            match_mode,
+            Some(alias),
+            Some(threshold),
+            None,
+        )
     }
     #[cfg(not(feature = "lite"))]
     /// Load template from encoded raw bytes and store prepared template data for multiple image search
@@ -441,7 +695,14 @@ impl crate::RustAutoGui {
     ) -> Result<(), AutoGuiError> {
         // RustAutoGui::check_alias_name(&alias)?;
         let image = image::load_from_memory(img_raw)?;
-        self.prepare_template_picture_bw(image.to_luma8(), region, match_mode, Some(alias), None)?;
+        self.prepare_template_picture_bw(
+            image.to_luma8(),
+            region,
+            match_mode,
+            Some(alias),
+            None,
+            None,
+        )?;
         Ok(())
     }
     #[cfg(not(feature = "lite"))]
@@ -461,9 +722,218 @@ impl crate::RustAutoGui {
             match_mode,
             Some(alias),
             Some(threshold),
+            None,
         )?;
         Ok(())
     }
+    ///////////////////////// store rotated template functions //////////////////////////
+    #[cfg(not(feature = "lite"))]
+    /// Stores rotated variants of template for every angle in rotation range, searched with
+    /// find_stored_image_on_screen_rotated. Each variant is prepared as a separate template,
+    /// so preparation and search time grow with number of angles
+    pub fn store_template_from_file_rotated(
+        &mut self,
+        template_path: &str,
+        region: Option<(u32, u32, u32, u32)>,
+        match_mode: MatchMode,
+        alias: &str,
+        rotation: RotationRange,
+    ) -> Result<(), AutoGuiError> {
+        let template: ImageBuffer<Luma<u8>, Vec<u8>> = imgtools::load_image_bw(template_path)?;
+        self.store_rotated_variants(template, region, match_mode, alias, rotation, None)
+    }
+
+    #[cfg(not(feature = "lite"))]
+    /// same as store_template_from_file_rotated, with custom segmentation threshold for every variant
+    pub fn store_template_from_file_rotated_custom(
+        &mut self,
+        template_path: &str,
+        region: Option<(u32, u32, u32, u32)>,
+        match_mode: MatchMode,
+        alias: &str,
+        rotation: RotationRange,
+        threshold: f32,
+    ) -> Result<(), AutoGuiError> {
+        let template: ImageBuffer<Luma<u8>, Vec<u8>> = imgtools::load_image_bw(template_path)?;
+        self.store_rotated_variants(
+            template,
+            region,
+            match_mode,
+            alias,
+            rotation,
+            Some(threshold),
+        )
+    }
+
+    #[cfg(not(feature = "lite"))]
+    /// same as store_template_from_file_rotated, works only on types RGB/RGBA/Luma
+    pub fn store_template_from_imagebuffer_rotated<P, T>(
+        &mut self,
+        image: ImageBuffer<P, Vec<T>>,
+        region: Option<(u32, u32, u32, u32)>,
+        match_mode: MatchMode,
+        alias: &str,
+        rotation: RotationRange,
+    ) -> Result<(), AutoGuiError>
+    where
+        P: Pixel<Subpixel = T> + 'static,
+        T: Primitive + ToPrimitive + 'static,
+    {
+        let color_scheme = imgtools::check_imagebuffer_color_scheme(&image)?;
+        let luma_img = imgtools::convert_t_imgbuffer_to_luma(&image, color_scheme)?;
+        self.store_rotated_variants(luma_img, region, match_mode, alias, rotation, None)
+    }
+
+    #[cfg(not(feature = "lite"))]
+    /// same as store_template_from_imagebuffer_rotated, with custom segmentation threshold for every variant
+    pub fn store_template_from_imagebuffer_rotated_custom<P, T>(
+        &mut self,
+        image: ImageBuffer<P, Vec<T>>,
+        region: Option<(u32, u32, u32, u32)>,
+        match_mode: MatchMode,
+        alias: &str,
+        rotation: RotationRange,
+        threshold: f32,
+    ) -> Result<(), AutoGuiError>
+    where
+        P: Pixel<Subpixel = T> + 'static,
+        T: Primitive + ToPrimitive + 'static,
+    {
+        let color_scheme = imgtools::check_imagebuffer_color_scheme(&image)?;
+        let luma_img = imgtools::convert_t_imgbuffer_to_luma(&image, color_scheme)?;
+        self.store_rotated_variants(
+            luma_img,
+            region,
+            match_mode,
+            alias,
+            rotation,
+            Some(threshold),
+        )
+    }
+
+    #[cfg(not(feature = "lite"))]
+    fn store_rotated_variants(
+        &mut self,
+        template: ImageBuffer<Luma<u8>, Vec<u8>>,
+        region: Option<(u32, u32, u32, u32)>,
+        match_mode: MatchMode,
+        alias: &str,
+        rotation: RotationRange,
+        user_threshold: Option<f32>,
+    ) -> Result<(), AutoGuiError> {
+        let mut variants: Vec<(f32, String)> = Vec::new();
+        for angle in rotation.angles()? {
+            let rotated = template_match::rotation::rotate_template(&template, angle);
+            let key = format!("{}{}{}", alias, ROTATED_ALIAS, angle);
+            self.prepare_template_picture_bw(
+                rotated,
+                region,
+                match_mode.clone(),
+                Some(&key),
+                user_threshold,
+                None,
+            )?;
+            variants.push((angle, key));
+        }
+        // variants of previously stored rotation range are replaced
+        if let Some(previous) = self
+            .template_data
+            .rotated_aliases
+            .insert(alias.to_string(), variants.clone())
+        {
+            let replaced: Vec<String> = previous
+                .into_iter()
+                .map(|(_, key)| key)
+                .filter(|key| !variants.iter().any(|(_, new_key)| new_key == key))
+                .collect();
+            // backups prepared for retina displays are removed with their variants
+            self.remove_stored_keys(|key| {
+                replaced.iter().any(|replaced_key| {
+                    (key == replaced_key)
+                        || (*key == format!("{}_{}", replaced_key, DEFAULT_BCKP_ALIAS))
+                })
+            });
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "lite"))]
+    /// removes stored template data of keys matching the predicate, including OpenCL buffers and kernels
+    fn remove_stored_keys(&mut self, remove: impl Fn(&String) -> bool) {
+        self.template_data
+            .prepared_data_stored
+            .retain(|key, _| !remove(key));
+        self.template_data.pyramids.retain(|key, _| !remove(key));
+        self.template_data
+            .auto_choices
+            .retain(|key, _| !remove(key));
+        #[cfg(feature = "opencl")]
+        if let Some(opencl_data) = self.opencl_data.as_mut() {
+            opencl_data.ocl_buffer_storage.retain(|key, _| !remove(key));
+            opencl_data
+                .ocl_kernel_storage
+                .lock()
+                .unwrap()
+                .retain(|key, _| !remove(key));
+            opencl_data
+                .ocl_dense_storage
+                .lock()
+                .unwrap()
+                .retain(|key, _| !remove(key));
+        }
+    }
+
+    ///////////////////////// store pyramid template functions //////////////////////////
+
+    #[cfg(not(feature = "lite"))]
+    /// Stores template searched coarse to fine. Candidates are first searched on screen and template
+    /// downscaled by pyramid factor, then verified at full resolution only around candidates.
+    /// Works with Segmented and FFT match modes and is searched with find_stored_image_on_screen
+    pub fn store_template_from_file_pyramid(
+        &mut self,
+        template_path: &str,
+        region: Option<(u32, u32, u32, u32)>,
+        match_mode: MatchMode,
+        alias: &str,
+        pyramid: PyramidSearch,
+    ) -> Result<(), AutoGuiError> {
+        let template: ImageBuffer<Luma<u8>, Vec<u8>> = imgtools::load_image_bw(template_path)?;
+        self.prepare_template_picture_bw(
+            template,
+            region,
+            match_mode,
+            Some(alias),
+            None,
+            Some(pyramid),
+        )
+    }
+
+    #[cfg(not(feature = "lite"))]
+    /// same as store_template_from_file_pyramid, works only on types RGB/RGBA/Luma
+    pub fn store_template_from_imagebuffer_pyramid<P, T>(
+        &mut self,
+        image: ImageBuffer<P, Vec<T>>,
+        region: Option<(u32, u32, u32, u32)>,
+        match_mode: MatchMode,
+        alias: &str,
+        pyramid: PyramidSearch,
+    ) -> Result<(), AutoGuiError>
+    where
+        P: Pixel<Subpixel = T> + 'static,
+        T: Primitive + ToPrimitive + 'static,
+    {
+        let color_scheme = imgtools::check_imagebuffer_color_scheme(&image)?;
+        let luma_img = imgtools::convert_t_imgbuffer_to_luma(&image, color_scheme)?;
+        self.prepare_template_picture_bw(
+            luma_img,
+            region,
+            match_mode,
+            Some(alias),
+            None,
+            Some(pyramid),
+        )
+    }
+
     #[cfg(not(feature = "lite"))]
     /// DEPRECATED
     #[deprecated(since = "2.2.0", note = "Renamed to prepare_template_from_file.")]
@@ -474,6 +944,6 @@ impl crate::RustAutoGui {
         match_mode: MatchMode,
     ) -> Result<(), AutoGuiError> {
         let template: ImageBuffer<Luma<u8>, Vec<u8>> = imgtools::load_image_bw(template_path)?;
-        self.prepare_template_picture_bw(template, region, match_mode, None, None)
+        self.prepare_template_picture_bw(template, region, match_mode, None, None, None)
     }
 }
@@ -1,2 +1,6 @@
 pub mod find_img_impl;
+#[cfg(not(feature = "lite"))]
+pub mod frame_impl;
 pub mod load_img_impl;
+#[cfg(not(feature = "lite"))]
+pub mod multi_find_impl;
@@ -0,0 +1,81 @@
+#![allow(clippy::type_complexity)]
+
+use crate::{AutoGuiError, ImageProcessingError};
+
+impl crate::RustAutoGui {
+    /// Searches for multiple stored images using a single screen capture and returns
+    /// alias with the highest correlation, along with its found locations.
+    /// Useful for deciding which of several dialogs is currently shown.
+    /// Returns None if none of the images is found
+    pub fn find_any_stored_image(
+        &mut self,
+        precision: f32,
+        aliases: &[&str],
+    ) -> Result<Option<(String, Vec<(u32, u32, f32)>)>, AutoGuiError> {
+        if aliases.is_empty() {
+            return Ok(None);
+        }
+        let region = self.stored_regions_bounds(aliases)?;
+        self.capture_frame_region(region)?
+            .find_any(precision, aliases)
+    }
+
+    /// Searches for multiple stored images using a single screen capture. Screen is captured once
+    /// and integral images are computed once, then all aliases are evaluated in parallel.
+    /// Returns found locations for every alias that was found, in the order aliases were given.
+    /// OpenCL match modes are evaluated sequentially after the CPU ones
+    pub fn find_all_stored_images(
+        &mut self,
+        precision: f32,
+        aliases: &[&str],
+    ) -> Result<Vec<(String, Vec<(u32, u32, f32)>)>, AutoGuiError> {
+        if aliases.is_empty() {
+            return Ok(Vec::new());
+        }
+        let region = self.stored_regions_bounds(aliases)?;
+        self.capture_frame_region(region)?
+            .find_all(precision, aliases)
+    }
+
+    /// Searches for rotated variants of template stored with store_template_*_rotated functions,
+    /// using a single screen capture. Returns angle of the variant with the highest correlation,
+    /// along with its found locations. Returns None if none of the variants is found
+    pub fn find_stored_image_on_screen_rotated(
+        &mut self,
+        precision: f32,
+        alias: &str,
+    ) -> Result<Option<(f32, Vec<(u32, u32, f32)>)>, AutoGuiError> {
+        let keys: Vec<String> = self
+            .rotated_variants(alias)?
+            .iter()
+            .map(|(_, key)| key.clone())
+            .collect();
+        let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+        let region = self.stored_regions_bounds(&keys)?;
+        self.capture_frame_region(region)?
+            .find_rotated(precision, alias)
+    }
+
+    /// area covering regions of all provided aliases, so it can be captured only once
+    pub(crate) fn stored_regions_bounds(
+        &self,
+        aliases: &[&str],
+    ) -> Result<(u32, u32, u32, u32), AutoGuiError> {
+        let searched_keys = self.stored_keys_for_aliases(aliases)?;
+        searched_keys
+            .iter()
+            .flatten()
+            .map(|key| self.template_data.prepared_data_stored[key].1)
+            .fold(None, |acc: Option<(u32, u32, u32, u32)>, r| match acc {
+                None => Some((r.0, r.1, r.0 + r.2, r.1 + r.3)),
+                Some(a) => Some((
+                    a.0.min(r.0),
+                    a.1.min(r.1),
+                    a.2.max(r.0 + r.2),
+                    a.3.max(r.1 + r.3),
+                )),
+            })
+            .map(|(x1, y1, x2, y2)| (x1, y1, x2 - x1, y2 - y1))
+            .ok_or(ImageProcessingError::new("No regions to search").into())
+    }
+}
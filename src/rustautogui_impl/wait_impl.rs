@@ -0,0 +1,50 @@
+use crate::core::polling::poll_blocking;
+use crate::{AutoGuiError, PollingPolicy};
+use std::time::Duration;
+
+impl crate::RustAutoGui {
+    /// sets polling policy used by loop_find_* and wait_until_image_vanishes functions.
+    /// Default is checking every 50 milliseconds
+    pub fn set_polling_policy(&mut self, policy: PollingPolicy) {
+        self.polling_policy = policy;
+    }
+
+    /// returns currently set polling policy
+    pub fn get_polling_policy(&self) -> PollingPolicy {
+        self.polling_policy
+    }
+
+    /// Evaluates condition repeatedly, sleeping poll_interval between evaluations, until it
+    /// returns true or timeout passes. Condition is always evaluated at least once.
+    /// Returns Ok(true) if condition was met and Ok(false) if it timed out.
+    /// Timeout of 0 waits indefinitely
+    pub fn wait_until<F>(
+        &mut self,
+        mut condition: F,
+        timeout: Duration,
+        poll_interval: Duration,
+    ) -> Result<bool, AutoGuiError>
+    where
+        F: FnMut(&mut Self) -> Result<bool, AutoGuiError>,
+    {
+        let policy = PollingPolicy::fixed(poll_interval);
+        let result = self.poll(&policy, timeout, |gui| Ok(condition(gui)?.then_some(())))?;
+        Ok(result.is_some())
+    }
+
+    /// Shared loop behind all waiting functions. Runs attempt until it returns Some,
+    /// sleeping between attempts as defined by policy.
+    /// Returns Ok(None) when timeout passes or attempts run out
+    pub(crate) fn poll<T, F>(
+        &mut self,
+        policy: &PollingPolicy,
+        timeout: Duration,
+        mut attempt: F,
+    ) -> Result<Option<T>, AutoGuiError>
+    where
+        F: FnMut(&mut Self) -> Result<Option<T>, AutoGuiError>,
+    {
+        let suppress_warnings = self.suppress_warnings;
+        poll_blocking(policy, timeout, suppress_warnings, || attempt(self))
+    }
+}
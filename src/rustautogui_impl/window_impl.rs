@@ -0,0 +1,44 @@
+#[cfg(not(feature = "lite"))]
+use image::{ImageBuffer, Rgba};
+
+use crate::AutoGuiError;
+
+impl crate::RustAutoGui {
+    /// searches open windows and returns id of the first window whose title contains provided name.
+    /// Returned id can be used with screenshot_window and set_capture_window
+    pub fn find_window_by_name(&self, name: &str) -> Option<u64> {
+        self.screen.find_window_by_name(name)
+    }
+
+    /// returns width and height of the window
+    pub fn get_window_size(&self, window: u64) -> Result<(u32, u32), AutoGuiError> {
+        self.screen.window_dimension(window)
+    }
+
+    #[cfg(not(feature = "lite"))]
+    /// captures contents of a single window, even when it is covered by other windows.
+    /// Requires X11 Composite extension. Minimized windows cannot be captured
+    pub fn screenshot_window(
+        &mut self,
+        window: u64,
+    ) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>, AutoGuiError> {
+        self.screen.grab_window_image(window)
+    }
+
+    #[cfg(not(feature = "lite"))]
+    /// captures contents of a single window and saves it at provided path
+    pub fn save_window_screenshot(&mut self, window: u64, path: &str) -> Result<(), AutoGuiError> {
+        let image = self.screen.grab_window_image(window)?;
+        Ok(image.save(path)?)
+    }
+
+    #[cfg(not(feature = "lite"))]
+    /// When Some(window) is set, all the find image functions capture only that window contents
+    /// instead of the whole screen, so templates can be found in windows that are in background.
+    /// Regions are then relative to the window top left corner, and so are the returned positions.
+    /// Functions that move the mouse to found image translate the position to the screen.
+    /// None returns to regular screen capture
+    pub fn set_capture_window(&mut self, window: Option<u64>) {
+        self.capture_window = window;
+    }
+}
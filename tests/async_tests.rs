@@ -0,0 +1,122 @@
+// run with cargo test --tests --release --features "dev async" -- --nocapture
+// polling loop is tested without display through poll_async,
+// AsyncRustAutoGui tests need X display and return early when DISPLAY is not set
+
+#[cfg(all(feature = "dev", feature = "async"))]
+mod async_tests {
+    use rustautogui::core::polling::poll_async;
+    use rustautogui::{AsyncRustAutoGui, MatchMode, PollingPolicy};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    // counts ticks of a task running next to the tested future. On current thread runtime
+    // ticker only advances if the tested future yields to executor instead of blocking it
+    fn spawn_ticker() -> Arc<AtomicUsize> {
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&ticks);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        ticks
+    }
+
+    #[tokio::test]
+    async fn poll_times_out_without_blocking_executor() {
+        let ticks = spawn_ticker();
+        let mut attempts = 0;
+        let start = Instant::now();
+        let result: Option<()> = poll_async(
+            &PollingPolicy::fixed(Duration::from_millis(100)),
+            Duration::from_millis(250),
+            true,
+            || {
+                attempts += 1;
+                async { Ok(None) }
+            },
+        )
+        .await
+        .unwrap();
+        let elapsed = start.elapsed();
+        assert!(result.is_none());
+        assert!(elapsed >= Duration::from_millis(250));
+        assert!(elapsed < Duration::from_millis(400));
+        assert_eq!(attempts, 4);
+        // ticker kept running while poll was waiting between attempts
+        assert!(ticks.load(Ordering::SeqCst) >= 10);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn dropped_poll_stops_attempts() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&attempts);
+        let policy = PollingPolicy::fixed(Duration::from_millis(20));
+        let polling = poll_async(&policy, Duration::from_secs(10), true, move || {
+            let counter = Arc::clone(&counter);
+            async move {
+                // attempt runs on blocking pool, same as AsyncRustAutoGui::run
+                tokio::task::spawn_blocking(move || {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                })
+                .await
+                .unwrap();
+                Ok(None::<()>)
+            }
+        });
+        // future is dropped when outer timeout passes
+        assert!(tokio::time::timeout(Duration::from_millis(100), polling)
+            .await
+            .is_err());
+        let attempts_after_drop = attempts.load(Ordering::SeqCst);
+        assert!(attempts_after_drop >= 2);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(attempts.load(Ordering::SeqCst), attempts_after_drop);
+    }
+
+    fn gui() -> Option<AsyncRustAutoGui> {
+        #[cfg(target_os = "linux")]
+        if std::env::var_os("DISPLAY").is_none() {
+            return None;
+        }
+        Some(AsyncRustAutoGui::new(false).unwrap())
+    }
+
+    #[tokio::test]
+    async fn wait_functions_respect_timeout() {
+        let Some(gui) = gui() else { return };
+        // template that is not expected on screen
+        gui.run(|gui| {
+            gui.store_template_from_file(
+                "tests/testing_images/algorithm_tests/Darts_template1.png",
+                None,
+                MatchMode::Segmented,
+                "darts",
+            )
+        })
+        .await
+        .unwrap();
+        let ticks = spawn_ticker();
+
+        let start = Instant::now();
+        let vanished = gui
+            .wait_until_image_vanishes(0.99, Duration::from_secs(5), "darts")
+            .await
+            .unwrap();
+        assert!(vanished);
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let start = Instant::now();
+        assert!(gui
+            .wait_for_image(0.99, Duration::from_millis(300), "darts")
+            .await
+            .is_err());
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(300));
+        // last attempt may still be running when timeout passes
+        assert!(elapsed < Duration::from_secs(3));
+        assert!(ticks.load(Ordering::SeqCst) >= 10);
+    }
+}
@@ -0,0 +1,150 @@
+// run with cargo test --tests --release --features dev -- --nocapture
+
+#[cfg(feature = "dev")]
+mod debug_artifacts_tests {
+    use rustautogui::core::debug_artifacts::{
+        best_candidate, save_found_artifacts, save_match_artifacts, CorrelationMap,
+    };
+    use rustautogui::core::template_match::{fft_ncc, segmented_ncc};
+    use rustautogui::data::PreparedData;
+    use rustautogui::imgtools;
+
+    fn check_artifacts(prepared_data: PreparedData, directory_name: &str) {
+        let image =
+            imgtools::load_image_bw("tests/testing_images/algorithm_tests/Darts_main.png").unwrap();
+        let correlation_map = CorrelationMap::compute(&image, &prepared_data).unwrap();
+        let (template_width, template_height) = match &prepared_data {
+            PreparedData::Segmented(data) => (data.template_width, data.template_height),
+            PreparedData::FFT(data) | PreparedData::Edge(data) => {
+                (data.template_width, data.template_height)
+            }
+            _ => unreachable!(),
+        };
+        assert_eq!(correlation_map.width, image.width() - template_width + 1);
+        assert_eq!(correlation_map.height, image.height() - template_height + 1);
+
+        let candidates = correlation_map.best_candidates((template_width, template_height), 10);
+        assert_eq!((candidates[0].0, candidates[0].1), (206, 1));
+        assert!(candidates[0].2 > 0.9);
+        // sorted and not overlapping with the best match
+        assert!(candidates.windows(2).all(|pair| pair[0].2 >= pair[1].2));
+        assert!(candidates[1..].iter().all(|(x, y, _)| {
+            (x.abs_diff(206) >= template_width / 2) | (y.abs_diff(1) >= template_height / 2)
+        }));
+
+        let directory = std::env::temp_dir().join(directory_name);
+        let _ = std::fs::remove_dir_all(&directory);
+        let (annotated_path, heatmap_path) =
+            save_match_artifacts(&directory, "darts/1", &image, &prepared_data, 0.9)
+                .unwrap()
+                .unwrap();
+        assert!(annotated_path
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .ends_with("_darts_1_matches.png"));
+        assert_eq!(
+            image::open(&annotated_path).unwrap().to_rgb8().dimensions(),
+            image.dimensions()
+        );
+        assert_eq!(
+            image::open(&heatmap_path).unwrap().to_rgb8().dimensions(),
+            (correlation_map.width, correlation_map.height)
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn artifacts_segmented() {
+        let template =
+            imgtools::load_image_bw("tests/testing_images/algorithm_tests/Darts_template1.png")
+                .unwrap();
+        let prepared_data = segmented_ncc::prepare_template_picture(&template, &false, None);
+        check_artifacts(prepared_data, "rustautogui_debug_artifacts_segmented");
+    }
+
+    #[test]
+    fn artifacts_fft() {
+        let image =
+            imgtools::load_image_bw("tests/testing_images/algorithm_tests/Darts_main.png").unwrap();
+        let template =
+            imgtools::load_image_bw("tests/testing_images/algorithm_tests/Darts_template1.png")
+                .unwrap();
+        let prepared_data = PreparedData::FFT(fft_ncc::prepare_template_picture(
+            &template,
+            image.width(),
+            image.height(),
+        ));
+        check_artifacts(prepared_data, "rustautogui_debug_artifacts_fft");
+    }
+
+    #[test]
+    fn found_artifacts_segmented() {
+        let image =
+            imgtools::load_image_bw("tests/testing_images/algorithm_tests/Darts_main.png").unwrap();
+        let template =
+            imgtools::load_image_bw("tests/testing_images/algorithm_tests/Darts_template1.png")
+                .unwrap();
+        let prepared_data = segmented_ncc::prepare_template_picture(&template, &false, None);
+        let directory = std::env::temp_dir().join("rustautogui_debug_artifacts_found");
+        let _ = std::fs::remove_dir_all(&directory);
+        let annotated_path = save_found_artifacts(
+            &directory,
+            "darts",
+            &image,
+            &prepared_data,
+            &[(206, 1, 0.95)],
+        )
+        .unwrap()
+        .unwrap();
+        let annotated = image::open(&annotated_path).unwrap().to_rgb8();
+        assert_eq!(annotated.dimensions(), image.dimensions());
+        // found location drawn in green, bottom edge is below the label
+        assert_eq!(
+            annotated.get_pixel(206, template.height()),
+            &image::Rgb([0, 220, 0])
+        );
+        // no heatmap is saved without computing correlation map
+        assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 1);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn best_candidate_drawn_when_nothing_found() {
+        let image =
+            imgtools::load_image_bw("tests/testing_images/algorithm_tests/Darts_main.png").unwrap();
+        let template =
+            imgtools::load_image_bw("tests/testing_images/algorithm_tests/Darts_template1.png")
+                .unwrap();
+        let prepared_data = segmented_ncc::prepare_template_picture(&template, &false, None);
+        let (x, y, _) = best_candidate(&image, &prepared_data).unwrap();
+        assert_eq!((x, y), (206, 1));
+
+        let directory = std::env::temp_dir().join("rustautogui_debug_artifacts_not_found");
+        let _ = std::fs::remove_dir_all(&directory);
+        // search with too high precision found nothing
+        let annotated_path = save_found_artifacts(&directory, "darts", &image, &prepared_data, &[])
+            .unwrap()
+            .unwrap();
+        let annotated = image::open(&annotated_path).unwrap().to_rgb8();
+        // best match drawn in red as not found
+        assert_eq!(
+            annotated.get_pixel(206, template.height()),
+            &image::Rgb([230, 0, 0])
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn no_artifacts_without_prepared_data() {
+        let image = image::GrayImage::new(20, 20);
+        let directory = std::env::temp_dir().join("rustautogui_debug_artifacts_none");
+        let saved =
+            save_match_artifacts(&directory, "template", &image, &PreparedData::None, 0.9).unwrap();
+        assert!(saved.is_none());
+        let saved =
+            save_found_artifacts(&directory, "template", &image, &PreparedData::None, &[]).unwrap();
+        assert!(saved.is_none());
+    }
+}
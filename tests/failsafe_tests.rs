@@ -0,0 +1,67 @@
+#[cfg(feature = "dev")]
+pub mod failsafe_tests {
+    use rustautogui::core::failsafe::{FailSafe, ScreenCorner};
+    use rustautogui::{errors::AutoGuiError, MouseClick, RustAutoGui};
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn corner_detection() {
+        let mut failsafe = FailSafe::new();
+        // disabled by default
+        assert!(!failsafe.is_in_corner((0, 0), (1920, 1080)));
+
+        failsafe.corners = vec![ScreenCorner::TopLeft, ScreenCorner::BottomRight];
+        assert!(failsafe.is_in_corner((0, 0), (1920, 1080)));
+        assert!(failsafe.is_in_corner((1919, 1079), (1920, 1080)));
+        assert!(!failsafe.is_in_corner((1919, 0), (1920, 1080)));
+        assert!(!failsafe.is_in_corner((1, 0), (1920, 1080)));
+
+        failsafe.corner_size = 10;
+        assert!(failsafe.is_in_corner((9, 9), (1920, 1080)));
+        assert!(failsafe.is_in_corner((1910, 1070), (1920, 1080)));
+        assert!(!failsafe.is_in_corner((10, 0), (1920, 1080)));
+    }
+
+    #[test]
+    fn switch_triggers_and_resets() {
+        let failsafe = FailSafe::new();
+        let switch = failsafe.switch();
+        assert!(!failsafe.is_triggered());
+
+        std::thread::spawn(move || switch.store(true, Ordering::SeqCst))
+            .join()
+            .unwrap();
+        assert!(failsafe.is_triggered());
+        // clones share the same state
+        assert!(failsafe.clone().is_triggered());
+
+        failsafe.reset();
+        assert!(!failsafe.is_triggered());
+    }
+
+    #[test]
+    fn release_works_after_trigger() {
+        // needs X display
+        #[cfg(target_os = "linux")]
+        if std::env::var_os("DISPLAY").is_none() {
+            return;
+        }
+        let gui = RustAutoGui::new(false).unwrap();
+        gui.key_down("shift").unwrap();
+        gui.failsafe_switch().store(true, Ordering::SeqCst);
+
+        assert!(matches!(
+            gui.key_down("shift"),
+            Err(AutoGuiError::FailSafeTriggered)
+        ));
+        assert!(matches!(
+            gui.click(MouseClick::LEFT),
+            Err(AutoGuiError::FailSafeTriggered)
+        ));
+        // held keys and buttons can still be released
+        gui.key_up("shift").unwrap();
+        gui.click_up(MouseClick::LEFT).unwrap();
+        assert!(gui.is_failsafe_triggered());
+        gui.reset_failsafe();
+    }
+}
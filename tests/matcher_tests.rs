@@ -0,0 +1,313 @@
+// run with cargo test --tests --release -- --nocapture
+
+#[cfg(not(feature = "lite"))]
+mod matcher_tests {
+    use rustautogui::{imgtools, FindStrategy, MatchMode, Matcher, PyramidSearch};
+
+    fn run_matcher(match_mode: MatchMode) {
+        let image =
+            imgtools::load_image_rgba("tests/testing_images/algorithm_tests/Darts_main.png")
+                .unwrap();
+        let template =
+            imgtools::load_image_rgba("tests/testing_images/algorithm_tests/Darts_template1.png")
+                .unwrap();
+        let matcher = Matcher::prepare(&template, match_mode).unwrap();
+        let (template_width, template_height) = matcher.template_size();
+        let found = matcher.find_in(&image, 0.95).unwrap().unwrap();
+        // matcher returns middle of the template
+        assert_eq!(found[0].0, 206 + template_width / 2);
+        assert_eq!(found[0].1, 1 + template_height / 2);
+    }
+
+    #[test]
+    fn matcher_segmented() {
+        run_matcher(MatchMode::Segmented);
+    }
+
+    #[test]
+    fn matcher_fft() {
+        run_matcher(MatchMode::FFT);
+    }
+
+    #[test]
+    fn matcher_template_larger_than_image() {
+        let template =
+            imgtools::load_image_bw("tests/testing_images/algorithm_tests/Darts_template1.png")
+                .unwrap();
+        let matcher = Matcher::prepare(&template, MatchMode::Segmented).unwrap();
+        let small_image = image::GrayImage::new(2, 2);
+        assert!(matcher.find_in(&small_image, 0.9).is_err());
+    }
+
+    fn run_best_match(match_mode: MatchMode) {
+        let image =
+            imgtools::load_image_rgba("tests/testing_images/algorithm_tests/Darts_main.png")
+                .unwrap();
+        let template =
+            imgtools::load_image_rgba("tests/testing_images/algorithm_tests/Darts_template1.png")
+                .unwrap();
+        let matcher = Matcher::prepare(&template, match_mode.clone()).unwrap();
+        let (template_width, template_height) = matcher.template_size();
+        let (x, y, corr) = matcher.find_best_in(&image).unwrap().unwrap();
+        assert_eq!((x, y), (206 + template_width / 2, 1 + template_height / 2));
+        // same location and correlation as the best result of regular search
+        let found = matcher.find_in(&image, 0.9).unwrap().unwrap();
+        assert_eq!((found[0].0, found[0].1), (x, y));
+        assert!((found[0].2 - corr).abs() < 1e-4);
+
+        // template from another image is not found, but best candidate is still returned
+        let socket_template =
+            imgtools::load_image_rgba("tests/testing_images/algorithm_tests/Socket_template1.png")
+                .unwrap();
+        let matcher = Matcher::prepare(&socket_template, match_mode).unwrap();
+        let (_, _, corr) = matcher.find_best_in(&image).unwrap().unwrap();
+        assert!(corr < 0.95);
+        assert!(matcher.find_in(&image, 0.95).unwrap().is_none());
+    }
+
+    #[test]
+    fn best_match_segmented() {
+        run_best_match(MatchMode::Segmented);
+    }
+
+    #[test]
+    fn best_match_fft() {
+        run_best_match(MatchMode::FFT);
+    }
+
+    #[test]
+    fn concurrent_searches_with_changing_image_size() {
+        let image =
+            imgtools::load_image_bw("tests/testing_images/algorithm_tests/Darts_main.png").unwrap();
+        let template =
+            imgtools::load_image_bw("tests/testing_images/algorithm_tests/Darts_template1.png")
+                .unwrap();
+        let cropped = image::imageops::crop_imm(&image, 0, 0, 600, 400).to_image();
+        let matcher = Matcher::prepare(&template, MatchMode::FFT).unwrap();
+        let (template_width, template_height) = matcher.template_size();
+        let expected = (206 + template_width / 2, 1 + template_height / 2);
+        // FFT data is prepared again when size changes, while other searches still use old one
+        std::thread::scope(|scope| {
+            for i in 0..4 {
+                let searched = if i % 2 == 0 { &image } else { &cropped };
+                let matcher = &matcher;
+                scope.spawn(move || {
+                    for _ in 0..3 {
+                        let found = matcher.find_in(searched, 0.95).unwrap().unwrap();
+                        assert_eq!((found[0].0, found[0].1), expected);
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn find_strategies() {
+        let image =
+            imgtools::load_image_rgba("tests/testing_images/algorithm_tests/Darts_main.png")
+                .unwrap();
+        let template =
+            imgtools::load_image_rgba("tests/testing_images/algorithm_tests/Darts_template1.png")
+                .unwrap();
+        for match_mode in [MatchMode::Segmented, MatchMode::FFT] {
+            let matcher = Matcher::prepare(&template, match_mode.clone()).unwrap();
+            let all = matcher
+                .find_in_with_strategy(&image, 0.8, FindStrategy::All)
+                .unwrap()
+                .unwrap();
+            assert_eq!(all, matcher.find_in(&image, 0.8).unwrap().unwrap());
+            let best = matcher
+                .find_in_with_strategy(&image, 0.8, FindStrategy::Best)
+                .unwrap()
+                .unwrap();
+            assert_eq!(best, vec![all[0]]);
+            // first found can be any of the positions above precision
+            let first = matcher
+                .find_in_with_strategy(&image, 0.8, FindStrategy::First)
+                .unwrap()
+                .unwrap();
+            assert_eq!(first.len(), 1);
+            assert!(all
+                .iter()
+                .any(|found| (found.0, found.1) == (first[0].0, first[0].1)));
+            // nothing is found by first when nothing is found at all
+            let socket_template = imgtools::load_image_rgba(
+                "tests/testing_images/algorithm_tests/Socket_template1.png",
+            )
+            .unwrap();
+            let matcher = Matcher::prepare(&socket_template, match_mode).unwrap();
+            assert!(matcher.find_in(&image, 0.95).unwrap().is_none());
+            assert!(matcher
+                .find_in_with_strategy(&image, 0.95, FindStrategy::First)
+                .unwrap()
+                .is_none());
+        }
+    }
+
+    #[test]
+    fn pyramid_search() {
+        let image =
+            imgtools::load_image_rgba("tests/testing_images/algorithm_tests/Darts_main.png")
+                .unwrap();
+        let template =
+            imgtools::load_image_rgba("tests/testing_images/algorithm_tests/Darts_template1.png")
+                .unwrap();
+        for match_mode in [MatchMode::Segmented, MatchMode::FFT] {
+            let regular = Matcher::prepare(&template, match_mode.clone())
+                .unwrap()
+                .find_in(&image, 0.95)
+                .unwrap()
+                .unwrap();
+            for factor in [2, 4] {
+                let matcher = Matcher::prepare_pyramid(
+                    &template,
+                    match_mode.clone(),
+                    PyramidSearch::new(factor),
+                )
+                .unwrap();
+                let found = matcher.find_in(&image, 0.95).unwrap().unwrap();
+                // verification at full resolution gives the same best match as regular search
+                assert_eq!((found[0].0, found[0].1), (regular[0].0, regular[0].1));
+                assert!((found[0].2 - regular[0].2).abs() < 1e-4);
+            }
+        }
+        // only factors 2 and 4 are supported
+        assert!(
+            Matcher::prepare_pyramid(&template, MatchMode::Segmented, PyramidSearch::new(3))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn auto_match_mode() {
+        let image =
+            imgtools::load_image_rgba("tests/testing_images/algorithm_tests/Darts_main.png")
+                .unwrap();
+        let template =
+            imgtools::load_image_rgba("tests/testing_images/algorithm_tests/Darts_template1.png")
+                .unwrap();
+        let matcher =
+            Matcher::prepare_for_size(&template, MatchMode::Auto, image.width(), image.height())
+                .unwrap();
+        let choice = matcher.auto_mode_choice().unwrap();
+        assert!([MatchMode::Segmented, MatchMode::FFT].contains(&choice.match_mode));
+        assert_eq!(matcher.match_mode(), choice.match_mode);
+        assert_eq!(choice.region_size, image.dimensions());
+        // when benchmark ran, picked mode is the fastest one
+        if let Some(fastest) = choice.timings.iter().min_by_key(|(_, timing)| *timing) {
+            assert_eq!(fastest.0, choice.match_mode);
+        }
+        let (template_width, template_height) = matcher.template_size();
+        let found = matcher.find_in(&image, 0.95).unwrap().unwrap();
+        assert_eq!(
+            (found[0].0, found[0].1),
+            (206 + template_width / 2, 1 + template_height / 2)
+        );
+
+        // without image size choice is made by estimate only
+        let matcher = Matcher::prepare(&template, MatchMode::Auto).unwrap();
+        assert!(matcher.auto_mode_choice().unwrap().timings.is_empty());
+        assert!(Matcher::prepare(&template, MatchMode::FFT)
+            .unwrap()
+            .auto_mode_choice()
+            .is_none());
+    }
+
+    #[test]
+    fn matcher_edge() {
+        run_matcher(MatchMode::Edge);
+    }
+
+    #[test]
+    fn edge_matches_inverted_theme() {
+        let mut dark_image =
+            imgtools::load_image_bw("tests/testing_images/algorithm_tests/Darts_main.png").unwrap();
+        image::imageops::invert(&mut dark_image);
+        let template =
+            imgtools::load_image_bw("tests/testing_images/algorithm_tests/Darts_template1.png")
+                .unwrap();
+
+        // raw luminance correlation of inverted image is negative
+        let fft_matcher = Matcher::prepare(&template, MatchMode::FFT).unwrap();
+        assert!(fft_matcher.find_in(&dark_image, 0.8).unwrap().is_none());
+
+        let edge_matcher = Matcher::prepare(&template, MatchMode::Edge).unwrap();
+        let (template_width, template_height) = edge_matcher.template_size();
+        let found = edge_matcher.find_in(&dark_image, 0.8).unwrap().unwrap();
+        assert_eq!(
+            (found[0].0, found[0].1),
+            (206 + template_width / 2, 1 + template_height / 2)
+        );
+    }
+
+    fn assert_near(actual: (f32, f32), expected: (f32, f32)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 4.0 && (actual.1 - expected.1).abs() < 4.0,
+            "{:?} not near {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn matcher_features() {
+        let image =
+            imgtools::load_image_bw("tests/testing_images/algorithm_tests/Darts_main.png").unwrap();
+        let template =
+            imgtools::load_image_bw("tests/testing_images/algorithm_tests/Darts_template1.png")
+                .unwrap();
+        let (width, height) = (template.width() as f32, template.height() as f32);
+        let matcher = Matcher::prepare(&template, MatchMode::Features).unwrap();
+        let found = matcher.find_features_in(&image, 0.5).unwrap().unwrap();
+        assert_near(found.corners[0], (206.0, 1.0));
+        assert_near(found.corners[2], (206.0 + width, 1.0 + height));
+        let located = matcher.find_in(&image, 0.5).unwrap().unwrap();
+        assert_eq!(located, vec![(found.center.0, found.center.1, found.score)]);
+
+        // screen scaled down, so template appears smaller
+        let scaled_image = image::imageops::resize(
+            &image,
+            image.width() * 4 / 5,
+            image.height() * 4 / 5,
+            image::imageops::FilterType::Triangle,
+        );
+        let found = matcher
+            .find_features_in(&scaled_image, 0.5)
+            .unwrap()
+            .unwrap();
+        assert_near(found.corners[0], (206.0 * 0.8, 0.8));
+        assert_near(
+            found.corners[2],
+            ((206.0 + width) * 0.8, (1.0 + height) * 0.8),
+        );
+
+        // part of template covered
+        let mut covered_image = image.clone();
+        for x in 206..206 + template.width() / 2 {
+            for y in 1..1 + template.height() / 2 {
+                covered_image.put_pixel(x, y, image::Luma([0]));
+            }
+        }
+        let found = matcher
+            .find_features_in(&covered_image, 0.5)
+            .unwrap()
+            .unwrap();
+        assert_near(found.corners[2], (206.0 + width, 1.0 + height));
+    }
+
+    #[test]
+    fn features_template_from_another_image() {
+        let image =
+            imgtools::load_image_bw("tests/testing_images/algorithm_tests/Darts_main.png").unwrap();
+        let template =
+            imgtools::load_image_bw("tests/testing_images/algorithm_tests/Socket_template1.png")
+                .unwrap();
+        let matcher = Matcher::prepare(&template, MatchMode::Features).unwrap();
+        assert!(matcher.find_in(&image, 0.5).unwrap().is_none());
+        assert!(matcher.find_features_in(&image, 0.5).unwrap().is_none());
+
+        // flat template has no corners to match
+        let flat_template = image::GrayImage::from_pixel(100, 100, image::Luma([128]));
+        assert!(Matcher::prepare(&flat_template, MatchMode::Features).is_err());
+    }
+}
@@ -6,7 +6,7 @@ pub mod mouse_tests {
 
     #[test]
     fn execute_tests() {
-        let mut gui = rustautogui::RustAutoGui::new(false).unwrap();
+        let gui = rustautogui::RustAutoGui::new(false).unwrap();
         let (s_w, s_h) = gui.get_screen_size();
 
         let center_x = (s_w / 2) as u32;
@@ -24,4 +24,24 @@ pub mod mouse_tests {
         gui.move_mouse_to(None, Some(s_h as u32 / 2), 0.5).unwrap();
         gui.move_mouse(s_w - 1, 0, 1.5).unwrap();
     }
+
+    #[test]
+    fn dry_run_tracks_virtual_cursor() {
+        let mut gui = rustautogui::RustAutoGui::new(false).unwrap();
+        let real_position = gui.get_mouse_position().unwrap();
+
+        gui.set_dry_run(true);
+        gui.set_pause(std::time::Duration::from_millis(50));
+        let start = std::time::Instant::now();
+        gui.move_mouse_to_pos(10, 10, 0.0).unwrap();
+        gui.move_mouse(5, 5, 0.0).unwrap();
+        gui.left_click().unwrap();
+        // pause is inserted after every action, also in dry run
+        assert!(start.elapsed() >= std::time::Duration::from_millis(150));
+        assert_eq!(gui.get_mouse_position().unwrap(), (15, 15));
+
+        // real mouse did not move
+        gui.set_dry_run(false);
+        assert_eq!(gui.get_mouse_position().unwrap(), real_position);
+    }
 }
@@ -3,20 +3,38 @@
 #[cfg(feature = "dev")]
 mod multi_test {
     use rustautogui::{self, imgtools, RustAutoGui};
+    use std::time::Duration;
 
     #[test]
     fn main_test() {
         let mut gui = rustautogui::RustAutoGui::new(true).unwrap();
-        gui.list_devices();
+        for device in gui.list_devices() {
+            println!("{}", device.print_device());
+        }
         load_imgs(&mut gui);
-        gui.loop_find_stored_image_on_screen_and_move_mouse(0.9, 1.0, 10, "step_0")
-            .unwrap();
+        gui.loop_find_stored_image_on_screen_and_move_mouse(
+            0.9,
+            1.0,
+            Duration::from_secs(10),
+            "step_0",
+        )
+        .unwrap();
         gui.left_click().unwrap();
-        gui.loop_find_stored_image_on_screen_and_move_mouse(0.9, 0.5, 10, "step_1")
-            .unwrap();
+        gui.loop_find_stored_image_on_screen_and_move_mouse(
+            0.9,
+            0.5,
+            Duration::from_secs(10),
+            "step_1",
+        )
+        .unwrap();
         gui.left_click().unwrap();
-        gui.loop_find_stored_image_on_screen_and_move_mouse(0.9, 1.5, 10, "step_2")
-            .unwrap();
+        gui.loop_find_stored_image_on_screen_and_move_mouse(
+            0.9,
+            1.5,
+            Duration::from_secs(10),
+            "step_2",
+        )
+        .unwrap();
         gui.left_click().unwrap();
         gui.keyboard_input("test!@#45<>/\\|{}[]&*()_+").unwrap();
     }
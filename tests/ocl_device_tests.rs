@@ -0,0 +1,90 @@
+// run with cargo test --tests --release --features opencl -- --nocapture
+
+#[cfg(feature = "opencl")]
+mod ocl_device_tests {
+    use rustautogui::{OclDeviceSelector, OclDeviceType, RustAutoGui};
+
+    #[test]
+    fn parse_selector() {
+        assert_eq!(OclDeviceSelector::parse("1"), OclDeviceSelector::Index(1));
+        assert_eq!(
+            OclDeviceSelector::parse(" GPU "),
+            OclDeviceSelector::Type(OclDeviceType::Gpu)
+        );
+        assert_eq!(
+            OclDeviceSelector::parse("cpu"),
+            OclDeviceSelector::Type(OclDeviceType::Cpu)
+        );
+        assert_eq!(
+            OclDeviceSelector::parse("vendor:NVIDIA"),
+            OclDeviceSelector::Vendor("NVIDIA".to_string())
+        );
+        assert_eq!(
+            OclDeviceSelector::parse("name:^gfx\\d+"),
+            OclDeviceSelector::NameRegex("^gfx\\d+".to_string())
+        );
+        assert_eq!(
+            OclDeviceSelector::parse("GeForce"),
+            OclDeviceSelector::NameRegex("GeForce".to_string())
+        );
+    }
+
+    #[test]
+    fn selector_from_env() {
+        // same parsing RustAutoGui::new() uses. Other tests only fall back to the best device
+        // if they read the variable meanwhile
+        const OCL_DEVICE_ENV: &str = "RUSTAUTOGUI_OCL_DEVICE";
+        std::env::set_var(OCL_DEVICE_ENV, " vendor:Intel ");
+        assert_eq!(
+            OclDeviceSelector::from_env().unwrap(),
+            Some(OclDeviceSelector::Vendor("Intel".to_string()))
+        );
+        std::env::set_var(OCL_DEVICE_ENV, "  ");
+        assert!(OclDeviceSelector::from_env().is_err());
+        std::env::remove_var(OCL_DEVICE_ENV);
+        assert_eq!(OclDeviceSelector::from_env().unwrap(), None);
+    }
+
+    #[test]
+    fn select_device() {
+        let mut gui = RustAutoGui::new(false).unwrap();
+        let devices = gui.list_devices().to_vec();
+        if devices.is_empty() {
+            // no OpenCL platform, CPU only
+            assert!(gui.selected_ocl_device().is_none());
+            assert!(gui.select_ocl_device(OclDeviceSelector::Best).is_err());
+            return;
+        }
+        let last = devices.last().unwrap();
+        gui.select_ocl_device(OclDeviceSelector::Index(last.index))
+            .unwrap();
+        assert_eq!(gui.selected_ocl_device().unwrap().index, last.index);
+        gui.select_ocl_device(OclDeviceSelector::NameRegex(regex_escape(&last.name)))
+            .unwrap();
+        assert_eq!(gui.selected_ocl_device().unwrap().name, last.name);
+        gui.select_ocl_device(OclDeviceSelector::Type(last.device_type))
+            .unwrap();
+        assert_eq!(
+            gui.selected_ocl_device().unwrap().device_type,
+            last.device_type
+        );
+        assert!(gui
+            .select_ocl_device(OclDeviceSelector::Index(devices.len() as u32))
+            .is_err());
+        assert!(gui
+            .select_ocl_device(OclDeviceSelector::NameRegex("(".to_string()))
+            .is_err());
+    }
+
+    fn regex_escape(text: &str) -> String {
+        text.chars()
+            .map(|c| {
+                if c.is_ascii_punctuation() {
+                    format!("\\{}", c)
+                } else {
+                    c.to_string()
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,74 @@
+// run with cargo test --tests --release -- --nocapture
+
+#[cfg(feature = "dev")]
+mod polling_tests {
+    use rustautogui::core::polling::PollingState;
+    use rustautogui::PollingPolicy;
+    use std::time::Duration;
+
+    fn sleeps(policy: PollingPolicy, count: usize) -> Vec<Option<Duration>> {
+        // no timeout, so sleeps only depend on policy
+        let mut state = PollingState::new(&policy, Duration::ZERO, true);
+        (0..count).map(|_| state.next_sleep()).collect()
+    }
+
+    fn ms(millis: u64) -> Option<Duration> {
+        Some(Duration::from_millis(millis))
+    }
+
+    #[test]
+    fn fixed_interval() {
+        let policy = PollingPolicy::fixed(Duration::from_millis(50));
+        assert_eq!(policy, PollingPolicy::default());
+        assert_eq!(sleeps(policy, 3), vec![ms(50), ms(50), ms(50)]);
+    }
+
+    #[test]
+    fn backoff_grows_until_max_interval() {
+        let policy =
+            PollingPolicy::backoff(Duration::from_millis(10), 2.0, Duration::from_millis(100));
+        assert_eq!(
+            sleeps(policy, 6),
+            vec![ms(10), ms(20), ms(40), ms(80), ms(100), ms(100)]
+        );
+    }
+
+    #[test]
+    fn backoff_arguments_are_clamped() {
+        // factor below 1 would shrink interval, and max interval below initial one would cut it
+        let policy =
+            PollingPolicy::backoff(Duration::from_millis(30), 0.5, Duration::from_millis(10));
+        assert_eq!(policy.backoff_factor, 1.0);
+        assert_eq!(policy.max_interval, Duration::from_millis(30));
+        assert_eq!(sleeps(policy, 2), vec![ms(30), ms(30)]);
+    }
+
+    #[test]
+    fn overflowing_backoff_is_capped() {
+        let policy = PollingPolicy::backoff(Duration::from_secs(1), 1e20, Duration::from_secs(10));
+        assert_eq!(sleeps(policy, 3), vec![ms(1000), ms(10000), ms(10000)]);
+
+        let mut policy = PollingPolicy::fixed(Duration::from_secs(1));
+        policy.backoff_factor = f32::INFINITY;
+        policy.max_interval = Duration::from_secs(5);
+        assert_eq!(sleeps(policy, 2), vec![ms(1000), ms(5000)]);
+    }
+
+    #[test]
+    fn max_attempts_ends_polling() {
+        let policy = PollingPolicy::fixed(Duration::from_millis(1)).with_max_attempts(3);
+        // sleeps only between attempts, none after the third one
+        assert_eq!(sleeps(policy, 4), vec![ms(1), ms(1), None, None]);
+    }
+
+    #[test]
+    fn last_sleep_is_clamped_to_timeout() {
+        let policy = PollingPolicy::fixed(Duration::from_secs(10));
+        let mut state = PollingState::new(&policy, Duration::from_millis(100), true);
+        let sleep = state.next_sleep().unwrap();
+        assert!(sleep <= Duration::from_millis(100));
+        assert!(sleep > Duration::from_millis(50));
+        std::thread::sleep(sleep);
+        assert_eq!(state.next_sleep(), None);
+    }
+}
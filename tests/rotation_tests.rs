@@ -0,0 +1,111 @@
+// run with cargo test --tests --release --features dev -- --nocapture
+
+#[cfg(not(feature = "lite"))]
+mod rotation_tests {
+    use rustautogui::RotationRange;
+
+    #[test]
+    fn rotation_range_angles() {
+        assert_eq!(
+            RotationRange::new(-30.0, 30.0, 15.0).angles().unwrap(),
+            vec![-30.0, -15.0, 0.0, 15.0, 30.0]
+        );
+        // max angle is not reached by whole steps
+        assert_eq!(
+            RotationRange::new(0.0, 10.0, 4.0).angles().unwrap(),
+            vec![0.0, 4.0, 8.0]
+        );
+        assert_eq!(
+            RotationRange::new(5.0, 5.0, 1.0).angles().unwrap(),
+            vec![5.0]
+        );
+        assert!(RotationRange::new(0.0, 10.0, 0.0).angles().is_err());
+        assert!(RotationRange::new(10.0, 0.0, 1.0).angles().is_err());
+    }
+
+    #[cfg(feature = "dev")]
+    mod variants {
+        use rustautogui::core::template_match::rotation::rotate_template;
+        use rustautogui::{imgtools, MatchMode, Matcher, RotationRange};
+
+        #[test]
+        fn rotate_keeps_size_for_right_angles() {
+            let template =
+                imgtools::load_image_bw("tests/testing_images/algorithm_tests/Darts_template1.png")
+                    .unwrap();
+            let (width, height) = template.dimensions();
+            assert_eq!(rotate_template(&template, 0.0), template);
+            assert_eq!(
+                rotate_template(&template, 90.0).dimensions(),
+                (height, width)
+            );
+            assert_eq!(
+                rotate_template(&template, 180.0).dimensions(),
+                (width, height)
+            );
+            // counter clockwise rotation moves top right corner to top left
+            let rotated = rotate_template(&template, 90.0);
+            assert_eq!(rotated.get_pixel(0, 0), template.get_pixel(width - 1, 0));
+            let rotated = rotate_template(&template, 45.0);
+            assert!(rotated.width() > width);
+            assert!(rotated.height() > height);
+        }
+
+        fn best_angle(match_mode: MatchMode) -> f32 {
+            let image =
+                imgtools::load_image_bw("tests/testing_images/algorithm_tests/Darts_main.png")
+                    .unwrap();
+            let template =
+                imgtools::load_image_bw("tests/testing_images/algorithm_tests/Darts_template1.png")
+                    .unwrap();
+            // whole image rotated, so template appears rotated by the same angle
+            let rotated_image = rotate_template(&image, 20.0);
+            let mut best: Option<(f32, f32)> = None;
+            for angle in RotationRange::new(-40.0, 40.0, 10.0).angles().unwrap() {
+                let variant = rotate_template(&template, angle);
+                let matcher = Matcher::prepare(&variant, match_mode.clone()).unwrap();
+                let (_, _, corr) = matcher.find_best_in(&rotated_image).unwrap().unwrap();
+                if best.is_none_or(|(_, best_corr)| corr > best_corr) {
+                    best = Some((angle, corr));
+                }
+            }
+            best.unwrap().0
+        }
+
+        #[test]
+        fn rotated_variant_segmented() {
+            assert_eq!(best_angle(MatchMode::Segmented), 20.0);
+        }
+
+        #[test]
+        fn rotated_variant_fft() {
+            assert_eq!(best_angle(MatchMode::FFT), 20.0);
+        }
+
+        #[test]
+        fn corners_lower_exact_match_correlation() {
+            let image =
+                imgtools::load_image_bw("tests/testing_images/algorithm_tests/Darts_main.png")
+                    .unwrap();
+            let template =
+                imgtools::load_image_bw("tests/testing_images/algorithm_tests/Darts_template1.png")
+                    .unwrap();
+            let best_corr = |angle: f32| {
+                let matcher =
+                    Matcher::prepare(&rotate_template(&template, angle), MatchMode::FFT).unwrap();
+                let (_, _, corr) = matcher
+                    .find_best_in(&rotate_template(&image, angle))
+                    .unwrap()
+                    .unwrap();
+                corr
+            };
+            let (corr_0, corr_20, corr_45) = (best_corr(0.0), best_corr(20.0), best_corr(45.0));
+            // screen content under the corners adds to image variance, more of it closer to 45 degrees
+            assert!(corr_0 > 0.99);
+            assert!(corr_20 < corr_0);
+            assert!(corr_45 < corr_20);
+            // still found with lowered precision
+            assert!(corr_45 > 0.7);
+        }
+    }
+}
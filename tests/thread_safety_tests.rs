@@ -0,0 +1,36 @@
+// compile time checks that public structs can be moved and shared between threads
+
+mod thread_safety_tests {
+    use rustautogui::RustAutoGui;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn rustautogui_is_send_sync() {
+        assert_send_sync::<RustAutoGui>();
+    }
+
+    #[cfg(not(feature = "lite"))]
+    #[test]
+    fn template_matching_is_send_sync() {
+        assert_send_sync::<rustautogui::Matcher>();
+        assert_send_sync::<rustautogui::Frame>();
+        assert_send_sync::<rustautogui::PollingPolicy>();
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn async_gui_is_send_sync() {
+        assert_send_sync::<rustautogui::AsyncRustAutoGui>();
+    }
+
+    // futures have to be Send to be spawned on multi threaded tokio runtime
+    #[cfg(all(feature = "async", not(feature = "lite")))]
+    #[allow(dead_code)]
+    fn async_futures_are_send(gui: &rustautogui::AsyncRustAutoGui) {
+        fn assert_send<T: Send>(_: T) {}
+        assert_send(gui.wait_for_image(0.9, std::time::Duration::from_secs(1), "alias"));
+        assert_send(gui.wait_until_image_vanishes(0.9, std::time::Duration::from_secs(1), "alias"));
+        assert_send(gui.move_mouse_to_pos(0, 0, 1.0));
+    }
+}
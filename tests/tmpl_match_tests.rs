@@ -4,7 +4,7 @@
 pub mod tmpl_match_tests {
     use rustautogui::core::template_match::open_cl::OclVersion;
     use rustautogui::core::template_match::*;
-    use rustautogui::data::{opencl::KernelStorage, opencl::*, PreparedData};
+    use rustautogui::data::{opencl::KernelStorage, opencl::*, FFTData, PreparedData};
     use rustautogui::imgtools;
 
     #[test]
@@ -55,6 +55,341 @@ pub mod tmpl_match_tests {
         }
     }
 
+    #[test]
+    fn segmented_in_region_matches_full_search() {
+        // searching a region of precomputed integral images must give same location as searching whole image
+        let main_image =
+            imgtools::load_image_bw("tests/testing_images/algorithm_tests/Darts_main.png").unwrap();
+        let template =
+            imgtools::load_image_bw("tests/testing_images/algorithm_tests/Darts_template1.png")
+                .unwrap();
+        let template_data = match segmented_ncc::prepare_template_picture(&template, &false, None)
+        {
+            PreparedData::Segmented(data) => data,
+            _ => panic!(),
+        };
+        let full_locations =
+            segmented_ncc::fast_ncc_template_match(&main_image, 0.95, &template_data, &false);
+        let integral_image = IntegralImage::new(&main_image);
+        let region = (100, 0, main_image.width() - 100, main_image.height());
+        let mut region_locations = segmented_ncc::fast_ncc_template_match_in_region(
+            &integral_image,
+            region,
+            0.95,
+            &template_data,
+        );
+        region_locations.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap());
+        assert!(!region_locations.is_empty());
+        assert_eq!(region_locations[0].0 + region.0, full_locations[0].0);
+        assert_eq!(region_locations[0].1 + region.1, full_locations[0].1);
+    }
+
+    #[test]
+    fn segmented_batches_match_single_positions() {
+        // positions evaluated in SIMD batches must give exactly same correlations as one by one.
+        // Region widths leave a shorter batch at the end of every row
+        let searches = [
+            ("Darts_main.png", "Darts_template1.png", (170, 0, 301, 190)),
+            ("Socket_main.png", "Socket_template2.png", (37, 780, 301, 179)),
+        ];
+        for (image_name, template_name, region) in searches {
+            let main_image = imgtools::load_image_bw(&format!(
+                "tests/testing_images/algorithm_tests/{image_name}"
+            ))
+            .unwrap();
+            let integral_image = IntegralImage::new(&main_image);
+            let template = imgtools::load_image_bw(&format!(
+                "tests/testing_images/algorithm_tests/{template_name}"
+            ))
+            .unwrap();
+            let template_data =
+                match segmented_ncc::prepare_template_picture(&template, &false, None) {
+                    PreparedData::Segmented(data) => data,
+                    _ => panic!(),
+                };
+            for precision in [0.0, 0.9] {
+                let min_fast = precision * template_data.expected_corr_fast - 0.0001;
+                let min_slow = precision * template_data.expected_corr_slow - 0.0001;
+                let mut expected = Vec::new();
+                for y in 0..=region.3 - template_data.template_height {
+                    for x in 0..=region.2 - template_data.template_width {
+                        let corr = segmented_ncc::correlation_at(
+                            &integral_image,
+                            &template_data,
+                            region.0 + x,
+                            region.1 + y,
+                            min_fast,
+                        );
+                        if corr >= min_slow {
+                            expected.push((x, y, corr));
+                        }
+                    }
+                }
+                assert!(!expected.is_empty());
+                // scalar fallback is checked as well, it gives same results so other tests are unaffected
+                for simd_enabled in [false, true] {
+                    segmented_simd::set_simd_enabled(simd_enabled);
+                    let mut found = segmented_ncc::fast_ncc_template_match_in_region(
+                        &integral_image,
+                        region,
+                        precision,
+                        &template_data,
+                    );
+                    found.sort_by_key(|&(x, y, _)| (y, x));
+                    assert_eq!(found, expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn segmented_best_match_is_exhaustive_maximum() {
+        // best match has to be the maximum of detailed correlation over all positions,
+        // same on every run no matter how threads are scheduled. Second template is not in the image,
+        // and its best match has higher detailed correlation than positions with better rough one
+        let searches = [
+            ("Socket_main.png", "Socket_template2.png", (0, 650, 539, 300)),
+            ("Socket_main.png", "Darts_template3.png", (0, 0, 539, 400)),
+        ];
+        for (image_name, template_name, region) in searches {
+            let main_image = imgtools::load_image_bw(&format!(
+                "tests/testing_images/algorithm_tests/{image_name}"
+            ))
+            .unwrap();
+            let integral_image = IntegralImage::new(&main_image);
+            let template = imgtools::load_image_bw(&format!(
+                "tests/testing_images/algorithm_tests/{template_name}"
+            ))
+            .unwrap();
+            let template_data =
+                match segmented_ncc::prepare_template_picture(&template, &false, None) {
+                    PreparedData::Segmented(data) => data,
+                    _ => panic!(),
+                };
+            let mut expected = (0, 0, f32::NEG_INFINITY);
+            for y in 0..=region.3 - template_data.template_height {
+                for x in 0..=region.2 - template_data.template_width {
+                    let corr = segmented_ncc::correlation_at(
+                        &integral_image,
+                        &template_data,
+                        region.0 + x,
+                        region.1 + y,
+                        f32::NEG_INFINITY,
+                    );
+                    if (corr > -100.0) & (corr > expected.2) {
+                        expected = (x, y, corr);
+                    }
+                }
+            }
+            for _ in 0..10 {
+                let best = segmented_ncc::fast_ncc_best_match_in_region(
+                    &integral_image,
+                    region,
+                    &template_data,
+                )
+                .unwrap();
+                assert_eq!(best.2, expected.2);
+                // equal correlation at another position is fine as well
+                let corr_at_best = segmented_ncc::correlation_at(
+                    &integral_image,
+                    &template_data,
+                    region.0 + best.0,
+                    region.1 + best.1,
+                    f32::NEG_INFINITY,
+                );
+                assert_eq!(corr_at_best, expected.2);
+            }
+        }
+    }
+
+    #[test]
+    fn segment_sums_above_signed_range() {
+        // white segment of 9 million pixels sums above 2^31, which signed conversion would turn negative
+        let image = image::GrayImage::from_pixel(
+            3000 + segmented_simd::LANES as u32,
+            3000,
+            image::Luma([255]),
+        );
+        let integral_image = IntegralImage::new(&image);
+        let segments = [(0, 0, 3000, 3000, 1.0)];
+        let mean_image = [0.0; segmented_simd::LANES];
+        let mut results = Vec::new();
+        for simd_enabled in [false, true] {
+            segmented_simd::set_simd_enabled(simd_enabled);
+            results.push(segmented_simd::segment_nominators(
+                &integral_image,
+                0,
+                0,
+                &segments,
+                0.0,
+                &mean_image,
+            ));
+        }
+        assert_eq!(
+            results[0],
+            [(9_000_000u32 * 255) as f32; segmented_simd::LANES]
+        );
+        assert_eq!(results[0], results[1]);
+    }
+
+    #[test]
+    fn ocl_integral_images_match_cpu() {
+        use rustautogui::RustAutoGui;
+
+        let data = RustAutoGui::dev_setup_opencl(None).unwrap();
+        let max_threads = data.ocl_workgroup_size as usize;
+        // odd width that threads split unevenly, single pixel wide image, and image narrower
+        // than thread count, which leaves some threads of the row without pixels
+        for (image_width, image_height) in [(333, 17), (1, 9), (5, 4)] {
+            let image = image::GrayImage::from_fn(image_width, image_height, |x, y| {
+                image::Luma([((x * 31 + y * 17 + x * y) % 256) as u8])
+            });
+            let mut expected = vec![0u64; (image_width * image_height) as usize];
+            let mut expected_squared = expected.clone();
+            for y in 0..image_height {
+                let mut row_sum = 0u64;
+                let mut row_squared_sum = 0u64;
+                for x in 0..image_width {
+                    let pixel = image.get_pixel(x, y)[0] as u64;
+                    row_sum += pixel;
+                    row_squared_sum += pixel * pixel;
+                    let index = (y * image_width + x) as usize;
+                    let above = index.checked_sub(image_width as usize);
+                    expected[index] = row_sum + above.map_or(0, |i| expected[i]);
+                    expected_squared[index] =
+                        row_squared_sum + above.map_or(0, |i| expected_squared[i]);
+                }
+            }
+            let segments = [(0, 0, 1, 1, 1.0)];
+            let gpu_pointers = GpuMemoryPointers::new(
+                image_width,
+                image_height,
+                1,
+                1,
+                &data.ocl_queue,
+                &segments,
+                &segments,
+            )
+            .unwrap();
+            for threads in [1, 7, 64, max_threads] {
+                let integral_kernels = IntegralKernels::new(
+                    &data.ocl_program,
+                    &data.ocl_queue,
+                    &gpu_pointers.buffer_image,
+                    &gpu_pointers.buffer_image_integral,
+                    &gpu_pointers.buffer_image_integral_squared,
+                    image_width,
+                    image_height,
+                    threads.min(max_threads),
+                )
+                .unwrap();
+                open_cl::compute_integral_images_ocl(
+                    &gpu_pointers.buffer_image,
+                    &integral_kernels,
+                    &image,
+                )
+                .unwrap();
+                let mut integral = vec![0u64; expected.len()];
+                let mut integral_squared = vec![0u64; expected.len()];
+                gpu_pointers
+                    .buffer_image_integral
+                    .read(&mut integral)
+                    .enq()
+                    .unwrap();
+                gpu_pointers
+                    .buffer_image_integral_squared
+                    .read(&mut integral_squared)
+                    .enq()
+                    .unwrap();
+                assert_eq!(integral, expected, "{image_width}x{image_height}, {threads} threads");
+                assert_eq!(
+                    integral_squared, expected_squared,
+                    "{image_width}x{image_height}, {threads} threads"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fft_shared_spectrum_matches_single_search() {
+        // templates prepared for same size share one image spectrum and find the same locations
+        // as searching with their own transformation
+        let main_image =
+            imgtools::load_image_bw("tests/testing_images/algorithm_tests/Socket_main.png")
+                .unwrap();
+        let (image_width, image_height) = main_image.dimensions();
+        let templates: Vec<FFTData> = ["Socket_template1.png", "Socket_template2.png"]
+            .iter()
+            .map(|name| {
+                let template = imgtools::load_image_bw(&format!(
+                    "tests/testing_images/algorithm_tests/{name}"
+                ))
+                .unwrap();
+                fft_ncc::prepare_template_picture(&template, image_width, image_height)
+            })
+            .collect();
+        let spectrum = fft_ncc::ImageSpectrum::new(&main_image, &templates[0]).unwrap();
+        for template_data in &templates {
+            assert!(spectrum.fits(template_data));
+            let single = fft_ncc::fft_ncc(&main_image, 0.9, template_data);
+            let shared = fft_ncc::fft_ncc_with_spectrum(&spectrum, 0.9, template_data);
+            assert!(!single.is_empty());
+            assert_eq!(single, shared);
+            assert_eq!(
+                fft_ncc::fft_ncc_best_match_with_spectrum(&spectrum, template_data),
+                Some(single[0])
+            );
+        }
+    }
+
+    #[test]
+    fn integral_image_region_sums() {
+        let image =
+            imgtools::load_image_bw("tests/testing_images/algorithm_tests/Socket_template2.png")
+                .unwrap();
+        let mut integral_image = IntegralImage::new(&image);
+        let brute_force = |x: u32, y: u32, width: u32, height: u32| {
+            let mut sum = 0u64;
+            let mut squared_sum = 0u64;
+            for dy in 0..height {
+                for dx in 0..width {
+                    let pixel = image.get_pixel(x + dx, y + dy)[0] as u64;
+                    sum += pixel;
+                    squared_sum += pixel * pixel;
+                }
+            }
+            (sum, squared_sum)
+        };
+        let regions = [
+            (0, 0, image.width(), image.height()),
+            (0, 0, 1, 1),
+            (5, 0, 17, 9),
+            (0, 7, 3, 20),
+            (image.width() - 4, image.height() - 6, 4, 6),
+        ];
+        for (x, y, width, height) in regions {
+            assert_eq!(
+                (
+                    integral_image.sum_region(x, y, width, height),
+                    integral_image.squared_sum_region(x, y, width, height)
+                ),
+                brute_force(x, y, width, height)
+            );
+        }
+        // recomputing for smaller image reuses buffers and gives same sums as new integral image
+        let smaller_image = imgtools::cut_screen_region(3, 2, 40, 30, &image);
+        integral_image.compute(&smaller_image);
+        let new_integral_image = IntegralImage::new(&smaller_image);
+        assert_eq!(
+            integral_image.sum_region(1, 1, 39, 29),
+            new_integral_image.sum_region(1, 1, 39, 29)
+        );
+        assert_eq!(
+            integral_image.sum_region(0, 0, 40, 30),
+            brute_force(3, 2, 40, 30).0
+        );
+    }
+
     fn segmented_run(
         template: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
         main_image: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
@@ -176,6 +511,7 @@ pub mod tmpl_match_tests {
             &main_image,
             &template_data,
             ocl_v,
+            None,
         )
         .unwrap();
         let mut first_location = (0, 0, 0.0);
@@ -193,6 +529,50 @@ pub mod tmpl_match_tests {
         );
     }
 
+    #[cfg(feature = "opencl")]
+    fn dense_ocl_run(
+        template: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+        main_image: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
+        target_positions: (i32, i32),
+    ) {
+        use rustautogui::RustAutoGui;
+
+        let data = RustAutoGui::dev_setup_opencl(Some(1)).unwrap();
+        let (image_width, image_height) = main_image.dimensions();
+        let storage = DenseKernelStorage::new(
+            template,
+            &data.ocl_program,
+            &data.ocl_queue,
+            image_width,
+            image_height,
+            data.ocl_workgroup_size as usize,
+        )
+        .unwrap();
+        let start = std::time::Instant::now();
+        let locations =
+            opencl_dense::gui_opencl_dense_ncc(&storage, 0.95, main_image, None).unwrap();
+        assert!(!locations.is_empty());
+        println!(
+            "OCL dense: Location found at {:?}, time: {}",
+            locations[0],
+            start.elapsed().as_secs_f32()
+        );
+        assert_eq!(
+            (locations[0].0, locations[0].1),
+            (target_positions.0 as u32, target_positions.1 as u32)
+        );
+
+        // only the best position is read back
+        let best = opencl_dense::gui_opencl_dense_best_match(&storage, main_image).unwrap();
+        assert_eq!(best, Some(locations[0]));
+        let top = opencl_dense::gui_opencl_dense_ncc(&storage, 0.95, main_image, Some(1)).unwrap();
+        assert_eq!(top, locations[..1]);
+        // every position passes, so threshold is raised until the best ones fit
+        let all = opencl_dense::gui_opencl_dense_ncc(&storage, -1.0, main_image, None).unwrap();
+        assert!(all.len() <= MAX_COMPACTED_RESULTS);
+        assert_eq!(all[0], locations[0]);
+    }
+
     fn fft_run(
         template: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
         main_image: &image::ImageBuffer<image::Luma<u8>, Vec<u8>>,
@@ -292,6 +672,7 @@ pub mod tmpl_match_tests {
             template_width,
             template_height,
         );
+        dense_ocl_run(&template, &main_image, target_positions);
         fft_run(
             &template,
             &main_image,
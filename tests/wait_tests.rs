@@ -0,0 +1,148 @@
+// run with cargo test --tests --release -- --nocapture
+// wait_until tests need X display and return early when DISPLAY is not set,
+// loop behind them is tested without display through poll_blocking
+
+#[cfg(feature = "dev")]
+mod wait_tests {
+    use rustautogui::core::polling::poll_blocking;
+    use rustautogui::{errors::AutoGuiError, PollingPolicy, RustAutoGui};
+    use std::time::{Duration, Instant};
+
+    fn gui() -> Option<RustAutoGui> {
+        #[cfg(target_os = "linux")]
+        if std::env::var_os("DISPLAY").is_none() {
+            return None;
+        }
+        Some(RustAutoGui::new(false).unwrap())
+    }
+
+    #[test]
+    fn poll_met_on_first_try() {
+        let mut attempts = 0;
+        let start = Instant::now();
+        let result = poll_blocking(
+            &PollingPolicy::fixed(Duration::from_secs(1)),
+            Duration::from_secs(5),
+            true,
+            || {
+                attempts += 1;
+                Ok(Some(attempts))
+            },
+        )
+        .unwrap();
+        assert_eq!(result, Some(1));
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn poll_times_out_without_oversleeping() {
+        let mut attempts = 0;
+        let start = Instant::now();
+        let result: Option<()> = poll_blocking(
+            &PollingPolicy::fixed(Duration::from_millis(100)),
+            Duration::from_millis(250),
+            true,
+            || {
+                attempts += 1;
+                Ok(None)
+            },
+        )
+        .unwrap();
+        let elapsed = start.elapsed();
+        assert!(result.is_none());
+        // attempts at 0, 100, 200 and 250 ms, last sleep is cut to the timeout
+        assert!(elapsed >= Duration::from_millis(250));
+        assert!(elapsed < Duration::from_millis(400));
+        assert_eq!(attempts, 4);
+    }
+
+    #[test]
+    fn poll_stops_on_error() {
+        let mut attempts = 0;
+        let result: Result<Option<()>, AutoGuiError> = poll_blocking(
+            &PollingPolicy::fixed(Duration::from_millis(10)),
+            Duration::from_secs(5),
+            true,
+            || {
+                attempts += 1;
+                if attempts == 2 {
+                    return Err(AutoGuiError::OSFailure("attempt failed".to_string()));
+                }
+                Ok(None)
+            },
+        );
+        assert!(matches!(result, Err(AutoGuiError::OSFailure(_))));
+        assert_eq!(attempts, 2);
+    }
+
+    #[test]
+    fn wait_until_met_on_first_try() {
+        let Some(mut gui) = gui() else { return };
+        let mut evaluations = 0;
+        let start = Instant::now();
+        let met = gui
+            .wait_until(
+                |_| {
+                    evaluations += 1;
+                    Ok(true)
+                },
+                Duration::from_secs(5),
+                Duration::from_secs(1),
+            )
+            .unwrap();
+        assert!(met);
+        assert_eq!(evaluations, 1);
+        // no sleep after successful evaluation
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn wait_until_times_out() {
+        let Some(mut gui) = gui() else { return };
+        let mut evaluations = 0;
+        let start = Instant::now();
+        let met = gui
+            .wait_until(
+                |_| {
+                    evaluations += 1;
+                    Ok(false)
+                },
+                Duration::from_millis(250),
+                Duration::from_millis(100),
+            )
+            .unwrap();
+        let elapsed = start.elapsed();
+        assert!(!met);
+        // last sleep is cut to the timeout instead of sleeping whole interval
+        assert!(elapsed >= Duration::from_millis(250));
+        assert!(elapsed < Duration::from_millis(400));
+        assert!(evaluations >= 3);
+    }
+
+    #[test]
+    fn wait_until_propagates_condition_error() {
+        let Some(mut gui) = gui() else { return };
+        let mut evaluations = 0;
+        let result = gui.wait_until(
+            |_| {
+                evaluations += 1;
+                if evaluations == 2 {
+                    return Err(AutoGuiError::OSFailure("condition failed".to_string()));
+                }
+                Ok(false)
+            },
+            Duration::from_secs(5),
+            Duration::from_millis(10),
+        );
+        assert!(matches!(result, Err(AutoGuiError::OSFailure(_))));
+        // loop stops on the first error
+        assert_eq!(evaluations, 2);
+    }
+
+    #[test]
+    fn wait_until_image_vanishes_without_stored_alias() {
+        let Some(mut gui) = gui() else { return };
+        let result = gui.wait_until_image_vanishes(0.9, Duration::from_millis(100), "missing");
+        assert!(matches!(result, Err(AutoGuiError::AliasError(_))));
+    }
+}
@@ -0,0 +1,23 @@
+// run with cargo test --tests --release -- --nocapture
+// needs X display, tests return early when DISPLAY is not set
+
+#[cfg(target_os = "linux")]
+mod window_tests {
+    use rustautogui::{errors::AutoGuiError, RustAutoGui};
+
+    #[test]
+    fn invalid_window_returns_error() {
+        if std::env::var_os("DISPLAY").is_none() {
+            return;
+        }
+        let gui = RustAutoGui::new(false).unwrap();
+        // BadWindow must be returned as error instead of exiting the process
+        let bogus_window = 0x7FFF_FFF0;
+        assert!(matches!(
+            gui.get_window_size(bogus_window),
+            Err(AutoGuiError::OSFailure(_))
+        ));
+        // display connection is still usable afterwards
+        assert!(gui.get_mouse_position().is_ok());
+    }
+}